@@ -4,6 +4,7 @@ use serde::Serialize;
 use std::error::Error;
 use std::fs::File;
 use std::io::prelude::*;
+use std::str::FromStr;
 use vss::*;
 
 #[derive(Serialize)]
@@ -20,6 +21,18 @@ pub struct OutputInfo {
     pub extension: String,
 }
 
+/// Settings of the `retina-map` subcommand, which writes a generated retina map to disk.
+#[derive(Debug)]
+pub struct RetinaMapExport {
+    pub parameters: ValueMap,
+    /// Width and height of each face.
+    pub resolution: u32,
+    /// Writes a single equirectangular image instead of six faces.
+    pub equirectangular: bool,
+    /// Path without suffix, e.g., `assets/configs/glaucoma` for `glaucoma.retina_pos_x.png`.
+    pub output: String,
+}
+
 #[derive(Debug)]
 pub struct Config {
     pub port: Option<u16>,
@@ -32,7 +45,9 @@ pub struct Config {
     pub parameters_r: Option<ValueMap>,
     pub parameters_l: Option<ValueMap>,
     pub track_perf: u32,
-    pub variance_log: Option<String>,
+    pub pipeline: Pipeline,
+    pub save_config: Option<String>,
+    pub retina_map: Option<RetinaMapExport>,
 }
 
 impl Default for Config {
@@ -48,7 +63,9 @@ impl Default for Config {
             parameters_r: None,
             parameters_l: None,
             track_perf: 0,
-            variance_log: None,
+            pipeline: Pipeline::default(),
+            save_config: None,
+            retina_map: None,
         }
     }
 }
@@ -71,30 +88,96 @@ fn parse_config(arg: &str) -> Result<(ValueMap, String), Box<dyn Error>> {
                 .unwrap(),
         )
     };
-    let object = value.as_object().unwrap();
-
-    let mut map = ValueMap::new();
-    for (key, value) in object.iter() {
-        match value {
-            serde_json::value::Value::Null => {}
-            serde_json::value::Value::Bool(b) => {
-                map.insert(key.to_string(), Value::Bool(*b));
-            }
-            serde_json::value::Value::Number(n) => {
-                map.insert(key.to_string(), Value::Number(n.as_f64().unwrap()));
-            }
-            serde_json::value::Value::String(ref s) => {
-                map.insert(key.to_string(), Value::Image(s.to_string()));
-            }
-            serde_json::value::Value::Array(_) => {}
-            serde_json::value::Value::Object(_) => {}
+    let object = value.as_object().ok_or("Expected a JSON object")?;
+
+    Ok((value_map_from_json(object)?, name))
+}
+
+/// Parses a command-line value, records a problem if it is invalid.
+fn parse_arg<T: FromStr>(name: &str, value: &str, problems: &mut Vec<String>) -> Option<T> {
+    match value.trim().parse::<T>() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            problems.push(format!("--{}: invalid value \"{}\"", name, value));
+            None
+        }
+    }
+}
+
+/// Parses all values of a command-line argument, records a problem if any of them is invalid.
+fn parse_args<T: FromStr>(name: &str, values: clap::Values, problems: &mut Vec<String>) -> Option<Vec<T>> {
+    values.map(|value| parse_arg(name, value, problems)).collect()
+}
+
+/// Collects errors, prints warnings right away.
+fn report_issues(issues: Vec<ConfigIssue>, problems: &mut Vec<String>) {
+    for issue in issues {
+        if issue.is_error() {
+            problems.push(issue.to_string());
+        } else {
+            eprintln!("warning: {}", issue);
         }
     }
-    Ok((map, name))
 }
 
-pub fn cmd_parse() -> Config {
-    use clap::{App, AppSettings, Arg};
+/// Parses and validates a configuration (file or JSON string).
+fn load_config(arg: &str, known: &[Parameter], problems: &mut Vec<String>) -> Option<(ValueMap, String)> {
+    match parse_config(arg) {
+        Ok((values, name)) => {
+            let source = if std::path::Path::new(arg).is_file() { arg } else { "inline JSON" };
+            report_issues(validate_values(&values, known, Some(source)), problems);
+            Some((values, name))
+        }
+        Err(err) => {
+            problems.push(format!("{}: {}", arg, err));
+            None
+        }
+    }
+}
+
+/// Lists all parameters that can be set in configuration files.
+fn parameters_help(registry: &NodeRegistry) -> String {
+    let mut help = "PARAMETERS (of --config files):\n  general:\n".to_string();
+    let mut general = Window::schema();
+    extend_parameters(&mut general, Flow::schema());
+    for parameter in general.iter() {
+        help += &format!("    {}\n", parameter);
+    }
+    for descriptor in registry.descriptors() {
+        if descriptor.parameters.is_empty() {
+            continue;
+        }
+        help += &format!("  {} - {}:\n", descriptor.name, descriptor.description);
+        for parameter in descriptor.parameters.iter() {
+            help += &format!("    {}\n", parameter);
+        }
+    }
+    help
+}
+
+/// Parses the arguments of the `retina-map` subcommand.
+fn parse_retina_map(matches: &clap::ArgMatches, known: &[Parameter], problems: &mut Vec<String>) -> RetinaMapExport {
+    let (parameters, config_name) = load_config(matches.value_of("config").unwrap(), known, problems)
+        .unwrap_or((ValueMap::new(), "Default".to_string()));
+    let resolution = matches
+        .value_of("res")
+        .and_then(|res| parse_arg::<u32>("res", res, problems))
+        .unwrap_or(512);
+    if resolution == 0 || resolution > u16::MAX as u32 {
+        problems.push(format!("--res: invalid value \"{}\"", resolution));
+    }
+    RetinaMapExport {
+        parameters,
+        resolution,
+        equirectangular: matches.is_present("equirectangular"),
+        output: matches.value_of("output").map_or(config_name, |output| output.to_string()),
+    }
+}
+
+pub fn cmd_parse(registry: &NodeRegistry) -> Config {
+    use clap::{App, AppSettings, Arg, SubCommand};
+
+    let parameters_help = parameters_help(registry);
 
     let matches = App::new("Visual System Simulator (VSS)")
         .version("1.1.0")
@@ -115,6 +198,20 @@ pub fn cmd_parse() -> Config {
                 .number_of_values(1)
                 .help("Sets the configuration parameters for simulation"),
         )
+        .arg(
+            Arg::with_name("pipeline")
+                .long("pipeline")
+                .value_name("FILE")
+                .number_of_values(1)
+                .help("Sets the simulation nodes and their order, e.g., assets/pipelines/default.json"),
+        )
+        .arg(
+            Arg::with_name("save_config")
+                .long("save_config")
+                .value_name("FILE")
+                .number_of_values(1)
+                .help("Saves the simulation parameters on exit (per eye, with _right/_left suffix, if needed)"),
+        )
         .arg(
             Arg::with_name("config_right")
                 .long("config_right")
@@ -194,7 +291,7 @@ pub fn cmd_parse() -> Config {
             Arg::with_name("perf")
                 .long("perf")
                 .number_of_values(1)
-                .help("Tracks performance metrics"),
+                .help("Tracks performance metrics for the given number of frames, including the time spent per node"),
         )
         .arg(
             Arg::with_name("variance")
@@ -244,109 +341,184 @@ pub fn cmd_parse() -> Config {
                 .multiple(true)
                 .index(1),
         )
+        .subcommand(
+            SubCommand::with_name("retina-map")
+                .about("Writes the retina map generated for a configuration, e.g., to hand-edit it")
+                .arg(
+                    Arg::with_name("config")
+                        .long("config")
+                        .short("c")
+                        .value_name("FILE|JSON")
+                        .number_of_values(1)
+                        .required(true)
+                        .help("Sets the configuration parameters, e.g., assets/configs/glaucoma-medium.json"),
+                )
+                .arg(
+                    Arg::with_name("res")
+                        .long("res")
+                        .value_name("SIZE")
+                        .number_of_values(1)
+                        .help("Sets the width and height of each face [default: 512]"),
+                )
+                .arg(
+                    Arg::with_name("equirectangular")
+                        .long("equirectangular")
+                        .takes_value(false)
+                        .help("Writes a single equirectangular image instead of six faces"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .value_name("PATH")
+                        .number_of_values(1)
+                        .help(
+                            "Sets the output path without suffix [default: name of the configuration], e.g.:\n\
+                            \x20\x20\"assets/configs/glaucoma\" writes assets/configs/glaucoma.retina_pos_x.png, ...",
+                        ),
+                ),
+        )
+        .after_help(parameters_help.as_str())
+        .setting(AppSettings::SubcommandsNegateReqs)
         .setting(AppSettings::ArgRequiredElseHelp)
         .setting(AppSettings::UnifiedHelpMessage)
         .get_matches();
 
     let default = Config::default();
 
+    // All problems are collected and reported at once, before anything is set up.
+    let mut problems = Vec::new();
+    let mut known = Window::schema();
+    extend_parameters(&mut known, Flow::schema());
+    extend_parameters(&mut known, registry.parameters());
+
+    if let Some(retina_map_matches) = matches.subcommand_matches("retina-map") {
+        let retina_map = parse_retina_map(retina_map_matches, &known, &mut problems);
+        if !problems.is_empty() {
+            for problem in problems.iter() {
+                eprintln!("error: {}", problem);
+            }
+            std::process::exit(1);
+        }
+        return Config {
+            retina_map: Some(retina_map),
+            ..default
+        };
+    }
+
     let port = if let Some(port_str) = matches.value_of("port") {
-        Some(port_str.parse::<u16>().expect("Invalid port"))
+        parse_arg::<u16>("port", port_str, &mut problems)
     } else {
         default.port
     };
 
-    let (mut parameters, config_name) = if let Some(config_str) = matches.value_of("config") {
-        parse_config(config_str).expect("Invalid config")
-    } else {
-        (default.parameters, "Default".to_string())
-    };
+    let (mut parameters, config_name) = matches
+        .value_of("config")
+        .and_then(|config_str| load_config(config_str, &known, &mut problems))
+        .unwrap_or((default.parameters, "Default".to_string()));
 
-    let mut parameters_r = if let Some(config_right_str) = matches.value_of("config_right") {
-        Some(parse_config(config_right_str).expect("Invalid config").0)
-    } else {
-        None
-    };
-    let mut parameters_l = if let Some(config_left_str) = matches.value_of("config_left") {
-        Some(parse_config(config_left_str).expect("Invalid config").0)
-    } else {
-        None
-    };
+    let mut parameters_r = matches
+        .value_of("config_right")
+        .and_then(|config_right_str| load_config(config_right_str, &known, &mut problems))
+        .map(|(values, _)| values);
+    let mut parameters_l = matches
+        .value_of("config_left")
+        .and_then(|config_left_str| load_config(config_left_str, &known, &mut problems))
+        .map(|(values, _)| values);
+
+    // Parameters set by command-line arguments.
+    let mut arguments = ValueMap::new();
 
     if let Some(base_image) = matches.value_of("base_image") {
-        let base_image = base_image.parse::<u16>().expect("Invalid base_image") as f64;
-        parameters.insert("file_base_image".to_string(), Value::Number(base_image));
+        if let Some(base_image) = parse_arg::<u16>("base_image", base_image, &mut problems) {
+            arguments.insert("file_base_image".to_string(), Value::Number(base_image as f64));
+        }
     }
     if let Some(mix_type) = matches.value_of("mix_type") {
-        let mix_type = mix_type.parse::<u16>().expect("Invalid mix_type") as f64;
-        parameters.insert("file_mix_type".to_string(), Value::Number(mix_type));
+        if let Some(mix_type) = parse_arg::<u16>("mix_type", mix_type, &mut problems) {
+            arguments.insert("file_mix_type".to_string(), Value::Number(mix_type as f64));
+        }
     }
     if let Some(cm_type) = matches.value_of("cm_type") {
-        let cm_type = cm_type.parse::<u16>().expect("Invalid cm_type") as f64;
-        parameters.insert("file_cm_type".to_string(), Value::Number(cm_type));
+        if let Some(cm_type) = parse_arg::<u16>("cm_type", cm_type, &mut problems) {
+            arguments.insert("file_cm_type".to_string(), Value::Number(cm_type as f64));
+        }
     }
     if let Some(cf) = matches.value_of("cf") {
-        let cf = cf.parse::<u16>().expect("Invalid cf") as f64;
-        parameters.insert("file_cf".to_string(), Value::Number(cf));
+        if let Some(cf) = parse_arg::<u16>("cf", cf, &mut problems) {
+            arguments.insert("file_cf".to_string(), Value::Number(cf as f64));
+        }
     }
 
     let track_perf = matches
         .value_of("perf")
-        .map(|v| v.parse::<u32>())
-        .unwrap_or(Ok(0u32))
+        .and_then(|perf| parse_arg::<u32>("perf", perf, &mut problems))
         .unwrap_or(0);
 
     if let Some(variance) = matches.values_of("variance") {
-        let variance = variance
-            .map(|t| t.trim().parse::<f64>().unwrap())
-            .collect::<Vec<f64>>();
-        parameters.insert("measure_variance".to_string(), Value::Number(variance[0]));
-        parameters.insert("variance_metric".to_string(), Value::Number(variance[1]));
-        parameters.insert("variance_color_space".to_string(), Value::Number(variance[2]));
+        if let Some(variance) = parse_args::<f64>("variance", variance, &mut problems) {
+            arguments.insert("measure_variance".to_string(), Value::Number(variance[0]));
+            arguments.insert("variance_metric".to_string(), Value::Number(variance[1]));
+            arguments.insert("variance_color_space".to_string(), Value::Number(variance[2]));
+        }
     };
     
-    let variance_log = if let Some(variance_log) = matches.value_of("variance_log") {
-        Some(variance_log.to_string())
+    if let Some(variance_log) = matches.value_of("variance_log") {
+        arguments.insert("variance_log".to_string(), Value::String(variance_log.to_string()));
+    }
+
+    let pipeline = if let Some(pipeline_path) = matches.value_of("pipeline") {
+        match Pipeline::from_file(pipeline_path).and_then(|pipeline| pipeline.validate(registry).map(|_| pipeline)) {
+            Ok(pipeline) => {
+                for node in pipeline.nodes.iter() {
+                    let source = format!("{} ({})", pipeline_path, node.name);
+                    report_issues(validate_values(&node.values, &known, Some(&source)), &mut problems);
+                }
+                pipeline
+            }
+            Err(err) => {
+                problems.push(format!("{}: {}", pipeline_path, err));
+                default.pipeline
+            }
+        }
     } else {
-        None
+        default.pipeline
     };
     
     if let Some(rays) = matches.value_of("rays") {
-        let rays = rays.parse::<f64>().expect("Invalid rays");
-        parameters.insert("rays".to_string(), Value::Number(rays));
+        if let Some(rays) = parse_arg::<f64>("rays", rays, &mut problems) {
+            arguments.insert("rays".to_string(), Value::Number(rays));
+        }
     }
 
     if let Some(cm_scale) = matches.value_of("cm_scale") {
-        let cm_scale = cm_scale.parse::<f64>().expect("Invalid cm_scale");
-        parameters.insert("cm_scale".to_string(), Value::Number(cm_scale));
-    }
-    if let Some(vis_type) = matches.value_of("vis_type") {
-        let vis_type = vis_type.parse::<u16>().expect("Invalid vis_type") as f64;
-        parameters.insert("file_vis_type".to_string(), Value::Number(vis_type));
-        // if let Some(parameters_r) = &mut parameters_r{
-        //     parameters_r.insert("file_vis_type".to_string(), Value::Number(vis_type));
-        // }
-        // if let Some(parameters_l) = &mut parameters_l{
-        //     parameters_l.insert("file_vis_type".to_string(), Value::Number(vis_type));
-        // }
+        if let Some(cm_scale) = parse_arg::<f64>("cm_scale", cm_scale, &mut problems) {
+            arguments.insert("cm_scale".to_string(), Value::Number(cm_scale));
+        }
     }
 
     if let Some(gaze) = matches.values_of("gaze") {
-        let gaze = gaze
-            .map(|t| t.trim().parse::<f64>().unwrap())
-            .collect::<Vec<f64>>();
-        parameters.insert("gaze_x".to_string(), Value::Number(gaze[0]));
-        parameters.insert("gaze_y".to_string(), Value::Number(gaze[1]));
+        if let Some(gaze) = parse_args::<f64>("gaze", gaze, &mut problems) {
+            arguments.insert("gaze_x".to_string(), Value::Number(gaze[0]));
+            arguments.insert("gaze_y".to_string(), Value::Number(gaze[1]));
+        }
     };
 
     if let Some(view) = matches.values_of("view") {
-        let view = view
-            .map(|t| t.trim().parse::<f64>().unwrap())
-            .collect::<Vec<f64>>();
-        parameters.insert("view_x".to_string(), Value::Number(view[0]));
-        parameters.insert("view_y".to_string(), Value::Number(view[1]));
+        if let Some(view) = parse_args::<f64>("view", view, &mut problems) {
+            arguments.insert("view_x".to_string(), Value::Number(view[0]));
+            arguments.insert("view_y".to_string(), Value::Number(view[1]));
+        }
     };
 
+    // Arguments are known by construction, only check their values.
+    let issues = validate_values(&arguments, &known, Some("command line"))
+        .into_iter()
+        .filter(|issue| issue.is_error())
+        .collect();
+    report_issues(issues, &mut problems);
+    parameters.extend(arguments);
+
     let (merged_parameters_r, merged_parameters_l) =
         match (parameters_r.clone(), parameters_l.clone()) {
             (Some(mut parameters_r), Some(mut parameters_l)) => {
@@ -364,10 +536,9 @@ pub fn cmd_parse() -> Config {
 
     let mut resolution = default.resolution;
     if let Some(res) = matches.values_of("res") {
-        let res = res
-            .map(|t| t.trim().parse::<f64>().unwrap())
-            .collect::<Vec<f64>>();
-        resolution = Some([res[0] as u32, res[1] as u32]);
+        if let Some(res) = parse_args::<u32>("res", res, &mut problems) {
+            resolution = Some([res[0], res[1]]);
+        }
     };
 
     let mut visible = default.visible;
@@ -381,7 +552,13 @@ pub fn cmd_parse() -> Config {
         } else {
             "{{dirname}}/{{stem}}_{{configname}}.{{extension}}"
         };
-        Some(mustache::compile_str(output).unwrap())
+        match mustache::compile_str(output) {
+            Ok(output) => Some(output),
+            Err(err) => {
+                problems.push(format!("--output: {}", err));
+                None
+            }
+        }
     } else {
         visible = true;
         default.output
@@ -393,7 +570,8 @@ pub fn cmd_parse() -> Config {
         .flat_map(|pattern| {
             if let Ok(entries) = glob(pattern) {
                 entries
-                    .map(|entry| entry.unwrap().into_os_string().into_string().unwrap())
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.to_string_lossy().into_owned())
                     .collect::<Vec<String>>()
             } else {
                 vec![pattern.to_string()]
@@ -401,6 +579,13 @@ pub fn cmd_parse() -> Config {
         })
         .collect();
 
+    if !problems.is_empty() {
+        for problem in problems.iter() {
+            eprintln!("error: {}", problem);
+        }
+        std::process::exit(1);
+    }
+
     Config {
         port,
         visible,
@@ -412,6 +597,8 @@ pub fn cmd_parse() -> Config {
         parameters_r,
         parameters_l,
         track_perf,
-        variance_log,
+        pipeline,
+        save_config: matches.value_of("save_config").map(|path| path.to_string()),
+        retina_map: None,
     }
 }
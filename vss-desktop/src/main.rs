@@ -9,7 +9,6 @@ mod openxr;
 use std::cell::RefCell;
 use std::time::Instant;
 use std::fs;
-use std::fs::OpenOptions;
 use vss::*;
 
 use crate::cmd::*;
@@ -42,24 +41,45 @@ impl IoGenerator {
     }
 
     #[cfg(feature = "varjo")]
-    fn next(&mut self, window: &Window, render_resolution: Option<[u32; 2]>) -> Option<IoNodePair> {
+    fn next(&mut self, window: &Window, render_resolution: Option<[u32; 2]>) -> Result<Option<IoNodePair>, NodeError> {
         self.input_idx += 1;
         self.current(&window, render_resolution,0)
     }
 
-    fn current(&mut self, window: &Window, render_resolution: Option<[u32; 2]>, flow_index: usize) -> Option<IoNodePair> {
+    fn current(&mut self, window: &Window, render_resolution: Option<[u32; 2]>, flow_index: usize) -> Result<Option<IoNodePair>, NodeError> {
         if self.input_idx >= self.inputs.len() {
-            None
+            Ok(None)
         } else {
             let input = &self.inputs[self.input_idx];
             if UploadRgbBuffer::has_image_extension(&input) {
                 let input_path = std::path::Path::new(input);
-                let mut input_node = UploadRgbBuffer::new(&window);
-                input_node.upload_image(load(input_path));
+                let mut input_node = UploadRgbBuffer::new(&window)?;
+                let data = try_load(input_path).map_err(|e| NodeError::Resource(format!("{}: {}", input, e)))?;
+                input_node.upload_image(data);
                 input_node.set_flags(RgbInputFlags::from_extension(&input));
                 input_node.set_render_resolution(render_resolution);
+                let output_node = self.current_output(window, flow_index)?;
+                Ok(Some((Box::new(input_node), output_node)))
+            } else if UploadVideo::has_video_extension(&input) {
+                let mut input_node = UploadVideo::new(&window)?;
+                input_node.set_flags(RgbInputFlags::from_extension(&input));
+                input_node
+                    .open(input)
+                    .map_err(|e| NodeError::Resource(format!("{}: {}", input, e)))?;
+                Ok(Some((Box::new(input_node), None)))
+            } else {
+                Err(NodeError::Resource(format!("{}: unknown file extension", input)))
+            }
+        }
+    }
+
+    /// Creates the node that writes the simulated image of the current input, if any.
+    fn current_output(&self, window: &Window, flow_index: usize) -> Result<Option<Box<dyn Node>>, NodeError> {
+        match self.inputs.get(self.input_idx) {
+            Some(input) if UploadRgbBuffer::has_image_extension(&input) => {
+                let input_path = std::path::Path::new(input);
                 let output_node = if let Some(output) = &self.output {
-                    let mut output_node = DownloadRgbBuffer::new(&window);
+                    let mut output_node = DownloadRgbBuffer::new(&window)?;
                     let output_info = OutputInfo {
                         configname: self.config_name.clone(),
                         dirname: input_path
@@ -95,66 +115,102 @@ impl IoGenerator {
                 } else {
                     None
                 };
-                Some((Box::new(input_node), output_node))
-            } else if UploadVideo::has_video_extension(&input) {
-                let mut input_node = UploadVideo::new(&window);
-                input_node.set_flags(RgbInputFlags::from_extension(&input));
-                input_node.open(input).unwrap();
-                Some((Box::new(input_node), None))
-            } else {
-                panic!("Unknown file extension");
+                Ok(output_node)
             }
+            _ => Ok(None),
         }
     }
 }
 
-fn build_flow(window: &mut Window, io_generator: &mut IoGenerator, flow_index: usize, render_resolution: Option<[u32; 2]>, variance_log: Option<String>){
-    let (input_node, output_node) = io_generator.current(&window, render_resolution, flow_index).unwrap();
+/// Reports errors that prevent the simulation from being set up and exits.
+fn exit_on_error<T, E: std::fmt::Display>(result: Result<T, E>) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    })
+}
+
+/// Writes the retina map generated for the given configuration, see the `retina-map` subcommand.
+fn export_retina_map(export: &RetinaMapExport) -> Result<(), String> {
+    let settings = RetinaMapSettings::from_values(&export.parameters)?;
+    let faces = generate_retina_images(export.resolution, &settings);
+    let mut images = Vec::new();
+    if export.equirectangular {
+        let image = equirectangular_retina_image(&faces, export.resolution * 4);
+        images.push((format!("{}.retina.png", export.output), image));
+    } else {
+        for (face, name) in faces.into_iter().zip(CUBEMAP_FACE_NAMES.iter()) {
+            images.push((format!("{}.retina_{}.png", export.output, name), face));
+        }
+    }
+    for (path, image) in images {
+        image.save(&path).map_err(|e| format!("{}: {}", path, e))?;
+        println!("Wrote {}", path);
+    }
+    Ok(())
+}
+
+fn build_flow(window: &mut Window, io_generator: &mut IoGenerator, flow_index: usize, render_resolution: Option<[u32; 2]>, pipeline: &Pipeline, registry: &NodeRegistry){
+    let (input_node, output_node) = exit_on_error(io_generator.current(&window, render_resolution, flow_index)).unwrap();
 
     // Add input node.
     window.add_node(input_node, flow_index);
 
-    // Visual system passes.
-    let node = Cataract::new(&window);
-    window.add_node(Box::new(node), flow_index);
-    let node = Lens::new(&window);
-    window.add_node(Box::new(node), flow_index);
-    let node = Retina::new(&window);
-    window.add_node(Box::new(node), flow_index);
-    let node = PeacockCB::new(&window);
-    window.add_node(Box::new(node), flow_index);
-    
-    // Measure Uncertainty
-    let mut node = VarianceMeasure::new(&window);
-    if variance_log.is_some(){
-        node.log_file = Some(OpenOptions::new()
-            .write(true)
-            .append(true)
-            .create(true)
-            .open(variance_log.unwrap())
-            .unwrap());
-    }
-    window.add_node(Box::new(node), flow_index);
-    
+    // Visual system passes (and display).
+    exit_on_error(pipeline.build(window, registry, flow_index));
+
     // Add output node, if present.
     if let Some(output_node) = output_node {
-        // Display node.
-        let node = Display::new(&window);
-        window.add_node(Box::new(node), flow_index);
         window.add_node(output_node, flow_index);
-    } else {
-        // window.add_node(Box::new(Passthrough::new(&window)), flow_index)
-        // Display node.
-        let node = Display::new(&window);
-        window.add_node(Box::new(node), flow_index);
     }
+}
 
+/// Builds a flow that simulates both eyes (right eye first) and shows one of them.
+#[cfg(not(feature = "varjo"))]
+fn build_stereo_flow(window: &mut Window, io_generator: &mut IoGenerator, render_resolution: Option<[u32; 2]>, pipeline: &Pipeline, registry: &NodeRegistry){
+    let (input_node, output_node) = exit_on_error(io_generator.current(&window, render_resolution, 0)).unwrap();
 
+    // Add input node, which is shared by both eyes.
+    window.add_node(input_node, 0);
+
+    // Visual system passes (and display), per eye.
+    exit_on_error(pipeline.build_stereo(window, registry, 0));
+
+    // Add output nodes, if present.
+    if let Some(output_node) = output_node {
+        let left_output_node = exit_on_error(io_generator.current_output(&window, 1)).unwrap();
+        window.add_node(Box::new(PerEye::from_nodes(output_node, left_output_node)), 0);
+    }
+
+    let node = exit_on_error(StereoDesktop::new(&window));
+    window.add_node(Box::new(node), 0);
+}
+
+/// Values of a stereo flow, i.e., the common values plus the values of each eye.
+#[cfg(not(feature = "varjo"))]
+fn stereo_values(parameters: &ValueMap, parameters_r: ValueMap, parameters_l: ValueMap) -> ValueMap {
+    let mut value_map = parameters.clone();
+    for (eye, eye_values) in vec![parameters_r, parameters_l].into_iter().enumerate() {
+        let mut eye_values = eye_values;
+        eye_values.insert("flow_id".into(), Value::Number(eye as f64));
+        value_map.insert(EYE_VALUES[eye].into(), Value::Object(eye_values));
+    }
+    value_map.insert("flow_id".into(), Value::Number(0.0));
+    // Field losses after a stroke, e.g., must be the same in both eyes.
+    for warning in share_binocular_values(&mut value_map) {
+        eprintln!("warning: {}", warning);
+    }
+    value_map
 }
 
 #[cfg(not(any(feature = "varjo", feature = "openxr")))]
 pub fn main() {
-    let config = cmd_parse();
+    let registry = NodeRegistry::default();
+    let config = cmd_parse(&registry);
+    if let Some(retina_map) = &config.retina_map {
+        exit_on_error(export_retina_map(retina_map));
+        return;
+    }
 
     // let remote = if let Some(port) = config.port {
     //     Some(Remote::new(port))
@@ -162,63 +218,38 @@ pub fn main() {
     //     None
     // };
     
-    let flow_count = match (config.parameters_r.clone(), config.parameters_l.clone()) {
-        (Some(_), Some(_)) => 2 , 
-        _ => 1
+ 
+    // Both eyes are simulated by a single flow, if configured separately.
+    let value_map = match (config.parameters_r.clone(), config.parameters_l.clone()) {
+        (Some(param_r), Some(param_l)) => stereo_values(&config.parameters, param_r, param_l),
+        _ => {
+            let mut value_map = config.parameters.clone();
+            value_map.insert("flow_id".into(),Value::Number(0.0));
+            value_map
+        }
     };
+    let is_stereo = value_map.contains_key(EYE_VALUES[0]);
 
-    let mut parameters = Vec::new();
-    for idx in 0 .. flow_count {
-        let mut value_map = ValueMap::new();
-        let iter = match (config.parameters_r.clone(), config.parameters_l.clone()) {
-            (Some(param_r), Some(param_l)) =>{
-                if idx == 0 {
-                    param_r.into_iter()
-                }
-                else{
-                    param_l.into_iter()
-                }
-            }
-            _ => {
-                config.parameters.clone().into_iter()
-            }
-        };
-        for (key, val) in iter {
-            value_map.insert((key).clone(), (val).clone());
-        }  
-        value_map.insert("flow_id".into(),Value::Number(idx as f64));
-        parameters.push(RefCell::new(value_map));
-    }
-
-    let mut window = Window::new(config.visible, None, parameters, flow_count);
+    let mut window = exit_on_error(Window::new(config.visible, None, vec![RefCell::new(value_map)], 1));
 
     let is_output_hack_used = config.output.is_some();
 
-    // let viewports = vec![
-    //     (0, 0, 640, 360),
-    //     (640, 0, 640, 360)
-    // ];
-
-    let mut desktop = SharedStereoDesktop::new();
-
-    for index in 0..flow_count {
-        let mut io_generator = IoGenerator::new(
-            config.inputs.clone(),
-            config.name.clone(),
-            config.output.clone(),
-        );
-        build_flow(&mut window, &mut io_generator, index, config.resolution, config.variance_log.clone());
-
-        if flow_count > 1 {
-            let node = desktop.get_stereo_desktop_node(&window);
-            window.add_node(Box::new(node), index);
-        }
+    let mut io_generator = IoGenerator::new(
+        config.inputs.clone(),
+        config.name.clone(),
+        config.output.clone(),
+    );
+    if is_stereo {
+        build_stereo_flow(&mut window, &mut io_generator, config.resolution, &config.pipeline, &registry);
+    } else {
+        build_flow(&mut window, &mut io_generator, 0, config.resolution, &config.pipeline, &registry);
     }
-    
+
 
     let mut done = false;
-    window.update_last_node();
-    window.update_nodes();
+    exit_on_error(window.update_last_node());
+    window.set_profiling(config.track_perf > 0);
+    exit_on_error(window.update_nodes());
 
     let mut frame_counter = 0u128; // you know, for the simulations than run longer than the universe exists
 
@@ -290,12 +321,64 @@ pub fn main() {
     // this async behaviour stems from the callback used in the download buffer
     if config.track_perf > 0 {
         dump_perf_data(frame_times);
+        dump_node_perf_data(&window);
+    }
+    if let Some(save_config) = &config.save_config {
+        save_values(&window, save_config);
     }
     use std::{thread, time};
     let a_second = time::Duration::from_secs(1);
     thread::sleep(a_second);
 }
 
+fn save_values(window: &Window, path: &str) {
+    let values = window.values(0);
+    let eyes: Vec<ValueMap> = EYE_VALUES
+        .iter()
+        .filter_map(|key| values.get(*key).and_then(Value::as_object))
+        .map(|eye_values| {
+            let mut merged: ValueMap = values
+                .iter()
+                .filter(|(key, _)| !EYE_VALUES.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            merged.extend(eye_values.iter().map(|(key, value)| (key.clone(), value.clone())));
+            merged
+        })
+        .collect();
+    if eyes.is_empty() {
+        match value_map_to_json(&values) {
+            Ok(json) => {
+                if let Err(e) = fs::write(path, json) {
+                    println!("save_values error {:?}", e);
+                }
+            }
+            Err(e) => eprintln!("error: {}: {}", path, e),
+        }
+        return;
+    }
+    for (index, eye_values) in eyes.iter().enumerate() {
+        // Same order as in main, i.e., right eye first.
+        let path = std::path::Path::new(path);
+        let eye = if index == 0 { "right" } else { "left" };
+        let file_name = format!(
+            "{}_{}.{}",
+            path.file_stem().and_then(|s| s.to_str()).unwrap_or("config"),
+            eye,
+            path.extension().and_then(|s| s.to_str()).unwrap_or("json")
+        );
+        let path = path.with_file_name(file_name);
+        match value_map_to_json(eye_values) {
+            Ok(json) => {
+                if let Err(e) = fs::write(&path, json) {
+                    println!("save_values error {:?}", e);
+                }
+            }
+            Err(e) => eprintln!("error: {}: {}", path.display(), e),
+        }
+    }
+}
+
 fn dump_perf_data(frame_times: Vec<(u128,u128,(u32,u32),u32,u32)>){
     match fs::write("vss_perf_data.csv", 
         frame_times.iter()
@@ -310,7 +393,12 @@ fn dump_perf_data(frame_times: Vec<(u128,u128,(u32,u32),u32,u32)>){
 #[cfg(feature = "openxr")]
 pub fn main() {
     let mut oxr = openxr::OpenXR::new();
-    let config = cmd_parse();
+    let registry = NodeRegistry::default();
+    let config = cmd_parse(&registry);
+    if let Some(retina_map) = &config.retina_map {
+        exit_on_error(export_retina_map(retina_map));
+        return;
+    }
 
     let remote = if let Some(port) = config.port {
         Some(Remote::new(port))
@@ -318,32 +406,13 @@ pub fn main() {
         None
     };
     
-    let flow_count = 2;
-
-    let mut parameters = Vec::new();
-    for idx in 0 .. flow_count {
-        let mut value_map = ValueMap::new();
-        let iter = match (config.parameters_r.clone(), config.parameters_l.clone()) {
-            (Some(param_r), Some(param_l)) =>{
-                if idx == 0 {
-                    param_r.into_iter()
-                }
-                else{
-                    param_l.into_iter()
-                }
-            }
-            _ => {
-                config.parameters.clone().into_iter()
-            }
-        };
-        for (key, val) in iter {
-            value_map.insert((key).clone(), (val).clone());
-        }  
-        value_map.insert("flow_id".into(),Value::Number(idx as f64));
-        parameters.push(RefCell::new(value_map));
-    }
+    // Both eyes are simulated by a single flow.
+    let value_map = match (config.parameters_r.clone(), config.parameters_l.clone()) {
+        (Some(param_r), Some(param_l)) => stereo_values(&config.parameters, param_r, param_l),
+        _ => stereo_values(&config.parameters, config.parameters.clone(), config.parameters.clone()),
+    };
 
-    let mut window = Window::new(config.visible, remote, parameters, flow_count);
+    let mut window = exit_on_error(Window::new(config.visible, remote, vec![RefCell::new(value_map)], 1));
 
     
     dbg!("Pre init");
@@ -353,24 +422,16 @@ pub fn main() {
 
 
 
-    let mut desktop = SharedStereoDesktop::new();
-
-    for index in 0..flow_count {
-        let mut io_generator = IoGenerator::new(
-            config.inputs.clone(),
-            config.name.clone(),
-            config.output.clone(),
-        );
-
-        build_flow(&mut window, &mut io_generator, index, config.resolution);
-        let mut node = desktop.get_stereo_desktop_node(&window);
-
-        window.add_node(Box::new(node), index);
-    }
+    let mut io_generator = IoGenerator::new(
+        config.inputs.clone(),
+        config.name.clone(),
+        config.output.clone(),
+    );
+    build_stereo_flow(&mut window, &mut io_generator, config.resolution, &config.pipeline, &registry);
 
 
     let mut done = false;
-    window.update_last_node();
+    exit_on_error(window.update_last_node());
 
     oxr.create_session(&window);
     oxr.create_render_targets(&window);
@@ -412,7 +473,12 @@ pub fn set_varjo_data(window: &mut Window, last_fov: &mut Vec<(f32, f32)>, varjo
 
 #[cfg(feature = "varjo")]
 pub fn main() {
-    let config = cmd_parse();
+    let registry = NodeRegistry::default();
+    let config = cmd_parse(&registry);
+    if let Some(retina_map) = &config.retina_map {
+        exit_on_error(export_retina_map(retina_map));
+        return;
+    }
 
     let remote = if let Some(port) = config.port {
         Some(Remote::new(port))
@@ -430,7 +496,7 @@ pub fn main() {
         parameters.push(RefCell::new(value_map));
     }
 
-    let mut window = Window::new(config.visible, remote, parameters, flow_count);
+    let mut window = exit_on_error(Window::new(config.visible, remote, parameters, flow_count));
 
     let mut varjo = varjo::Varjo::new();
     let mut log_counter = 0; //TODO: used to reduce log spam, remove when no longer needed or replace with a better solution
@@ -441,8 +507,8 @@ pub fn main() {
 
     for index in 0 .. flow_count {
         let viewport = &varjo_viewports[index];
-        build_flow(&mut window, &mut io_generator, index, Some([viewport.width, viewport.height]), None);
-        let mut node = VRCompositor::new(&window);
+        build_flow(&mut window, &mut io_generator, index, Some([viewport.width, viewport.height]), &config.pipeline, &registry);
+        let mut node = exit_on_error(VRCompositor::new(&window));
         node.set_viewport(viewport.x as f32, viewport.y as f32, viewport.width as f32, viewport.height as f32);
         window.add_node(Box::new(node), index);
     }
@@ -457,7 +523,7 @@ pub fn main() {
             set_varjo_data(&mut window, &mut varjo_fov, &mut varjo);
         }
         
-        window.update_last_node();
+        exit_on_error(window.update_last_node());
         
         done = window.poll_events();
 
@@ -467,15 +533,15 @@ pub fn main() {
         }
 
         if io_generator.is_ready() {
-            if let Some((input_node, output_node)) = io_generator.next(&window, None) {
+            if let Some((input_node, output_node)) = exit_on_error(io_generator.next(&window, None)) {
                 window.replace_node(0, input_node, 0);
                 let output_node = if let Some(output_node) = output_node {
                     output_node
                 } else {
-                    Box::new(Passthrough::new(&window))
+                    Box::new(exit_on_error(Passthrough::new(&window)))
                 };
                 window.replace_node(window.nodes_len() - 2, output_node, 0);
-                window.update_nodes();
+                exit_on_error(window.update_nodes());
             } else {
                 if !config.visible {
                     // Exit once all inputs have been processed, unless visible.
@@ -485,3 +551,14 @@ pub fn main() {
         }
     }
 }
+
+fn dump_node_perf_data(window: &Window) {
+    println!("{:<16} {:>8} {:>12} {:>12} {:>17}", "node", "frames", "gpu ms", "render ms", "update ms (total)");
+    for timing in window.timings(0) {
+        println!("{}", timing);
+    }
+    match fs::write("vss_perf_nodes.csv", window.performance_report()) {
+        Err(e) => println!("dump_node_perf_data error {:?}", e),
+        _ => ()
+    }
+}
@@ -202,10 +202,10 @@ impl UploadVideo {
 }
 
 impl Node for UploadVideo {
-    fn new(window: &Window) -> Self {
-        let mut uploader = UploadRgbBuffer::new(window);
+    fn new(window: &Window) -> Result<Self, NodeError> {
+        let mut uploader = UploadRgbBuffer::new(window)?;
         uploader.set_flags(RgbInputFlags::VERTICALLY_FLIPPED);
-        Self {
+        Ok(Self {
             upload_start: None,
             uploader,
             next_pts: -1.0,
@@ -218,14 +218,18 @@ impl Node for UploadVideo {
             video_decoder: None,
             #[cfg(feature = "video")]
             video_scaler: None,
-        }
+        })
     }
 
-    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> NodeSlots {
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
         self.validate_data();
         self.uploader.negociate_slots(window, slots)
     }
 
+    fn output_channels(&self) -> Vec<ChannelInfo> {
+        self.uploader.output_channels()
+    }
+
     fn update_values(&mut self, window: &Window, values: &ValueMap) {
         self.uploader.update_values(window, values);
     }
@@ -1,10 +1,12 @@
 use crate::*;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use cgmath::{Matrix4};
 use cgmath::Vector3;
-use gfx::format::Rgba32F;
 use cgmath::Rad;
+use std::fmt;
 use std::ops::Mul;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 /// Represents properties of eye-tracking data.
 #[derive(Debug, Clone)]
@@ -15,12 +17,82 @@ pub struct EyePerspective {
     pub gaze: Vector3<f32>,
 }
 
+/// Identifier that refers to the first node of a [Flow], usually the node that provides the input image.
+pub const FLOW_INPUT: &str = "input";
+
+/// Rotation of the eye axis, as configured by `eye_axis_rot_x` and `eye_axis_rot_y`.
+pub fn eye_axis_view(values: &ValueMap) -> Matrix4<f32> {
+    let mut configured_view = Matrix4::from_scale(1.0);
+    // if the eye has strabism, it needs some angle offset
+    if let Some(Value::Number(eye_axis_rot_x)) = values.get("eye_axis_rot_x") {
+        configured_view = configured_view.mul(Matrix4::from_angle_x(Rad(*eye_axis_rot_x as f32)));
+    }
+    if let Some(Value::Number(eye_axis_rot_y)) = values.get("eye_axis_rot_y") {
+        configured_view = configured_view.mul(Matrix4::from_angle_y(Rad(*eye_axis_rot_y as f32)));
+    }
+    configured_view
+}
+
+/// Reasons why the nodes of a [Flow] cannot be set up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlowError {
+    /// The flow has no nodes.
+    Empty,
+    /// A node failed (with its position in the flow).
+    Node { index: usize, error: NodeError },
+    /// A node reads from an identifier that no preceding node has.
+    UnknownInput { index: usize, id: String },
+}
+
+impl fmt::Display for FlowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlowError::Empty => write!(f, "flow has no nodes"),
+            FlowError::Node { index, error } => write!(f, "node at position {}: {}", index, error),
+            FlowError::UnknownInput { index, id } => {
+                write!(f, "node at position {} reads from unknown node \"{}\"", index, id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FlowError {}
+
+/// Where a node of a [Flow] reads from.
+struct NodeLinks {
+    /// Identifier other nodes can read from.
+    id: Option<String>,
+    /// Positions of the nodes whose outputs are read, the first one is the main input.
+    inputs: Vec<usize>,
+}
+
 /// A flow encapsulates simulation nodes, i.e., all simulation and rendering.
+///
+/// By default, each node reads the output of its predecessor. Nodes with an identifier can be read by any
+/// later node, such that flows form directed acyclic graphs (e.g., a simulated and an unmodified
+/// branch that are merged for comparison). Nodes are rendered in the order they were added.
+///
+/// Nodes with an identifier can be bypassed (see the `bypass` value), except for the first and
+/// the last node. Bypassed nodes are neither negociated nor rendered, their input is passed on.
 pub struct Flow {
     nodes: RefCell<Vec<Box<dyn Node>>>,
+    node_values: RefCell<Vec<ValueMap>>,
+    links: RefCell<Vec<NodeLinks>>,
+    bypassed: RefCell<Vec<bool>>,
+    /// Whether nodes were bypassed or restored since the flow was negociated.
+    outdated: Cell<bool>,
     last_slot: RefCell<Option<NodeSlots>>,
+    pool: Rc<RefCell<TexturePool>>,
+    /// Channels requested from each node when the flow was negociated.
+    requested: RefCell<Vec<Vec<ChannelInfo>>>,
+    /// Whether rendering is timed per node, see [Flow::set_profiling].
+    profiling: Cell<bool>,
+    profiler: RefCell<Option<Profiler>>,
+    /// CPU time spent in [Node::update_values], per node.
+    update_times: RefCell<Vec<Duration>>,
     pub last_perspective: RefCell<EyePerspective>,
-    well_known: WellKnownSlots,
+    /// Perspectives that replace `last_perspective` for single eyes, see [Flow::set_eye_perspective].
+    eye_perspectives: RefCell<Vec<Option<EyePerspective>>>,
     configured_view: RefCell<Matrix4<f32>>,
 }
 
@@ -28,173 +100,421 @@ impl Flow {
     pub fn new() -> Self {
         Flow {
             nodes: RefCell::new(Vec::new()),
+            node_values: RefCell::new(Vec::new()),
+            links: RefCell::new(Vec::new()),
+            bypassed: RefCell::new(Vec::new()),
+            outdated: Cell::new(false),
             last_slot: RefCell::new(None),
+            pool: Rc::new(RefCell::new(TexturePool::new())),
+            requested: RefCell::new(Vec::new()),
+            profiling: Cell::new(false),
+            profiler: RefCell::new(None),
+            update_times: RefCell::new(Vec::new()),
             last_perspective: RefCell::new(EyePerspective {
                 position: Vector3::new(0.0, 0.0, 0.0),
                 view: Matrix4::from_scale(1.0),
                 proj: cgmath::perspective(cgmath::Deg(70.0), 1.0, 0.05, 1000.0),
                 gaze: Vector3::new(0.0, 0.0, 1.0),
             }),
-            well_known: WellKnownSlots::new(),
+            eye_perspectives: RefCell::new(vec![None; EYES]),
             configured_view:  RefCell::new(Matrix4::from_scale(1.0)),
         }
     }
 
     pub fn add_node(&mut self, node: Box<dyn Node>) {
+        self.add_configured_node(node, ValueMap::new());
+    }
+
+    /// Adds a node with values that override the flow's values for this node only.
+    pub fn add_configured_node(&mut self, node: Box<dyn Node>, values: ValueMap) {
+        self.add_linked_node(node, values, None, &[])
+            .expect("Reading from the predecessor never fails");
+    }
+
+    /// Adds a node that can be read by later nodes using `id` and that reads the nodes identified
+    /// by `inputs` (the predecessor, if empty). Inputs after the first one are auxiliary inputs.
+    pub fn add_linked_node(
+        &mut self,
+        node: Box<dyn Node>,
+        values: ValueMap,
+        id: Option<&str>,
+        inputs: &[String],
+    ) -> Result<(), FlowError> {
+        let index = self.nodes.borrow().len();
+        let inputs = if inputs.is_empty() {
+            index.checked_sub(1).into_iter().collect()
+        } else {
+            inputs
+                .iter()
+                .map(|input| self.find_node(input, index))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        self.links.borrow_mut().push(NodeLinks {
+            id: id.map(|id| id.to_string()),
+            inputs,
+        });
         self.nodes.borrow_mut().push(node);
+        self.node_values.borrow_mut().push(values);
+        self.bypassed.borrow_mut().push(false);
+        self.update_times.borrow_mut().push(Duration::default());
+        Ok(())
+    }
+
+    /// Position of the latest node with the given identifier, before position `index`.
+    fn find_node(&self, id: &str, index: usize) -> Result<usize, FlowError> {
+        let links = self.links.borrow();
+        links
+            .iter()
+            .rposition(|links| links.id.as_deref() == Some(id))
+            .or_else(|| if id == FLOW_INPUT && index > 0 { Some(0) } else { None })
+            .ok_or_else(|| FlowError::UnknownInput {
+                index,
+                id: id.to_string(),
+            })
     }
 
     pub fn replace_node(&mut self, index: usize, node: Box<dyn Node>) {
         self.nodes.borrow_mut()[index] = node;
     }
 
+    /// Describes the values read by this flow itself (not by its nodes).
+    pub fn schema() -> Vec<Parameter> {
+        vec![
+            Parameter::number("eye_axis_rot_x", "rad", (-3.2, 3.2), 0.0, "Vertical deviation of the eye axis (strabismus)"),
+            Parameter::number("eye_axis_rot_y", "rad", (-3.2, 3.2), 0.0, "Horizontal deviation of the eye axis (strabismus)"),
+            Parameter::list("bypass", "Identifiers of nodes that are skipped, e.g., to show their contribution"),
+        ]
+    }
+
+    /// Describes the values read by this flow and all of its nodes.
+    pub fn parameters(&self) -> Vec<Parameter> {
+        let mut parameters = Self::schema();
+        for node in self.nodes.borrow().iter() {
+            extend_parameters(&mut parameters, node.parameters());
+        }
+        parameters
+    }
+
     pub fn nodes_len(&self) -> usize {
         self.nodes.borrow().len()
     }
+
+    /// Identifiers of the nodes that can be bypassed, in order.
+    pub fn bypassable(&self) -> Vec<String> {
+        let links = self.links.borrow();
+        links
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.can_bypass(*index))
+            .filter_map(|(_, links)| links.id.clone())
+            .collect()
+    }
+
+    fn can_bypass(&self, index: usize) -> bool {
+        let links = self.links.borrow();
+        links[index].id.is_some() && !links[index].inputs.is_empty() && index + 1 < links.len()
+    }
+
+    pub fn is_bypassed(&self, index: usize) -> bool {
+        self.bypassed.borrow()[index]
+    }
+
+    /// Bypasses the node at the given position (or renders it again), takes effect once negociated.
+    pub fn set_bypassed(&self, index: usize, bypassed: bool) {
+        let bypassed = bypassed && self.can_bypass(index);
+        let previous = std::mem::replace(&mut self.bypassed.borrow_mut()[index], bypassed);
+        if previous != bypassed {
+            self.outdated.set(true);
+        }
+    }
     
-    pub fn update_last_slot(&self, window: &Window) {
-        if self.last_slot.borrow().is_some() {
-            let output = self.last_slot.borrow_mut().as_mut().unwrap().take_output();
-            let suggested_slot = 
-                NodeSlots::new_io(
-                    window,
-                    self.last_slot.borrow_mut().as_mut().unwrap().take_input(),
-                    match output {
-                        Slot::Rgb{
-                            color: _,
-                            color_view,
-                            deflection,
-                            deflection_view,
-                            color_change,
-                            color_change_view,
-                            color_uncertainty,
-                            color_uncertainty_view,
-                            covariances,
-                            covariances_view
-                        } => Slot::Rgb {
-                            color: window.target(),
-                            color_view,
-                            deflection,
-                            deflection_view,
-                            color_change, 
-                            color_change_view, 
-                            color_uncertainty, 
-                            color_uncertainty_view,
-                            covariances,
-                            covariances_view
+    /// Times each node while rendering, which flushes commands after every node.
+    pub fn set_profiling(&self, enabled: bool) {
+        self.profiling.set(enabled);
+    }
+
+    /// Time spent in each node, named by identifier or position (e.g., `#2`).
+    pub fn timings(&self) -> Vec<NodeTiming> {
+        let mut timings = match self.profiler.borrow().as_ref() {
+            Some(profiler) => profiler.timings().to_vec(),
+            None => self
+                .node_names()
+                .into_iter()
+                .map(|name| NodeTiming {
+                    name,
+                    ..NodeTiming::default()
+                })
+                .collect(),
+        };
+        for (timing, update) in timings.iter_mut().zip(self.update_times.borrow().iter()) {
+            timing.update = *update;
+        }
+        timings
+    }
+
+    fn node_names(&self) -> Vec<String> {
+        let links = self.links.borrow();
+        links
+            .iter()
+            .enumerate()
+            .map(|(index, links)| links.id.clone().unwrap_or_else(|| format!("#{}", index)))
+            .collect()
+    }
+
+    pub fn update_last_slot(&self, window: &Window) -> Result<(), FlowError> {
+        let last_slot = self.last_slot.borrow_mut().take();
+        if let Some(mut last_slot) = last_slot {
+            // Render to the window's (possibly new) target.
+            let output = match last_slot.take_output() {
+                Slot::Image(mut channels) if !channels.contains_key(CHANNEL_DEPTH) => {
+                    channels.insert(
+                        CHANNEL_COLOR,
+                        Channel::Color {
+                            target: window.target(),
+                            view: None,
                         },
-                        _ => Slot::Empty,
-                    },
-                );
+                    );
+                    Slot::Image(channels)
+                }
+                _ => Slot::Empty,
+            };
+            let suggested_slot = last_slot.with_output(output);
             // Negociate and swap.
-            let new_last_slot = self.nodes.borrow_mut().last_mut().unwrap().negociate_slots_wk(window, suggested_slot, &self.well_known);
+            let mut nodes = self.nodes.borrow_mut();
+            let index = nodes.len().checked_sub(1).ok_or(FlowError::Empty)?;
+            let new_last_slot = nodes[index]
+                .negociate_slots(window, suggested_slot)
+                .map_err(|error| FlowError::Node { index, error })?;
             self.last_slot.replace(Some(new_last_slot));
+            Ok(())
         }else{
-            self.negociate_slots(window);
+            self.negociate_slots(window)
+        }
+    }
+
+    /// Channels that succeeding nodes read from the output of each node.
+    fn requested_channels(&self) -> Vec<Vec<ChannelInfo>> {
+        let nodes = self.nodes.borrow();
+        let links = self.links.borrow();
+        let mut requested: Vec<Vec<ChannelInfo>> = vec![Vec::new(); nodes.len()];
+        let bypassed = self.bypassed.borrow();
+        for (idx, node) in nodes.iter().enumerate().rev() {
+            // What the node reads, and what it updates or passes on.
+            let mut read = if bypassed[idx] { Vec::new() } else { node.input_channels() };
+            read.extend(requested[idx].iter().copied());
+            if let Some(input) = links[idx].inputs.first() {
+                for info in read {
+                    if !requested[*input].contains(&info) {
+                        requested[*input].push(info);
+                    }
+                }
+            }
         }
+        requested
     }
 
-    pub fn negociate_slots(&self, window: &Window) {
-        let mut slot_a = NodeSlots::new(window);
-        let mut slot_b = NodeSlots::new(window);
+    /// Whether nodes were bypassed or restored, or whether nodes read other channels than when the
+    /// flow was negociated (see [Node::input_channels]).
+    pub fn needs_negociation(&self) -> bool {
+        self.outdated.get() || *self.requested.borrow() != self.requested_channels()
+    }
+
+    /// Negociates the slots of all nodes, stopping at the first node that fails.
+    ///
+    /// Outputs only contain channels that are read by succeeding nodes. Their targets are taken from
+    /// the flow's [TexturePool] and released once they are not read anymore (for efficiency).
+    pub fn negociate_slots(&self, window: &Window) -> Result<(), FlowError> {
         let nodes_len = self.nodes.borrow().len();
-        for (idx, node) in self.nodes.borrow_mut().iter_mut().enumerate() {
+        if nodes_len == 0 {
+            return Err(FlowError::Empty);
+        }
+        let mut requested = self.requested_channels();
+        self.requested.replace(requested.clone());
+        self.outdated.set(false);
+        self.pool.borrow_mut().reclaim();
+        let links = self.links.borrow();
+        let bypassed = self.bypassed.borrow();
 
-            let suggested_slot = if idx + 1 == nodes_len {
-                // Suggest window as final output.
-                let mut factory = window.factory().borrow_mut();
+        // Position of the last node that reads each output.
+        let mut last_read: Vec<Option<usize>> = vec![None; nodes_len];
+        for (idx, links) in links.iter().enumerate() {
+            for input in links.inputs.iter() {
+                last_read[*input] = Some(idx);
+            }
+        }
 
-                let (width, height, ..) = window.target().get_dimensions();
+        let mut outputs: Vec<Slot> = Vec::with_capacity(nodes_len);
+        let mut last_slot = None;
+        for (idx, node) in self.nodes.borrow_mut().iter_mut().enumerate() {
+            let mut inputs = links[idx].inputs.iter().map(|input| outputs[*input].clone());
+            let input = inputs.next().unwrap_or_default();
+            let aux_inputs: Vec<Slot> = inputs.collect();
+            if bypassed[idx] {
+                // Pass on the input, which provides the channels requested from this node as well.
+                outputs.push(input);
+                self.release_inputs(idx, &links, &last_read, &outputs);
+                continue;
+            }
 
-                let (deflection, deflection_view) = create_texture_render_target::<Rgba32F>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-                let (color_change, color_change_view) = create_texture_render_target::<Rgba32F>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-                let (color_uncertainty, color_uncertainty_view) = create_texture_render_target::<Rgba32F>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-                let (covariances, covariances_view) = create_texture_render_target::<Rgba32F>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
+            let output_slot = if idx + 1 == nodes_len {
+                // Suggest window as final output.
+                let mut channels = Channels::new();
+                channels.insert(
+                    CHANNEL_COLOR,
+                    Channel::Color {
+                        target: window.target(),
+                        view: None,
+                    },
                 );
-
-                drop(factory);
-
-                let output_slot = Slot::Rgb {
-                    color: window.target(),
-                    color_view: None,
-                    deflection,
-                    deflection_view,
-                    color_change, 
-                    color_change_view, 
-                    color_uncertainty, 
-                    color_uncertainty_view,
-                    covariances,
-                    covariances_view
-                };
-
-                NodeSlots::new_io(
-                    window,
-                    slot_b.take_output(),
-                    output_slot
-                )
+                Slot::Image(channels)
             } else {
-                // Suggest reusing output of the pre-predecessor.
-                NodeSlots::new_io(window, slot_b.take_output(), slot_a.take_output())
+                Slot::Empty
             };
-            // Negociate and swap.
-            slot_a = node.negociate_slots_wk(window, suggested_slot, &self.well_known);
-            std::mem::swap(&mut slot_a, &mut slot_b);
+            let suggested_slot = NodeSlots::new_io(window, input, output_slot)
+                .with_aux_inputs(aux_inputs)
+                .with_channels(node.output_channels(), std::mem::take(&mut requested[idx]))
+                .with_pool(self.pool.clone());
+
+            let slots = node
+                .negociate_slots(window, suggested_slot)
+                .map_err(|error| FlowError::Node { index: idx, error })?;
+            outputs.push(slots.output().clone());
+
+            self.release_inputs(idx, &links, &last_read, &outputs);
+            last_slot = Some(slots);
+        }
+        self.pool.borrow_mut().trim();
+        self.last_slot.replace(last_slot);
+        Ok(())
+    }
+
+    /// Releases targets of outputs read for the last time by the node at `idx`, unless they are still
+    /// shared with other outputs.
+    fn release_inputs(&self, idx: usize, links: &[NodeLinks], last_read: &[Option<usize>], outputs: &[Slot]) {
+        let mut pool = self.pool.borrow_mut();
+        for input in links[idx].inputs.iter().filter(|input| last_read[**input] == Some(idx)) {
+            let still_read = |other: usize| last_read[other].map_or(other == idx, |last| last > idx);
+            for channel in outputs[*input].channels() {
+                let shared = (0..=idx)
+                    .filter(|other| other != input && still_read(*other))
+                    .any(|other| outputs[other].contains_target(channel));
+                if !shared {
+                    pool.release(channel);
+                }
+            }
         }
-        self.last_slot.replace(Some(slot_b));
     }
 
     pub fn update_values(&self, window: &Window, values: &ValueMap) {
         let mut perspective = self.last_perspective.borrow_mut();
-        let mut configured_view = Matrix4::from_scale(1.0);
-        // if the eye has strabism, it needs some angle offset
-        if let Some(Value::Number(eye_axis_rot_x)) = values.get("eye_axis_rot_x") {
-            
-            configured_view = configured_view.mul(Matrix4::from_angle_x(Rad(*eye_axis_rot_x as f32)));
-        }
-        if let Some(Value::Number(eye_axis_rot_y)) = values.get("eye_axis_rot_y") {
-            configured_view = configured_view.mul(Matrix4::from_angle_y(Rad(*eye_axis_rot_y as f32)));
-        }
+        let configured_view = eye_axis_view(values);
 
         perspective.view = configured_view.mul(perspective.view );
 
         self.configured_view.replace(configured_view);
 
-        // Propagate to nodes.
-        for node in self.nodes.borrow_mut().iter_mut() {
-            node.update_values(window, &values);
+        let bypass = match values.get("bypass") {
+            Some(Value::Array(ids)) => ids.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        for index in 0..self.nodes_len() {
+            let id = self.links.borrow()[index].id.clone();
+            self.set_bypassed(index, id.map_or(false, |id| bypass.contains(&id.as_str())));
         }
+
+        // Propagate to nodes, with node-specific overrides.
+        let node_values = self.node_values.borrow();
+        let mut update_times = self.update_times.borrow_mut();
+        for ((node, overrides), update_time) in self
+            .nodes
+            .borrow_mut()
+            .iter_mut()
+            .zip(node_values.iter())
+            .zip(update_times.iter_mut())
+        {
+            let start = Instant::now();
+            if overrides.is_empty() {
+                node.update_values(window, &values);
+            } else {
+                let mut merged = values.clone();
+                merged.extend(overrides.iter().map(|(key, value)| (key.clone(), value.clone())));
+                node.update_values(window, &merged);
+            }
+            *update_time += start.elapsed();
+        }
+    }
+
+    /// Sets the perspective of all eyes.
+    pub fn set_perspective(&self, perspective: EyePerspective) {
+        self.last_perspective.replace(perspective);
+        self.eye_perspectives.replace(vec![None; EYES]);
+    }
+
+    /// Sets the perspective of a single eye (right eye first), e.g., for a stereo flow with a view,
+    /// projection, and gaze per eye (see [PerEye]).
+    pub fn set_eye_perspective(&self, eye: usize, perspective: EyePerspective) {
+        self.eye_perspectives.borrow_mut()[eye] = Some(perspective);
     }
 
     pub fn input(&self, vis_param: &VisualizationParameters) {
+        let configured_view = *self.configured_view.borrow();
         let mut perspective = self.last_perspective.borrow().clone();
-        perspective.view = self.configured_view.borrow().mul(perspective.view );
+        let eyes: Vec<EyePerspective> = self
+            .eye_perspectives
+            .borrow()
+            .iter()
+            .map(|eye| {
+                let mut eye = eye.as_ref().unwrap_or(&perspective).clone();
+                eye.view = configured_view.mul(eye.view);
+                eye
+            })
+            .collect();
+        perspective.view = configured_view.mul(perspective.view );
 
         // Propagate to nodes.
         for node in self.nodes.borrow_mut().iter_mut().rev() {
-            perspective = node.input( &perspective, vis_param);
+            perspective = node.input_eyes(&perspective, &eyes, vis_param);
         }
         //self.last_perspective.replace(perspective);
     }
 
     pub fn render(&self, window: &Window) {
+        if self.profiling.get() {
+            self.render_profiled(window);
+            return;
+        }
         // Render all nodes.
-        for node in self.nodes.borrow_mut().iter_mut() {
-            node.render(window);
+        let bypassed = self.bypassed.borrow();
+        for (node, bypassed) in self.nodes.borrow_mut().iter_mut().zip(bypassed.iter()) {
+            if !bypassed {
+                node.render(window);
+            }
+        }
+    }
+
+    fn render_profiled(&self, window: &Window) {
+        let mut profiler = self.profiler.borrow_mut();
+        if profiler.as_ref().map_or(true, |profiler| profiler.nodes_len() != self.nodes_len()) {
+            let mut rebuilt = Profiler::new(window, self.node_names());
+            if let Some(previous) = profiler.take() {
+                rebuilt.resume(previous.timings());
+            }
+            *profiler = Some(rebuilt);
+        }
+        let profiler = profiler.as_mut().unwrap();
+
+        // Flush any pending commands, such that they are not attributed to the first node.
+        window.flush(&mut window.encoder().borrow_mut());
+        let bypassed = self.bypassed.borrow();
+        for (index, (node, bypassed)) in self.nodes.borrow_mut().iter_mut().zip(bypassed.iter()).enumerate() {
+            if !bypassed {
+                profiler.begin_node(window, index);
+                let start = Instant::now();
+                node.render(window);
+                window.flush(&mut window.encoder().borrow_mut());
+                profiler.end_node(window, index, start.elapsed());
+            }
         }
+        profiler.end_frame(window);
     }
 }
@@ -5,18 +5,28 @@ extern crate bitflags;
 
 mod flow;
 mod node;
+mod parameter;
+mod pipeline;
+mod profiler;
 mod remote;
+mod simulator;
 mod texture;
 mod utils;
+mod validation;
 mod value;
 mod window;
 mod vis_param;
 
 pub use self::flow::*;
 pub use self::node::*;
+pub use self::parameter::*;
+pub use self::pipeline::*;
+pub use self::profiler::*;
 pub use self::remote::*;
+pub use self::simulator::*;
 pub use self::texture::*;
 pub use self::utils::*;
+pub use self::validation::*;
 pub use self::value::*;
 pub use self::window::*;
 pub use self::window::*;
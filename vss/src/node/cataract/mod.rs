@@ -30,28 +30,27 @@ pub struct Cataract {
 }
 
 impl Node for Cataract {
-    fn new(window: &Window) -> Self {
+    fn new(window: &Window) -> Result<Self, NodeError> {
         let mut factory = window.factory().borrow_mut();
         let pso = factory
             .create_pipeline_simple(
                 &include_glsl!("../mod.vert"),
                 &include_glsl!("mod.frag"),
                 pipe::new(),
-            )
-            .unwrap();
+            )?;
         let sampler = factory.create_sampler_linear();
-        let (_, color_view) = load_texture_from_bytes(&mut factory, &[0; 4], 1, 1).unwrap();
+        let (_, color_view) = load_texture_from_bytes(&mut factory, &[0; 4], 1, 1).map_err(NodeError::Resource)?;
         let (_, depth_view) =
-            load_single_channel_texture_from_bytes(&mut factory, &[0; 4], 1, 1).unwrap();
-        let (_, _, rt_color) = factory.create_render_target(1, 1).unwrap();
-        let (_, _, rt_depth) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_deflection, rt_deflection) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_color_change, rt_color_change) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_color_uncertainty, rt_color_uncertainty) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_covariances, rt_covariances) = factory.create_render_target(1, 1).unwrap();
+            load_single_channel_texture_from_bytes(&mut factory, &[0; 4], 1, 1).map_err(NodeError::Resource)?;
+        let (_, _, rt_color) = factory.create_render_target(1, 1)?;
+        let (_, _, rt_depth) = factory.create_render_target(1, 1)?;
+        let (_, s_deflection, rt_deflection) = factory.create_render_target(1, 1)?;
+        let (_, s_color_change, rt_color_change) = factory.create_render_target(1, 1)?;
+        let (_, s_color_uncertainty, rt_color_uncertainty) = factory.create_render_target(1, 1)?;
+        let (_, s_covariances, rt_covariances) = factory.create_render_target(1, 1)?;
 
 
-        Cataract {
+        Ok(Cataract {
             pso,
             pso_data: pipe::Data {
                 u_active: 0,
@@ -72,37 +71,48 @@ impl Node for Cataract {
                 rt_covariances,
                 u_track_error: 0
             },
-        }
+        })
+    }
+
+    fn schema() -> Vec<Parameter> {
+        vec![
+            Parameter::toggle("ct_onoff", "Enables the cataract simulation"),
+            Parameter::number("ct_blur_factor", "%", (0.0, 100.0), 0.0, "Amount of blur caused by the clouded lens"),
+            Parameter::number("ct_contrast_factor", "%", (0.0, 100.0), 0.0, "Loss of contrast caused by the clouded lens"),
+        ]
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        Self::schema()
+    }
+
+    fn output_channels(&self) -> Vec<ChannelInfo> {
+        ERROR_CHANNELS.to_vec()
     }
 
-    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> NodeSlots {
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
         let slots = slots
-            .to_color_depth_input(window)
-            .to_color_depth_output(window);
-        self.pso_data.u_resolution = slots.output_size_f32();
-        let (color_view, depth_view) = slots.as_color_depth_view();
+            .to_color_depth_input(window)?
+            .to_color_depth_output(window)?;
+        self.pso_data.u_resolution = slots.output_size_f32()?;
+        let (color_view, depth_view) = slots.as_color_depth_view()?;
 
 
         self.pso_data.s_color = color_view;
         self.pso_data.s_depth = depth_view;
-        let (color, depth) = slots.as_color_depth();
+        let (color, depth) = slots.as_color_depth()?;
         self.pso_data.rt_color = color;
         self.pso_data.rt_depth = depth;
-        self.pso_data.s_deflection = slots.as_deflection_view();
-        self.pso_data.rt_deflection = slots.as_deflection();
-        self.pso_data.s_color_change = slots.as_color_change_view();
-        self.pso_data.rt_color_change = slots.as_color_change();  
-        self.pso_data.s_color_uncertainty = slots.as_color_uncertainty_view();
-        self.pso_data.rt_color_uncertainty = slots.as_color_uncertainty();
-        self.pso_data.s_covariances = slots.as_covariances_view();
-        self.pso_data.rt_covariances = slots.as_covariances();
+        self.pso_data.s_deflection = slots.as_deflection_view()?;
+        self.pso_data.rt_deflection = slots.as_deflection()?;
+        self.pso_data.s_color_change = slots.as_color_change_view()?;
+        self.pso_data.rt_color_change = slots.as_color_change()?;  
+        self.pso_data.s_color_uncertainty = slots.as_color_uncertainty_view()?;
+        self.pso_data.rt_color_uncertainty = slots.as_color_uncertainty()?;
+        self.pso_data.s_covariances = slots.as_covariances_view()?;
+        self.pso_data.rt_covariances = slots.as_covariances()?;
 
-        slots
-    }
-    fn negociate_slots_wk(&mut self, window: &Window, slots: NodeSlots, well_known: &WellKnownSlots) -> NodeSlots{
-        let slots = self.negociate_slots(window, slots);
-        well_known.set_original(slots.as_color_depth_view().0);
-        slots
+        Ok(slots)
     }
 
     fn update_values(&mut self, _window: &Window, values: &ValueMap) {
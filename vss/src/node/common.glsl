@@ -189,4 +189,25 @@ void applyBloom(inout vec3 color, in float blur_factor, inout mat3 S) {
 
     float bloom_factor = 1. + brightness *  c;
     color = color * bloom_factor;
-}
\ No newline at end of file
+}
+
+// Position on the visual field in degrees (azimuthal equidistant projection, fovea at -z), see
+// retina/retina_map/perimetry.rs.
+vec2 field_position(vec3 direction) {
+    if (length(direction.xy) == 0.0) {
+        return vec2(0.0);
+    }
+    float angle = acos(clamp(dot(vec3(0.0, 0.0, -1.0), direction), -1.0, 1.0));
+    float azimuth = atan(direction.y, direction.x);
+    return degrees(angle) * vec2(cos(azimuth), sin(azimuth));
+}
+
+// Direction of a position on the visual field in degrees, the inverse of field_position.
+vec3 field_direction(vec2 position) {
+    if (length(position) == 0.0) {
+        return vec3(0.0, 0.0, -1.0);
+    }
+    float eccentricity = radians(length(position));
+    float azimuth = atan(position.y, position.x);
+    return vec3(sin(eccentricity) * vec2(cos(azimuth), sin(azimuth)), -cos(eccentricity));
+}
@@ -0,0 +1,44 @@
+uniform int u_track_error;
+uniform int u_mode;
+uniform float u_position;
+
+uniform sampler2D s_color;
+uniform sampler2D s_reference;
+uniform sampler2D s_deflection;
+uniform sampler2D s_color_change;
+uniform sampler2D s_color_uncertainty;
+uniform sampler2D s_covariances;
+
+in vec2 v_tex;
+
+out vec4 rt_color;
+out vec4 rt_deflection;
+out vec4 rt_color_change;
+out vec4 rt_color_uncertainty;
+out vec4 rt_covariances;
+
+void main() {
+    vec4 color = texture(s_color, v_tex);
+    vec4 reference = texture(s_reference, v_tex);
+
+    if (u_mode == 0) {
+        // Split screen, reference on the right.
+        rt_color = v_tex.x < u_position ? color : reference;
+        if (abs(v_tex.x - u_position) < 0.001) {
+            rt_color = vec4(1.0);
+        }
+    } else if (u_mode == 1) {
+        // Blend towards the reference.
+        rt_color = mix(color, reference, u_position);
+    } else {
+        // Absolute difference.
+        rt_color = vec4(abs(color.rgb - reference.rgb), 1.0);
+    }
+
+    if (u_track_error == 1) {
+        rt_deflection = texture(s_deflection, v_tex);
+        rt_color_change = texture(s_color_change, v_tex);
+        rt_color_uncertainty = texture(s_color_uncertainty, v_tex);
+        rt_covariances = texture(s_covariances, v_tex);
+    }
+}
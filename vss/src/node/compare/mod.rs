@@ -0,0 +1,125 @@
+use super::*;
+use gfx;
+use gfx::format::Rgba32F;
+
+gfx_defines! {
+    pipeline pipe {
+        u_track_error: gfx::Global<i32> = "u_track_error",
+        u_mode: gfx::Global<i32> = "u_mode",
+        u_position: gfx::Global<f32> = "u_position",
+        s_color: gfx::TextureSampler<[f32; 4]> = "s_color",
+        s_reference: gfx::TextureSampler<[f32; 4]> = "s_reference",
+        rt_color: gfx::RenderTarget<ColorFormat> = "rt_color",
+        s_deflection: gfx::TextureSampler<[f32; 4]> = "s_deflection",
+        rt_deflection: gfx::RenderTarget<Rgba32F> = "rt_deflection",
+        s_color_change: gfx::TextureSampler<[f32; 4]> = "s_color_change",
+        rt_color_change: gfx::RenderTarget<Rgba32F> = "rt_color_change",
+        s_color_uncertainty: gfx::TextureSampler<[f32; 4]> = "s_color_uncertainty",
+        rt_color_uncertainty: gfx::RenderTarget<Rgba32F> = "rt_color_uncertainty",
+        s_covariances: gfx::TextureSampler<[f32; 4]> = "s_covariances",
+        rt_covariances: gfx::RenderTarget<Rgba32F> = "rt_covariances",
+    }
+}
+
+/// A node that merges two branches of a flow, e.g., the simulation and the unmodified input.
+///
+/// Reads its input and the first auxiliary input (the reference).
+pub struct Compare {
+    pso: gfx::PipelineState<Resources, pipe::Meta>,
+    pso_data: pipe::Data<Resources>,
+}
+
+impl Node for Compare {
+    fn new(window: &Window) -> Result<Self, NodeError> {
+        let mut factory = window.factory().borrow_mut();
+
+        let pso = factory
+            .create_pipeline_simple(
+                &include_glsl!("../mod.vert"),
+                &include_glsl!("mod.frag"),
+                pipe::new(),
+            )?;
+
+        let sampler = factory.create_sampler_linear();
+        let (_, color_view) = load_texture_from_bytes(&mut factory, &[0; 4], 1, 1).map_err(NodeError::Resource)?;
+        let (_, _, rt_color) = factory.create_render_target(1, 1)?;
+        let (_, s_deflection, rt_deflection) = factory.create_render_target(1, 1)?;
+        let (_, s_color_change, rt_color_change) = factory.create_render_target(1, 1)?;
+        let (_, s_color_uncertainty, rt_color_uncertainty) = factory.create_render_target(1, 1)?;
+        let (_, s_covariances, rt_covariances) = factory.create_render_target(1, 1)?;
+
+        Ok(Compare {
+            pso,
+            pso_data: pipe::Data {
+                u_track_error: 0,
+                u_mode: 0,
+                u_position: 0.5,
+                s_color: (color_view.clone(), sampler.clone()),
+                s_reference: (color_view, sampler.clone()),
+                rt_color,
+                s_deflection: (s_deflection, sampler.clone()),
+                rt_deflection,
+                s_color_change: (s_color_change, sampler.clone()),
+                rt_color_change,
+                s_color_uncertainty: (s_color_uncertainty, sampler.clone()),
+                rt_color_uncertainty,
+                s_covariances: (s_covariances, sampler.clone()),
+                rt_covariances,
+            },
+        })
+    }
+
+    fn schema() -> Vec<Parameter> {
+        vec![
+            Parameter::integer("compare_mode", (0.0, 2.0), 0.0, "Comparison with the reference (0: split screen, 1: blend, 2: difference)"),
+            Parameter::number("compare_position", "", (0.0, 1.0), 0.5, "Position of the split or weight of the reference when blending"),
+        ]
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        Self::schema()
+    }
+
+    fn output_channels(&self) -> Vec<ChannelInfo> {
+        ERROR_CHANNELS.to_vec()
+    }
+
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
+        let slots = slots
+            .to_color_input(window)?
+            .to_color_output(window)?;
+
+        self.pso_data.s_color = slots.as_color_view()?;
+        self.pso_data.s_reference = slots.as_aux_color_view(0)?;
+        self.pso_data.rt_color = slots.as_color()?;
+        self.pso_data.s_deflection = slots.as_deflection_view()?;
+        self.pso_data.rt_deflection = slots.as_deflection()?;
+        self.pso_data.s_color_change = slots.as_color_change_view()?;
+        self.pso_data.rt_color_change = slots.as_color_change()?;
+        self.pso_data.s_color_uncertainty = slots.as_color_uncertainty_view()?;
+        self.pso_data.rt_color_uncertainty = slots.as_color_uncertainty()?;
+        self.pso_data.s_covariances = slots.as_covariances_view()?;
+        self.pso_data.rt_covariances = slots.as_covariances()?;
+
+        Ok(slots)
+    }
+
+    fn update_values(&mut self, _window: &Window, values: &ValueMap) {
+        if let Some(Value::Number(mode)) = values.get("compare_mode") {
+            self.pso_data.u_mode = *mode as i32;
+        }
+        if let Some(Value::Number(position)) = values.get("compare_position") {
+            self.pso_data.u_position = *position as f32;
+        }
+    }
+
+    fn input(&mut self, perspective: &EyePerspective, vis_param: &VisualizationParameters) -> EyePerspective {
+        self.pso_data.u_track_error = vis_param.has_to_track_error() as i32;
+        perspective.clone()
+    }
+
+    fn render(&mut self, window: &Window) {
+        let mut encoder = window.encoder().borrow_mut();
+        encoder.draw(&gfx::Slice::from_vertex_count(6), &self.pso, &self.pso_data);
+    }
+}
@@ -65,7 +65,7 @@ pub struct Display {
 }
 
 impl Node for Display {
-    fn new(window: &window::Window) -> Self {
+    fn new(window: &window::Window) -> Result<Self, NodeError> {
         let mut factory = window.factory().borrow_mut();
 
         let pso = factory
@@ -73,54 +73,52 @@ impl Node for Display {
                 &include_glsl!("mod.vert"),
                 &include_glsl!("mod.frag"),
                 pipe::new(),
-            )
-            .unwrap();
+            )?;
         
         let gui_pso = factory
             .create_pipeline_simple(
                 &include_glsl!("gui.vert"),
                 &include_glsl!("gui.frag"),
                 gui_pipe::new(),
-            )
-            .unwrap();
+            )?;
 
         let sampler = factory.create_sampler_linear();
-        let (_, src, dst) = factory.create_render_target(1, 1).unwrap();
+        let (_, src, dst) = factory.create_render_target(1, 1)?;
 
         // add deflection view
         let (_, s_deflection, _): (
             _,
             _,
             gfx::handle::RenderTargetView<gfx_device_gl::Resources, [f32; 4]>,
-        ) = factory.create_render_target(1, 1).unwrap();
+        ) = factory.create_render_target(1, 1)?;
         let (_, s_color_change, _): (
             _,
             _,
             gfx::handle::RenderTargetView<gfx_device_gl::Resources, [f32; 4]>,
-        ) = factory.create_render_target(1, 1).unwrap();
+        ) = factory.create_render_target(1, 1)?;
         let (_, s_color_uncertainty, _): (
             _,
             _,
             gfx::handle::RenderTargetView<gfx_device_gl::Resources, [f32; 4]>,
-        ) = factory.create_render_target(1, 1).unwrap();
+        ) = factory.create_render_target(1, 1)?;
         let (_, s_original, _): (
             _,
             _,
             gfx::handle::RenderTargetView<gfx_device_gl::Resources, [f32; 4]>,
-        ) = factory.create_render_target(1, 1).unwrap();
+        ) = factory.create_render_target(1, 1)?;
 
         let (_, s_covariances, _):(
             _,
             _,
             gfx::handle::RenderTargetView<gfx_device_gl::Resources, [f32; 4]>,
-        ) = factory.create_render_target(1, 1).unwrap();
+        ) = factory.create_render_target(1, 1)?;
 
         let (vertex_buffer, _slice) = factory.create_vertex_buffer_with_slice(&TRIANGLE, ());
-        let (_, gui_texture) = load_texture_from_bytes(&mut factory, &[127; 4], 1, 1).unwrap();
+        let (_, gui_texture) = load_texture_from_bytes(&mut factory, &[127; 4], 1, 1).map_err(NodeError::Resource)?;
 
         let gui_context = eframe::egui::CtxRef::default();
 
-        Display {
+        Ok(Display {
             pso,
             pso_data: pipe::Data {
                 u_stereo: 0,
@@ -158,31 +156,52 @@ impl Node for Display {
             gui_texture_version: 0,
             gui_meshes: Vec::new(),
             gui_active: false
-        }
+        })
     }
 
-    fn negociate_slots(&mut self, window: &window::Window, slots: NodeSlots) -> NodeSlots {
-        let slots = slots.to_color_input(window).to_color_output(window);
-        self.pso_data.u_resolution_in = slots.input_size_f32();
-        self.pso_data.u_resolution_out = slots.output_size_f32();
-        self.pso_data.s_source = slots.as_color_view();
-        self.pso_data.s_deflection = slots.as_deflection_view();
-        self.pso_data.s_color_change = slots.as_color_change_view();
-        self.pso_data.s_color_uncertainty = slots.as_color_uncertainty_view();
-        self.pso_data.s_covariances = slots.as_covariances_view();
-        
-        self.pso_data.rt_color = slots.as_color();
+    fn schema() -> Vec<Parameter> {
+        vec![
+            Parameter::toggle("split_screen_switch", "Shows the original image next to the simulation"),
+            Parameter::integer("flow_id", (0.0, 16.0), 0.0, "Index of the flow (eye), usually set by the application"),
+        ]
+    }
 
-        self.gui_pso_data.u_resolution_in = slots.input_size_f32();
-        self.gui_pso_data.u_resolution_out = slots.output_size_f32();
-        self.gui_pso_data.rt_color = slots.as_color();
+    fn parameters(&self) -> Vec<Parameter> {
+        Self::schema()
+    }
 
-        slots
+    fn input_channels(&self) -> Vec<ChannelInfo> {
+        // Errors are only visualized when mixed with colormaps.
+        if self.pso_data.u_mix_type != 0 {
+            ERROR_CHANNELS.to_vec()
+        } else {
+            Vec::new()
+        }
     }
 
-    fn negociate_slots_wk(&mut self, window: &window::Window, slots: NodeSlots, well_known: &WellKnownSlots) -> NodeSlots{
-        self.pso_data.s_original = well_known.get_original().expect("Nah, no original image?");
-        self.negociate_slots(window, slots)
+    fn negociate_slots(&mut self, window: &window::Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
+        let slots = slots.to_color_input(window)?.to_color_output(window)?;
+        self.pso_data.u_resolution_in = slots.input_size_f32()?;
+        self.pso_data.u_resolution_out = slots.output_size_f32()?;
+        self.pso_data.s_source = slots.as_color_view()?;
+        self.pso_data.s_deflection = slots.as_deflection_view()?;
+        self.pso_data.s_color_change = slots.as_color_change_view()?;
+        self.pso_data.s_color_uncertainty = slots.as_color_uncertainty_view()?;
+        self.pso_data.s_covariances = slots.as_covariances_view()?;
+        // Without a second input (e.g., the flow's input), fall back to the input.
+        self.pso_data.s_original = if slots.aux_inputs_len() > 0 {
+            slots.as_aux_color_view(0)?
+        } else {
+            slots.as_color_view()?
+        };
+
+        self.pso_data.rt_color = slots.as_color()?;
+
+        self.gui_pso_data.u_resolution_in = slots.input_size_f32()?;
+        self.gui_pso_data.u_resolution_out = slots.output_size_f32()?;
+        self.gui_pso_data.rt_color = slots.as_color()?;
+
+        Ok(slots)
     }
 
 
@@ -0,0 +1,61 @@
+use super::*;
+use std::fmt;
+
+/// Reasons why a [Node] cannot be created or cannot negociate its slots.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeError {
+    /// Shaders could not be compiled or linked.
+    Pipeline(String),
+    /// A GPU resource (texture, render target, ...) could not be created.
+    Resource(String),
+    /// The node needs an input, but its predecessor provides none.
+    MissingInput,
+    /// The node needs an output, but none was negociated.
+    MissingOutput,
+    /// The input does not provide what the node needs, e.g., color without depth.
+    IncompatibleInput { expected: SlotKind, provided: SlotKind },
+    /// The output does not match what the node renders.
+    IncompatibleOutput { expected: SlotKind, provided: SlotKind },
+    /// The input cannot be sampled (e.g., it is the window's render target).
+    UnreadableInput,
+    /// A channel (e.g., `deflection`) is neither provided by the input nor by the output.
+    MissingChannel(String),
+    /// A channel has a different format than expected.
+    IncompatibleChannel { name: String, expected: ChannelFormat, provided: ChannelFormat },
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NodeError::Pipeline(msg) => write!(f, "cannot create shader pipeline: {}", msg),
+            NodeError::Resource(msg) => write!(f, "cannot create GPU resource: {}", msg),
+            NodeError::MissingInput => write!(f, "input expected, but the predecessor provides none"),
+            NodeError::MissingOutput => write!(f, "output expected"),
+            NodeError::IncompatibleInput { expected, provided } => {
+                write!(f, "expects {:?} input, but {:?} is provided", expected, provided)
+            }
+            NodeError::IncompatibleOutput { expected, provided } => {
+                write!(f, "expects {:?} output, but {:?} is provided", expected, provided)
+            }
+            NodeError::UnreadableInput => write!(f, "input cannot be read from shaders"),
+            NodeError::MissingChannel(name) => write!(f, "channel \"{}\" expected", name),
+            NodeError::IncompatibleChannel { name, expected, provided } => {
+                write!(f, "expects {:?} channel \"{}\", but {:?} is provided", expected, name, provided)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NodeError {}
+
+impl From<gfx::PipelineStateError<String>> for NodeError {
+    fn from(err: gfx::PipelineStateError<String>) -> Self {
+        NodeError::Pipeline(err.to_string())
+    }
+}
+
+impl From<gfx::CombinedError> for NodeError {
+    fn from(err: gfx::CombinedError) -> Self {
+        NodeError::Resource(err.to_string())
+    }
+}
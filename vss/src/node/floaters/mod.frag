@@ -0,0 +1,62 @@
+#include "common.glsl"
+
+// Largest distance (in degrees) the floaters drift from their resting position.
+#define DRIFT_AMPLITUDE 1.5
+// Angular frequency (in radians per second) of the drift.
+#define DRIFT_SPEED 0.15
+
+uniform mat4 u_proj;
+uniform int u_floaters;
+uniform float u_time;
+// Displacement of the floaters by eye movements (in degrees).
+uniform vec2 u_lag;
+uniform int u_track_error;
+
+// Floaters, one per column: position, radius, and opacity (first row), aspect, angle, lag, and phase (second row).
+uniform highp sampler2D s_floaters;
+uniform sampler2D s_color;
+uniform sampler2D s_deflection;
+uniform sampler2D s_color_change;
+uniform sampler2D s_color_uncertainty;
+uniform sampler2D s_covariances;
+
+in vec2 v_tex;
+out vec4 rt_color;
+out vec4 rt_deflection;
+out vec4 rt_color_change;
+out vec4 rt_color_uncertainty;
+out vec4 rt_covariances;
+
+// Fraction of light that passes all floaters.
+float transmission(vec2 field) {
+    float transmission = 1.0;
+    for (int i = 0; i < u_floaters; i++) {
+        highp vec4 floater = texelFetch(s_floaters, ivec2(i, 0), 0);
+        highp vec4 shape = texelFetch(s_floaters, ivec2(i, 1), 0);
+        float time = u_time * DRIFT_SPEED;
+        vec2 drift = DRIFT_AMPLITUDE * vec2(sin(time + shape.w), sin(0.7 * time + 2.0 * shape.w));
+        vec2 offset = field - (floater.xy + drift + u_lag * shape.z);
+        // distance in the frame of the floater, stretched along its shorter axis
+        vec2 axis = vec2(cos(shape.y), sin(shape.y));
+        vec2 local = vec2(dot(offset, axis), dot(offset, vec2(-axis.y, axis.x)) / shape.x);
+        float shadow = floater.w * (1.0 - smoothstep(0.3, 1.0, length(local) / floater.z));
+        transmission *= 1.0 - shadow;
+    }
+    return transmission;
+}
+
+void main() {
+    vec4 color = texture(s_color, v_tex);
+    rt_color = color;
+    if (u_floaters > 0) {
+        vec3 fragment_dir = normalize((u_proj * vec4(v_tex * 2.0 - 1.0, 0.9, 1.0)).xyz);
+        rt_color.rgb *= transmission(field_position(fragment_dir));
+    }
+
+    if (u_track_error == 1) {
+        rt_deflection = texture(s_deflection, v_tex);
+        rt_color_change = vec4(texture(s_color_change, v_tex).rgb + (rt_color.rgb - color.rgb), 0.0);
+        rt_color_uncertainty = texture(s_color_uncertainty, v_tex);
+        rt_covariances = texture(s_covariances, v_tex);
+    }
+}
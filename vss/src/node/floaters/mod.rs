@@ -0,0 +1,297 @@
+use super::*;
+use cgmath::{Matrix4, Point3, SquareMatrix, Vector3};
+use gfx;
+use gfx::format::Rgba32F;
+use std::time::Instant;
+
+gfx_defines! {
+    pipeline pipe {
+        u_proj: gfx::Global<[[f32; 4];4]> = "u_proj",
+        u_floaters: gfx::Global<i32> = "u_floaters",
+        u_time: gfx::Global<f32> = "u_time",
+        u_lag: gfx::Global<[f32; 2]> = "u_lag",
+        s_floaters: gfx::TextureSampler<[f32; 4]> = "s_floaters",
+        s_color: gfx::TextureSampler<[f32; 4]> = "s_color",
+        rt_color: gfx::RenderTarget<ColorFormat> = "rt_color",
+        s_deflection: gfx::TextureSampler<[f32; 4]> = "s_deflection",
+        rt_deflection: gfx::RenderTarget<Rgba32F> = "rt_deflection",
+        s_color_change: gfx::TextureSampler<[f32; 4]> = "s_color_change",
+        rt_color_change: gfx::RenderTarget<Rgba32F> = "rt_color_change",
+        s_color_uncertainty: gfx::TextureSampler<[f32; 4]> = "s_color_uncertainty",
+        rt_color_uncertainty: gfx::RenderTarget<Rgba32F> = "rt_color_uncertainty",
+        s_covariances: gfx::TextureSampler<[f32; 4]> = "s_covariances",
+        rt_covariances: gfx::RenderTarget<Rgba32F> = "rt_covariances",
+        u_track_error: gfx::Global<i32> = "u_track_error",
+    }
+}
+
+/// Eccentricity (in degrees) up to which floaters are scattered.
+const MAX_ECCENTRICITY: f32 = 30.0;
+
+/// Stiffness and damping of the vitreous, which pulls floaters back after eye movements.
+const STIFFNESS: f32 = 12.0;
+const DAMPING: f32 = 3.0;
+
+/// Longest time step (in seconds), such that the floaters settle after pauses instead of jumping.
+const MAX_TIME_STEP: f32 = 0.1;
+
+/// Values that change the floaters themselves, not only their animation.
+const SHAPE_VALUES: [&str; 4] = ["floaters_density", "floaters_size", "floaters_opacity", "floaters_seed"];
+
+/// A shadow cast onto the retina by an opacity in the vitreous.
+#[derive(Debug, Clone, PartialEq)]
+struct Floater {
+    /// Resting position on the visual field (in degrees, positive is right and superior).
+    position: [f32; 2],
+    /// Radius along the longer axis (in degrees).
+    radius: f32,
+    /// Ratio of the shorter to the longer axis.
+    aspect: f32,
+    /// Orientation of the longer axis (in radians).
+    angle: f32,
+    opacity: f32,
+    /// How far the floater lags behind eye movements, relative to the other floaters.
+    lag: f32,
+    /// Phase of the slow drift (in radians).
+    phase: f32,
+}
+
+///
+/// Scatters floaters over the central visual field.
+///
+/// # Arguments
+///
+/// - `count`   - number of floaters
+/// - `size`    - average radius (in degrees)
+/// - `opacity` - largest opacity, value between 0 and 1
+/// - `seed`    - the same seed always results in the same floaters
+///
+fn floaters(count: usize, size: f32, opacity: f32, seed: u64) -> Vec<Floater> {
+    let mut random = SeededRandom::new(seed);
+    (0..count)
+        .map(|_| {
+            let eccentricity = MAX_ECCENTRICITY * random.next_f32().sqrt();
+            let azimuth = random.range(0.0, 2.0 * std::f32::consts::PI);
+            Floater {
+                position: [eccentricity * azimuth.cos(), eccentricity * azimuth.sin()],
+                radius: size * random.range(0.5, 1.5),
+                aspect: random.range(0.2, 1.0),
+                angle: random.range(0.0, std::f32::consts::PI),
+                opacity: opacity * random.range(0.4, 1.0),
+                lag: random.range(0.6, 1.4),
+                phase: random.range(0.0, 2.0 * std::f32::consts::PI),
+            }
+        })
+        .collect()
+}
+
+/// Simulates vitreous floaters, i.e., semi-transparent shadows that drift slowly and lag behind eye movements.
+pub struct Floaters {
+    pso: gfx::PipelineState<Resources, pipe::Meta>,
+    pso_data: pipe::Data<Resources>,
+    /// Values the floaters were scattered with, see [SHAPE_VALUES].
+    shape: Vec<Option<Value>>,
+    /// Fixed time per frame (in seconds) for reproducible renders, or zero to use the elapsed time.
+    frame_time: f32,
+    /// When the floaters were last rendered, to measure the elapsed time.
+    last_render: Option<Instant>,
+    /// Gaze of the last frame, as horizontal and vertical angle (in degrees).
+    gaze: Option<[f32; 2]>,
+    /// Gaze of the current frame, applied when rendering.
+    next_gaze: [f32; 2],
+    /// Displacement of the floaters by eye movements (in degrees) and its velocity.
+    lag: [f32; 2],
+    lag_velocity: [f32; 2],
+}
+
+impl Floaters {
+    /// Uploads the floaters, two texels per floater (one per row).
+    fn floater_texture(
+        factory: &mut gfx_device_gl::Factory,
+        floaters: &[Floater],
+    ) -> Result<
+        (
+            gfx::handle::Texture<Resources, gfx::format::R32_G32_B32_A32>,
+            gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+        ),
+        NodeError,
+    > {
+        // A placeholder if there are no floaters.
+        let mut values = vec![[0.0; 4]; 2 * floaters.len().max(1)];
+        for (i, floater) in floaters.iter().enumerate() {
+            values[i] = [floater.position[0], floater.position[1], floater.radius, floater.opacity];
+            values[floaters.len() + i] = [floater.aspect, floater.angle, floater.lag, floater.phase];
+        }
+        load_float_texture(factory, &values, floaters.len().max(1) as u32, 2).map_err(NodeError::Resource)
+    }
+
+    /// Advances the animation by a time step (in seconds).
+    fn step(&mut self, dt: f32) {
+        let dt = dt.max(0.0).min(MAX_TIME_STEP);
+        self.pso_data.u_time += dt;
+
+        // Floaters stay behind when the eye moves, then the vitreous pulls them along (and a bit beyond).
+        let gaze = self.next_gaze;
+        let previous = self.gaze.unwrap_or(gaze);
+        self.gaze = Some(gaze);
+        for axis in 0..2 {
+            self.lag[axis] -= gaze[axis] - previous[axis];
+            self.lag_velocity[axis] += (-STIFFNESS * self.lag[axis] - DAMPING * self.lag_velocity[axis]) * dt;
+            self.lag[axis] += self.lag_velocity[axis] * dt;
+        }
+        self.pso_data.u_lag = self.lag;
+    }
+}
+
+impl Node for Floaters {
+    fn new(window: &Window) -> Result<Self, NodeError> {
+        let mut factory = window.factory().borrow_mut();
+        let pso = factory
+            .create_pipeline_simple(
+                &include_glsl!("../mod.vert"),
+                &include_glsl!("mod.frag"),
+                pipe::new(),
+            )?;
+        let sampler = factory.create_sampler_linear();
+        let (_, floaters_view) = Self::floater_texture(&mut factory, &[])?;
+        let (_, color_view) = load_texture_from_bytes(&mut factory, &[0; 4], 1, 1).map_err(NodeError::Resource)?;
+        let (_, _, rt_color) = factory.create_render_target(1, 1)?;
+        let (_, s_deflection, rt_deflection) = factory.create_render_target(1, 1)?;
+        let (_, s_color_change, rt_color_change) = factory.create_render_target(1, 1)?;
+        let (_, s_color_uncertainty, rt_color_uncertainty) = factory.create_render_target(1, 1)?;
+        let (_, s_covariances, rt_covariances) = factory.create_render_target(1, 1)?;
+
+        Ok(Floaters {
+            pso,
+            pso_data: pipe::Data {
+                u_proj: Matrix4::from_scale(1.0).into(),
+                u_floaters: 0,
+                u_time: 0.0,
+                u_lag: [0.0, 0.0],
+                s_floaters: (floaters_view, create_sampler_nearest(&mut factory)),
+                s_color: (color_view, sampler.clone()),
+                rt_color,
+                s_deflection: (s_deflection, sampler.clone()),
+                rt_deflection,
+                s_color_change: (s_color_change, sampler.clone()),
+                rt_color_change,
+                s_color_uncertainty: (s_color_uncertainty, sampler.clone()),
+                rt_color_uncertainty,
+                s_covariances: (s_covariances, sampler.clone()),
+                rt_covariances,
+                u_track_error: 0,
+            },
+            shape: Vec::new(),
+            frame_time: 0.0,
+            last_render: None,
+            gaze: None,
+            next_gaze: [0.0, 0.0],
+            lag: [0.0, 0.0],
+            lag_velocity: [0.0, 0.0],
+        })
+    }
+
+    fn schema() -> Vec<Parameter> {
+        vec![
+            Parameter::toggle("floaters_onoff", "Enables vitreous floaters"),
+            Parameter::integer("floaters_density", (0.0, 64.0), 8.0, "Number of floaters"),
+            Parameter::number("floaters_size", "°", (0.1, 10.0), 1.5, "Average radius of the floaters"),
+            Parameter::number("floaters_opacity", "%", (0.0, 100.0), 30.0, "Largest opacity of the floaters"),
+            Parameter::integer("floaters_seed", (0.0, 4294967295.0), 0.0, "Seed of the scattered floaters"),
+            Parameter::number("floaters_frame_time", "ms", (0.0, 1000.0), 0.0, "Fixed time per frame for reproducible renders (0: elapsed time)"),
+        ]
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        Self::schema()
+    }
+
+    fn output_channels(&self) -> Vec<ChannelInfo> {
+        ERROR_CHANNELS.to_vec()
+    }
+
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
+        let slots = slots
+            .to_color_input(window)?
+            .to_color_output(window)?;
+
+        self.pso_data.s_color = slots.as_color_view()?;
+        self.pso_data.rt_color = slots.as_color()?;
+        self.pso_data.s_deflection = slots.as_deflection_view()?;
+        self.pso_data.rt_deflection = slots.as_deflection()?;
+        self.pso_data.s_color_change = slots.as_color_change_view()?;
+        self.pso_data.rt_color_change = slots.as_color_change()?;
+        self.pso_data.s_color_uncertainty = slots.as_color_uncertainty_view()?;
+        self.pso_data.rt_color_uncertainty = slots.as_color_uncertainty()?;
+        self.pso_data.s_covariances = slots.as_covariances_view()?;
+        self.pso_data.rt_covariances = slots.as_covariances()?;
+
+        Ok(slots)
+    }
+
+    fn update_values(&mut self, window: &Window, values: &ValueMap) {
+        let number = |name: &str, default: f64| values.get(name).and_then(Value::as_f64).unwrap_or(default);
+        self.frame_time = number("floaters_frame_time", 0.0).max(0.0) as f32 / 1000.0;
+
+        let shape: Vec<Option<Value>> = match values.get("floaters_onoff") {
+            Some(Value::Bool(true)) => SHAPE_VALUES.iter().map(|name| values.get(*name).cloned()).collect(),
+            _ => Vec::new(),
+        };
+        if shape == self.shape {
+            return;
+        }
+        self.shape = shape;
+
+        let floaters = if self.shape.is_empty() {
+            Vec::new()
+        } else {
+            floaters(
+                number("floaters_density", 8.0).max(0.0).min(64.0) as usize,
+                number("floaters_size", 1.5) as f32,
+                (number("floaters_opacity", 30.0) as f32 / 100.0).max(0.0).min(1.0),
+                number("floaters_seed", 0.0) as u64,
+            )
+        };
+        let mut factory = window.factory().borrow_mut();
+        match Self::floater_texture(&mut factory, &floaters) {
+            Ok((_, view)) => {
+                self.pso_data.s_floaters.0 = view;
+                self.pso_data.u_floaters = floaters.len() as i32;
+            }
+            Err(e) => {
+                eprintln!("error: floaters: {}", e);
+                self.pso_data.u_floaters = 0;
+            }
+        }
+        // The same values always start with the same frame.
+        self.pso_data.u_time = 0.0;
+        self.gaze = None;
+        self.lag = [0.0, 0.0];
+        self.lag_velocity = [0.0, 0.0];
+    }
+
+    fn input(&mut self, perspective: &EyePerspective, vis_param: &VisualizationParameters) -> EyePerspective {
+        let gaze_rotation = Matrix4::look_to_lh(Point3::new(0.0, 0.0, 0.0), perspective.gaze, Vector3::unit_y());
+        self.pso_data.u_proj = (gaze_rotation * perspective.proj.invert().unwrap()).into();
+        let gaze = perspective.gaze;
+        self.next_gaze = [
+            gaze.x.atan2(gaze.z).to_degrees(),
+            gaze.y.atan2(gaze.x.hypot(gaze.z)).to_degrees(),
+        ];
+        self.pso_data.u_track_error = vis_param.has_to_track_error() as i32;
+        perspective.clone()
+    }
+
+    fn render(&mut self, window: &Window) {
+        // Window::delta_t stops with the bees, but floaters keep moving.
+        let now = Instant::now();
+        let elapsed = self.last_render.map_or(0.0, |last| now.duration_since(last).as_secs_f32());
+        self.last_render = Some(now);
+        if self.pso_data.u_floaters > 0 {
+            let dt = if self.frame_time > 0.0 { self.frame_time } else { elapsed };
+            self.step(dt);
+        }
+        let mut encoder = window.encoder().borrow_mut();
+        encoder.draw(&gfx::Slice::from_vertex_count(6), &self.pso, &self.pso_data);
+    }
+}
@@ -15,34 +15,34 @@ pub struct NormalMapGenerator {
 }
 
 impl NormalMapGenerator {
-    pub fn new(window: &Window) -> Self {
+    pub fn new(window: &Window) -> Result<Self, NodeError> {
         let mut factory = window.factory().borrow_mut();
         let pso = factory
             .create_pipeline_simple(
                 &include_glsl!("../mod.vert"),
                 &include_glsl!("generator.frag"),
                 pipe::new(),
-            )
-            .unwrap();
+            )?;
 
-        let (texture, _, dst) = factory.create_render_target(1, 1).unwrap();
+        let (texture, _, dst) = factory.create_render_target(1, 1)?;
 
-        NormalMapGenerator {
+        Ok(NormalMapGenerator {
             texture,
             pso,
             pso_data: pipe::Data {
                 rt_color: dst,
             },
-        }
+        })
     }
 
-    pub fn generate(&mut self, window: &Window, width: texture::Size, height: texture::Size){
+    pub fn generate(&mut self, window: &Window, width: texture::Size, height: texture::Size) -> Result<(), NodeError> {
         let mut factory = window.factory().borrow_mut();
-        let (texture, _, dst) = factory.create_render_target(width, height).unwrap();
+        let (texture, _, dst) = factory.create_render_target(width, height)?;
         self.texture = texture;
         self.pso_data = pipe::Data {rt_color: dst};
         
         let mut encoder = window.encoder().borrow_mut();
         encoder.draw(&gfx::Slice::from_vertex_count(6), &self.pso, &self.pso_data);
+        Ok(())
     }
 }
@@ -57,37 +57,36 @@ pub struct Lens {
 }
 
 impl Node for Lens {
-    fn new(window: &Window) -> Self {
-        let generator = NormalMapGenerator::new(&window);
+    fn new(window: &Window) -> Result<Self, NodeError> {
+        let generator = NormalMapGenerator::new(&window)?;
         let mut factory = window.factory().borrow_mut();
         let pso = factory
             .create_pipeline_simple(
                 &include_glsl!("../mod.vert"),
                 &include_glsl!("mod.frag"),
                 pipe::new(),
-            )
-            .unwrap();
+            )?;
 
         //TODO: use generalized load_texture_from_bytes
-        let (_, normal_view) = load_highp_texture_from_bytes(&mut factory, &[127; 4], 1, 1).unwrap();
+        let (_, normal_view) = load_highp_texture_from_bytes(&mut factory, &[127; 4], 1, 1).map_err(NodeError::Resource)?;
 
-        let (_, cornea_view) = load_texture_from_bytes(&mut factory, &[127; 4], 1, 1).unwrap();
+        let (_, cornea_view) = load_texture_from_bytes(&mut factory, &[127; 4], 1, 1).map_err(NodeError::Resource)?;
 
         let sampler = factory.create_sampler_linear();
-        let (_, src, dst) = factory.create_render_target(1, 1).unwrap();
+        let (_, src, dst) = factory.create_render_target(1, 1)?;
 
         let (_, srv, _): (
             _,
             _,
             gfx::handle::RenderTargetView<gfx_device_gl::Resources, f32>,
-        ) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_deflection, rt_deflection) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_color_change, rt_color_change) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_color_uncertainty, rt_color_uncertainty) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_covariances, rt_covariances) = factory.create_render_target(1, 1).unwrap();
+        ) = factory.create_render_target(1, 1)?;
+        let (_, s_deflection, rt_deflection) = factory.create_render_target(1, 1)?;
+        let (_, s_color_change, rt_color_change) = factory.create_render_target(1, 1)?;
+        let (_, s_color_uncertainty, rt_color_uncertainty) = factory.create_render_target(1, 1)?;
+        let (_, s_covariances, rt_covariances) = factory.create_render_target(1, 1)?;
 
 
-        Lens {
+        Ok(Lens {
             generator,
             pso,
             pso_data: pipe::Data {
@@ -121,19 +120,40 @@ impl Node for Lens {
                 u_eye_distance_center: 0.0,
                 u_track_error: 0
             },
-        }
+        })
+    }
+
+    fn schema() -> Vec<Parameter> {
+        vec![
+            Parameter::integer("rays", (1.0, 4.0), 4.0, "Quality of the ray casting (more rays per pixel)"),
+            Parameter::toggle("presbyopia_onoff", "Enables presbyopia"),
+            Parameter::number("presbyopia_near_point", "mm", (0.0, 10000.0), 0.0, "Closest distance that can be focused"),
+            Parameter::toggle("myopiahyperopia_onoff", "Enables myopia or hyperopia"),
+            Parameter::number("myopiahyperopia_mnh", "", (0.0, 100.0), 50.0, "Refractive error, maps 0 to 100 to -3 D (myopia) to +3 D (hyperopia)"),
+            Parameter::number("astigmatism_dpt", "D", (0.0, 10.0), 0.0, "Strength of astigmatism"),
+            Parameter::number("astigmatism_angle_deg", "deg", (-180.0, 180.0), 0.0, "Axis of astigmatism"),
+            Parameter::number("eye_distance_center", "mm", (-50.0, 50.0), 0.0, "Horizontal offset of the eye from the optical axis"),
+        ]
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        Self::schema()
+    }
+
+    fn output_channels(&self) -> Vec<ChannelInfo> {
+        ERROR_CHANNELS.to_vec()
     }
 
-    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> NodeSlots {
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
         use gfx::format;
 
-        let slots = slots.to_color_depth_input(window).to_color_output(window);
-        let (color_view, depth_view) = slots.as_color_depth_view();
+        let slots = slots.to_color_depth_input(window)?.to_color_output(window)?;
+        let (color_view, depth_view) = slots.as_color_depth_view()?;
         self.pso_data.s_color = color_view;
         self.pso_data.s_depth = depth_view;
 
-        let size = slots.output_size_f32();
-        self.generator.generate(window, size[0] as u16, size[1] as u16);
+        let size = slots.output_size_f32()?;
+        self.generator.generate(window, size[0] as u16, size[1] as u16)?;
         let mut factory = window.factory().borrow_mut();
         let normal_texture = factory
         .view_texture_as_shader_resource::<(gfx::format::R32_G32_B32_A32, gfx::format::Float)>(
@@ -141,20 +161,20 @@ impl Node for Lens {
             (0, 0),
             format::Swizzle::new(),
         )
-        .unwrap();
+        .map_err(|err| NodeError::Resource(err.to_string()))?;
         self.pso_data.s_normal = (normal_texture, factory.create_sampler_linear());
 
-        self.pso_data.rt_color = slots.as_color();
-        self.pso_data.s_deflection = slots.as_deflection_view();
-        self.pso_data.rt_deflection = slots.as_deflection();
-        self.pso_data.s_color_change = slots.as_color_change_view();
-        self.pso_data.rt_color_change = slots.as_color_change();  
-        self.pso_data.s_color_uncertainty = slots.as_color_uncertainty_view();
-        self.pso_data.rt_color_uncertainty = slots.as_color_uncertainty();
-        self.pso_data.s_covariances = slots.as_covariances_view();
-        self.pso_data.rt_covariances = slots.as_covariances();
+        self.pso_data.rt_color = slots.as_color()?;
+        self.pso_data.s_deflection = slots.as_deflection_view()?;
+        self.pso_data.rt_deflection = slots.as_deflection()?;
+        self.pso_data.s_color_change = slots.as_color_change_view()?;
+        self.pso_data.rt_color_change = slots.as_color_change()?;  
+        self.pso_data.s_color_uncertainty = slots.as_color_uncertainty_view()?;
+        self.pso_data.rt_color_uncertainty = slots.as_color_uncertainty()?;
+        self.pso_data.s_covariances = slots.as_covariances_view()?;
+        self.pso_data.rt_covariances = slots.as_covariances()?;
         
-        slots
+        Ok(slots)
     }
 
     fn update_values(&mut self, _window: &Window, values: &ValueMap) {
@@ -4,9 +4,15 @@
 #[macro_use]
 mod macros;
 mod cataract;
+mod compare;
 mod display;
+mod error;
+mod floaters;
 mod lens;
 mod passthrough;
+mod per_eye;
+mod pool;
+mod registry;
 mod retina;
 mod rgb_buffer;
 mod slot;
@@ -17,9 +23,15 @@ mod variance;
 mod peacock;
 
 pub use self::cataract::*;
+pub use self::compare::*;
 pub use self::display::*;
+pub use self::error::*;
+pub use self::floaters::*;
 pub use self::lens::*;
 pub use self::passthrough::*;
+pub use self::per_eye::*;
+pub use self::pool::*;
+pub use self::registry::*;
 pub use self::retina::*;
 pub use self::rgb_buffer::*;
 pub use self::slot::*;
@@ -41,17 +53,44 @@ use super::*;
 
 /// An executable function that implements an aspect of the simulation.
 pub trait Node {
-    /// Initializes this node.
-    fn new(window: &Window) -> Self
+    /// Initializes this node, e.g., compiles its shaders.
+    fn new(window: &Window) -> Result<Self, NodeError>
     where
         Self: Sized;
 
     /// Negociates input and output for this node (source texture and render target),
     /// possibly re-using suggested `slots` (for efficiency).
-    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> NodeSlots;
+    ///
+    /// Fails if the suggested input cannot be used, e.g., if depth is required but not provided.
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError>;
 
-    fn negociate_slots_wk(&mut self, window: &Window, slots: NodeSlots, _well_known: &WellKnownSlots) -> NodeSlots{
-        self.negociate_slots(window, slots)
+    /// Describes the values this node type reads in [update_values](Node::update_values).
+    fn schema() -> Vec<Parameter>
+    where
+        Self: Sized,
+    {
+        Vec::new()
+    }
+
+    /// Describes the values this node reads, usually its [schema](Node::schema).
+    fn parameters(&self) -> Vec<Parameter> {
+        Vec::new()
+    }
+
+    /// Channels this node reads from its input besides color and depth, e.g., to visualize errors.
+    ///
+    /// Preceding nodes provide these channels in their outputs. The flow negociates again if they
+    /// change (see [Node::input]), so that channels are only allocated while they are read.
+    fn input_channels(&self) -> Vec<ChannelInfo> {
+        Vec::new()
+    }
+
+    /// Channels this node renders to besides color and depth.
+    ///
+    /// Nodes are assumed to update these channels, i.e., if succeeding nodes read them, they are read
+    /// from the input as well. Other channels read by succeeding nodes are passed on from the input.
+    fn output_channels(&self) -> Vec<ChannelInfo> {
+        Vec::new()
     }
 
     /// Set new parameters for this effect
@@ -64,6 +103,17 @@ pub trait Node {
         perspective.clone()
     }
 
+    /// Handle input, with the perspective of each eye for nodes that run once per eye (see [PerEye]).
+    #[allow(unused_variables)]
+    fn input_eyes(
+        &mut self,
+        perspective: &EyePerspective,
+        eyes: &[EyePerspective],
+        vis_param: &VisualizationParameters,
+    ) -> EyePerspective {
+        self.input(perspective, vis_param)
+    }
+
     /// Render the node.
     fn render(&mut self, window: &Window);
 }
@@ -4,12 +4,12 @@ use super::*;
 pub struct Passthrough;
 
 impl Node for Passthrough {
-    fn new(_window: &Window) -> Self {
-        Passthrough {}
+    fn new(_window: &Window) -> Result<Self, NodeError> {
+        Ok(Passthrough {})
     }
 
-    fn negociate_slots(&mut self, _window: &Window, slots: NodeSlots) -> NodeSlots {
-        slots.to_passthrough()
+    fn negociate_slots(&mut self, _window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
+        Ok(slots.to_passthrough())
     }
 
     fn render(&mut self, _window: &Window) {}
@@ -28,7 +28,7 @@ pub struct PeacockCB {
 impl PeacockCB{}
 
 impl Node for PeacockCB {
-    fn new(window: &Window) -> Self {
+    fn new(window: &Window) -> Result<Self, NodeError> {
         let mut factory = window.factory().borrow_mut();
 
         let pso = factory
@@ -36,18 +36,17 @@ impl Node for PeacockCB {
                 &include_glsl!("../mod.vert"),
                 &include_glsl!("mod.frag"),
                 pipe::new(),
-            )
-            .unwrap();
+            )?;
 
         let sampler = factory.create_sampler_linear();
-        let (_, color_view) = load_texture_from_bytes(&mut factory, &[0; 4], 1, 1).unwrap();
-        let (_, _, rt_color) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_deflection, rt_deflection) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_color_change, rt_color_change) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_color_uncertainty, rt_color_uncertainty) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_covariances, rt_covariances) = factory.create_render_target(1, 1).unwrap();
+        let (_, color_view) = load_texture_from_bytes(&mut factory, &[0; 4], 1, 1).map_err(NodeError::Resource)?;
+        let (_, _, rt_color) = factory.create_render_target(1, 1)?;
+        let (_, s_deflection, rt_deflection) = factory.create_render_target(1, 1)?;
+        let (_, s_color_change, rt_color_change) = factory.create_render_target(1, 1)?;
+        let (_, s_color_uncertainty, rt_color_uncertainty) = factory.create_render_target(1, 1)?;
+        let (_, s_covariances, rt_covariances) = factory.create_render_target(1, 1)?;
 
-        PeacockCB {
+        Ok(PeacockCB {
             pso,
             pso_data: pipe::Data {
                 s_color: (color_view, sampler.clone()),
@@ -64,26 +63,42 @@ impl Node for PeacockCB {
                 u_cb_strength: 0.0,
                 u_cb_type: 0,
             },
-        }
+        })
+    }
+
+    fn schema() -> Vec<Parameter> {
+        vec![
+            Parameter::toggle("peacock_cb_onoff", "Enables the simulation of color vision deficiencies"),
+            Parameter::number("peacock_cb_strength", "", (0.0, 1.0), 0.0, "Severity, from normal (0) to dichromacy (1)"),
+            Parameter::integer("peacock_cb_type", (0.0, 3.0), 0.0, "Deficiency (0: protan, 1: deutan, 2: tritan, 3: monochromacy)"),
+        ]
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        Self::schema()
+    }
+
+    fn output_channels(&self) -> Vec<ChannelInfo> {
+        ERROR_CHANNELS.to_vec()
     }
 
-    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> NodeSlots {
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
         let slots = slots
-            .to_color_input(window)
-            .to_color_output(window);
+            .to_color_input(window)?
+            .to_color_output(window)?;
 
-        self.pso_data.s_color = slots.as_color_view();
-        self.pso_data.rt_color = slots.as_color();
-        self.pso_data.s_deflection = slots.as_deflection_view();
-        self.pso_data.rt_deflection = slots.as_deflection();
-        self.pso_data.s_color_change = slots.as_color_change_view();
-        self.pso_data.rt_color_change = slots.as_color_change();  
-        self.pso_data.s_color_uncertainty = slots.as_color_uncertainty_view();
-        self.pso_data.rt_color_uncertainty = slots.as_color_uncertainty();
-        self.pso_data.s_covariances = slots.as_covariances_view();
-        self.pso_data.rt_covariances = slots.as_covariances();
+        self.pso_data.s_color = slots.as_color_view()?;
+        self.pso_data.rt_color = slots.as_color()?;
+        self.pso_data.s_deflection = slots.as_deflection_view()?;
+        self.pso_data.rt_deflection = slots.as_deflection()?;
+        self.pso_data.s_color_change = slots.as_color_change_view()?;
+        self.pso_data.rt_color_change = slots.as_color_change()?;  
+        self.pso_data.s_color_uncertainty = slots.as_color_uncertainty_view()?;
+        self.pso_data.rt_color_uncertainty = slots.as_color_uncertainty()?;
+        self.pso_data.s_covariances = slots.as_covariances_view()?;
+        self.pso_data.rt_covariances = slots.as_covariances()?;
         
-        slots
+        Ok(slots)
     }
 
     fn update_values(&mut self, _window: &Window, values: &ValueMap) {
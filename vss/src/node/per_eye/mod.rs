@@ -0,0 +1,140 @@
+use super::*;
+use cgmath::{Matrix4, SquareMatrix};
+
+/// Number of eyes in stereo slots.
+pub const EYES: usize = 2;
+
+/// Keys of values that apply to one eye only (right eye first, like flows), see [PerEye].
+pub const EYE_VALUES: [&str; EYES] = ["right_eye", "left_eye"];
+
+/// A node that runs one instance of another node per eye, such that a single flow carries both eyes.
+///
+/// Reads stereo slots (or the same image for both eyes) and writes stereo slots, see [Slot::Stereo].
+/// Values given as objects under the keys in [EYE_VALUES] override values for that eye only,
+/// including the eye axis (e.g., `{"left_eye": {"eye_axis_rot_y": 0.1}}`).
+pub struct PerEye {
+    eyes: Vec<Box<dyn Node>>,
+    views: Vec<Matrix4<f32>>,
+}
+
+impl PerEye {
+    /// Runs the given nodes for the right and left eye, respectively.
+    pub fn from_nodes(right: Box<dyn Node>, left: Box<dyn Node>) -> Self {
+        PerEye {
+            eyes: vec![right, left],
+            views: vec![Matrix4::identity(); EYES],
+        }
+    }
+
+}
+
+impl Node for PerEye {
+    /// Runs a passthrough per eye, use [PerEye::from_nodes] instead.
+    fn new(window: &Window) -> Result<Self, NodeError> {
+        Ok(Self::from_nodes(Box::new(Passthrough::new(window)?), Box::new(Passthrough::new(window)?)))
+    }
+
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
+        let mut eyes = Vec::with_capacity(EYES);
+        for (node, slots) in self.eyes.iter_mut().zip(slots.split_eyes()) {
+            eyes.push(node.negociate_slots(window, slots)?);
+        }
+        NodeSlots::merge_eyes(eyes)
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        self.eyes[0].parameters()
+    }
+
+    fn input_channels(&self) -> Vec<ChannelInfo> {
+        let mut channels = Vec::new();
+        for info in self.eyes.iter().flat_map(|node| node.input_channels()) {
+            if !channels.contains(&info) {
+                channels.push(info);
+            }
+        }
+        channels
+    }
+
+    fn output_channels(&self) -> Vec<ChannelInfo> {
+        let mut channels = Vec::new();
+        for info in self.eyes.iter().flat_map(|node| node.output_channels()) {
+            if !channels.contains(&info) {
+                channels.push(info);
+            }
+        }
+        channels
+    }
+
+    fn update_values(&mut self, window: &Window, values: &ValueMap) {
+        // The flow already applies the common eye axis.
+        let flow_view = eye_axis_view(values).invert().unwrap_or_else(Matrix4::identity);
+        for (eye, node) in self.eyes.iter_mut().enumerate() {
+            match values.get(EYE_VALUES[eye]) {
+                Some(Value::Object(eye_values)) => {
+                    let mut merged = values.clone();
+                    merged.extend(eye_values.iter().map(|(key, value)| (key.clone(), value.clone())));
+                    node.update_values(window, &merged);
+                    self.views[eye] = eye_axis_view(&merged) * flow_view;
+                }
+                _ => {
+                    node.update_values(window, values);
+                    self.views[eye] = Matrix4::identity();
+                }
+            }
+        }
+    }
+
+    fn input(&mut self, perspective: &EyePerspective, vis_param: &VisualizationParameters) -> EyePerspective {
+        self.input_eyes(perspective, &vec![perspective.clone(); EYES], vis_param)
+    }
+
+    fn input_eyes(
+        &mut self,
+        perspective: &EyePerspective,
+        eyes: &[EyePerspective],
+        vis_param: &VisualizationParameters,
+    ) -> EyePerspective {
+        for ((node, view), eye_perspective) in self.eyes.iter_mut().zip(self.views.iter()).zip(eyes.iter()) {
+            let mut eye_perspective = eye_perspective.clone();
+            eye_perspective.view = view * eye_perspective.view;
+            node.input(&eye_perspective, vis_param);
+        }
+        perspective.clone()
+    }
+
+    fn render(&mut self, window: &Window) {
+        for node in self.eyes.iter_mut() {
+            node.render(window);
+        }
+    }
+}
+
+/// Moves values that must be the same for both eyes (see [BINOCULAR_VALUES]) from the values of
+/// each eye to the common values, e.g., if the eyes are configured separately.
+///
+/// If the eyes disagree, the value of the right eye is used and a warning is returned.
+pub fn share_binocular_values(values: &mut ValueMap) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for name in BINOCULAR_VALUES.iter() {
+        let mut shared: Option<Value> = None;
+        for eye in EYE_VALUES.iter() {
+            if let Some(Value::Object(eye_values)) = values.get_mut(*eye) {
+                if let Some(value) = eye_values.remove(*name) {
+                    match &shared {
+                        Some(shared) if *shared != value => warnings.push(format!(
+                            "\"{}\" differs between the eyes, using the value of the right eye",
+                            name
+                        )),
+                        Some(_) => {}
+                        None => shared = Some(value),
+                    }
+                }
+            }
+        }
+        if let Some(value) = shared {
+            values.insert(name.to_string(), value);
+        }
+    }
+    warnings
+}
@@ -0,0 +1,127 @@
+use super::*;
+use gfx::format::Rgba32F;
+
+struct PoolEntry {
+    channel: Channel,
+    /// Currently part of an output that is read by some node.
+    in_use: bool,
+    /// Acquired since the last [TexturePool::reclaim].
+    acquired: bool,
+}
+
+/// Render targets that are re-used by the nodes of a [Flow].
+///
+/// Targets are acquired while negociating slots and released once the last node that reads them
+/// is negociated, such that later nodes can render to them again. Nodes may have to render to
+/// channels that no one reads (e.g., when error tracking is off), these share scratch targets.
+pub struct TexturePool {
+    entries: Vec<PoolEntry>,
+    scratch: Vec<(&'static str, PoolEntry)>,
+    blank: Option<gfx::handle::ShaderResourceView<Resources, [f32; 4]>>,
+}
+
+impl TexturePool {
+    pub fn new() -> Self {
+        TexturePool {
+            entries: Vec::new(),
+            scratch: Vec::new(),
+            blank: None,
+        }
+    }
+
+    /// Returns a target that is not in use, allocating it if there is none.
+    pub fn acquire(
+        &mut self,
+        factory: &mut DeviceFactory,
+        format: ChannelFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Channel, NodeError> {
+        let free = self.entries.iter_mut().find(|entry| {
+            !entry.in_use && entry.channel.format() == format && entry.channel.size() == [width, height]
+        });
+        if let Some(entry) = free {
+            entry.in_use = true;
+            entry.acquired = true;
+            return Ok(entry.channel.clone());
+        }
+        let channel = Channel::create(factory, format, width, height)?;
+        self.entries.push(PoolEntry {
+            channel: channel.clone(),
+            in_use: true,
+            acquired: true,
+        });
+        Ok(channel)
+    }
+
+    /// Allows the target of `channel` to be acquired again (if it is part of this pool).
+    pub fn release(&mut self, channel: &Channel) {
+        for entry in self.entries.iter_mut() {
+            if entry.channel.shares_target(channel) {
+                entry.in_use = false;
+            }
+        }
+    }
+
+    /// Returns the target that all nodes render to if no one reads the channel `info`.
+    pub fn scratch(
+        &mut self,
+        factory: &mut DeviceFactory,
+        info: ChannelInfo,
+        width: u32,
+        height: u32,
+    ) -> Result<Channel, NodeError> {
+        let existing = self.scratch.iter_mut().find(|(name, entry)| {
+            *name == info.name && entry.channel.format() == info.format && entry.channel.size() == [width, height]
+        });
+        if let Some((_, entry)) = existing {
+            entry.acquired = true;
+            return Ok(entry.channel.clone());
+        }
+        let channel = Channel::create(factory, info.format, width, height)?;
+        self.scratch.push((
+            info.name,
+            PoolEntry {
+                channel: channel.clone(),
+                in_use: true,
+                acquired: true,
+            },
+        ));
+        Ok(channel)
+    }
+
+    /// Returns a 1x1 texture of zeros, for sampling channels that are not provided.
+    pub fn blank(
+        &mut self,
+        factory: &mut DeviceFactory,
+        encoder: &mut DeviceEncoder,
+    ) -> Result<gfx::handle::ShaderResourceView<Resources, [f32; 4]>, NodeError> {
+        if let Some(blank) = &self.blank {
+            return Ok(blank.clone());
+        }
+        let (target, view) = create_texture_render_target::<Rgba32F>(factory, 1, 1)?;
+        encoder.clear(&target, [0.0; 4]);
+        self.blank = Some(view.clone());
+        Ok(view)
+    }
+
+    /// Marks all targets as unused, e.g., before negociating all nodes again.
+    pub fn reclaim(&mut self) {
+        for entry in self.entries.iter_mut().chain(self.scratch.iter_mut().map(|(_, entry)| entry)) {
+            entry.in_use = false;
+            entry.acquired = false;
+        }
+    }
+
+    /// Frees targets that were not acquired since the last [TexturePool::reclaim], e.g., after resizing.
+    pub fn trim(&mut self) {
+        self.entries.retain(|entry| entry.acquired);
+        self.scratch.retain(|(_, entry)| entry.acquired);
+    }
+}
+
+impl Default for TexturePool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,187 @@
+use super::*;
+
+/// Kind of slot a node consumes or produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotKind {
+    /// Color only (depth, if present, is dropped).
+    Color,
+    /// Color and depth.
+    ColorDepth,
+    /// Whatever is provided (e.g., for passthrough nodes).
+    Any,
+}
+
+impl SlotKind {
+    /// Whether a slot of kind `provided` can be consumed as a slot of this kind.
+    pub fn accepts(self, provided: SlotKind) -> bool {
+        match (self, provided) {
+            (SlotKind::ColorDepth, SlotKind::Color) => false,
+            _ => true,
+        }
+    }
+}
+
+/// Creates a node for the given window.
+pub type NodeConstructor = Box<dyn Fn(&Window) -> Result<Box<dyn Node>, NodeError>>;
+
+/// Describes a node that can be created by name.
+pub struct NodeDescriptor {
+    /// Stable identifier, e.g., `cataract`.
+    pub name: String,
+    /// Human-readable summary of what the node simulates.
+    pub description: String,
+    /// Required input slot.
+    pub input: SlotKind,
+    /// Produced output slot.
+    pub output: SlotKind,
+    /// Values read by the node.
+    pub parameters: Vec<Parameter>,
+    constructor: NodeConstructor,
+}
+
+impl NodeDescriptor {
+    pub fn create(&self, window: &Window) -> Result<Box<dyn Node>, NodeError> {
+        (self.constructor)(window)
+    }
+}
+
+/// Maps node names to constructors, e.g., for building flows from pipeline descriptions.
+///
+/// The default registry contains all built-in nodes, additional ones can be registered.
+pub struct NodeRegistry {
+    descriptors: Vec<NodeDescriptor>,
+}
+
+impl NodeRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        NodeRegistry {
+            descriptors: Vec::new(),
+        }
+    }
+
+    /// Registers a node type, replacing previous registrations with the same name.
+    pub fn register<N: Node + 'static>(&mut self, name: &str, description: &str, input: SlotKind, output: SlotKind) {
+        self.register_constructor(
+            name,
+            description,
+            input,
+            output,
+            N::schema(),
+            Box::new(|window| Ok(Box::new(N::new(window)?) as Box<dyn Node>)),
+        );
+    }
+
+    /// Registers a custom constructor, replacing previous registrations with the same name.
+    pub fn register_constructor(
+        &mut self,
+        name: &str,
+        description: &str,
+        input: SlotKind,
+        output: SlotKind,
+        parameters: Vec<Parameter>,
+        constructor: NodeConstructor,
+    ) {
+        let descriptor = NodeDescriptor {
+            name: name.to_string(),
+            description: description.to_string(),
+            input,
+            output,
+            parameters,
+            constructor,
+        };
+        if let Some(existing) = self.descriptors.iter_mut().find(|d| d.name == name) {
+            *existing = descriptor;
+        } else {
+            self.descriptors.push(descriptor);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&NodeDescriptor> {
+        self.descriptors.iter().find(|d| d.name == name)
+    }
+
+    /// All registered nodes, in order of registration.
+    pub fn descriptors(&self) -> &[NodeDescriptor] {
+        &self.descriptors
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.descriptors.iter().map(|d| d.name.as_str()).collect()
+    }
+
+    /// Describes the values read by all registered nodes.
+    pub fn parameters(&self) -> Vec<Parameter> {
+        let mut parameters = Vec::new();
+        for descriptor in self.descriptors.iter() {
+            extend_parameters(&mut parameters, descriptor.parameters.clone());
+        }
+        parameters
+    }
+
+    /// Creates a node by name, returns `None` for unknown names.
+    pub fn create(&self, name: &str, window: &Window) -> Option<Result<Box<dyn Node>, NodeError>> {
+        self.get(name).map(|descriptor| descriptor.create(window))
+    }
+}
+
+impl Default for NodeRegistry {
+    fn default() -> Self {
+        let mut registry = NodeRegistry::new();
+        registry.register::<Cataract>(
+            "cataract",
+            "Clouding of the lens (blur and contrast loss)",
+            SlotKind::ColorDepth,
+            SlotKind::ColorDepth,
+        );
+        registry.register::<Lens>(
+            "lens",
+            "Refractive errors (myopia, hyperopia, presbyopia, astigmatism)",
+            SlotKind::ColorDepth,
+            SlotKind::Color,
+        );
+        registry.register::<Floaters>(
+            "floaters",
+            "Drifting shadows of vitreous opacities that lag behind eye movements",
+            SlotKind::Color,
+            SlotKind::Color,
+        );
+        registry.register::<Retina>(
+            "retina",
+            "Retinal diseases based on a retina map (glaucoma, macular degeneration, ...)",
+            SlotKind::Color,
+            SlotKind::Color,
+        );
+        registry.register::<PeacockCB>(
+            "peacock",
+            "Color vision deficiencies",
+            SlotKind::Color,
+            SlotKind::Color,
+        );
+        registry.register::<VarianceMeasure>(
+            "variance",
+            "Measures the variance introduced by the simulation",
+            SlotKind::Color,
+            SlotKind::Color,
+        );
+        registry.register::<Display>(
+            "display",
+            "Visualizes the simulation and its uncertainty",
+            SlotKind::Color,
+            SlotKind::Color,
+        );
+        registry.register::<Compare>(
+            "compare",
+            "Compares its input with a reference input (split screen, blend, difference)",
+            SlotKind::Color,
+            SlotKind::Color,
+        );
+        registry.register::<Passthrough>(
+            "passthrough",
+            "Does not alter anything",
+            SlotKind::Any,
+            SlotKind::Any,
+        );
+        registry
+    }
+}
@@ -7,6 +7,11 @@ uniform vec2 u_resolution;
 uniform mat4 u_proj;
 uniform float u_achromatopsia_blur_factor;
 uniform int u_track_error;
+// Projects directions (see u_proj) to the screen.
+uniform mat4 u_view_proj;
+uniform int u_metamorphopsia;
+uniform float u_metamorphopsia_radius;
+uniform float u_metamorphopsia_amplitude;
 
 uniform sampler2D s_color;
 uniform samplerCube s_retina;
@@ -14,6 +19,8 @@ uniform sampler2D s_deflection;
 uniform sampler2D s_color_change;
 uniform sampler2D s_color_uncertainty;
 uniform sampler2D s_covariances;
+// Displacement of samples on the visual field (rg, 0.5: none), see retina_map/metamorphopsia.rs.
+uniform sampler2D s_distortion;
 
 in vec2 v_tex;
 out vec4 rt_color;
@@ -30,7 +37,19 @@ struct ErrorValues
   mat2 S_pos;
 };
 
-void applyBlurAndBloom(inout vec4 color, in vec4 retina, inout ErrorValues ev) {
+// Where to sample the input, displaced around the macula.
+vec2 metamorphopsia(vec3 direction) {
+    vec2 field = field_position(direction);
+    vec2 uv = field / (2.0 * u_metamorphopsia_radius) + 0.5;
+    if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+        return v_tex;
+    }
+    vec2 displacement = (texture(s_distortion, uv).rg * 255.0 - 128.0) / 127.0 * u_metamorphopsia_amplitude;
+    vec4 clip = u_view_proj * vec4(field_direction(field + displacement), 1.0);
+    return clip.xy / clip.w * 0.5 + 0.5;
+}
+
+void applyBlurAndBloom(inout vec4 color, in vec4 retina, inout ErrorValues ev, in vec2 tex) {
     float max_rgb = max(max(retina.r, retina.g), retina.b);
     float max_rgb_var = 0.0;
 
@@ -50,10 +69,10 @@ void applyBlurAndBloom(inout vec4 color, in vec4 retina, inout ErrorValues ev) {
         // luckily we can assume that the retina values do not have any uncertainty attached
         float blur_scale = (0.75 - (retina.r + retina.g + retina.b) / 3.0) * 5.0 * u_achromatopsia_blur_factor;
         if(u_track_error==1){
-            color =  blur_with_error(v_tex, s_color, blur_scale, u_resolution, ev.S_col, ev.S_pos, s_color_uncertainty, s_covariances, s_deflection);        
+            color =  blur_with_error(tex, s_color, blur_scale, u_resolution, ev.S_col, ev.S_pos, s_color_uncertainty, s_covariances, s_deflection);        
         }
         else{
-            color =  blur(v_tex, s_color, blur_scale, u_resolution);     
+            color =  blur(tex, s_color, blur_scale, u_resolution);     
         }
         // apply bloom
         //original calculation: 
@@ -178,6 +197,7 @@ void glaucoma(inout vec4 color, in vec4 retina_mask, inout ErrorValues ev) {
 void main() {
     vec3 fragment_dir = normalize((u_proj * vec4(v_tex*2.0-1.0, 0.9, 1.0)).xyz);
     vec4 retina_mask = texture(s_retina, fragment_dir);
+    vec2 tex = u_metamorphopsia != 0 ? metamorphopsia(fragment_dir) : v_tex;
     //vec4 world_dir = inverse(u_proj) * vec4(v_tex * 2.0 - 1.0, 0.9, 1.0);
     //vec4 retina_mask = texture(s_retina, normalize(world_dir.xyz)/world_dir.w);
 
@@ -187,10 +207,10 @@ void main() {
         Since the resulting color is constant, it does not make sense to have uncertainties regarding position
     */
     if (retina_mask == vec4(0)) {
-        vec4 color_diff = texture(s_color, v_tex) - GLAUCOMA_COLOR;
+        vec4 color_diff = texture(s_color, tex) - GLAUCOMA_COLOR;
         rt_color_change =    
             vec4(
-                texture(s_color_change, v_tex).rgb + color_diff.rgb,
+                texture(s_color_change, tex).rgb + color_diff.rgb,
                 0.0
             );
         rt_color_uncertainty = 
@@ -202,14 +222,14 @@ void main() {
         return;
     }
 
-    rt_color = texture(s_color, v_tex);
+    rt_color = texture(s_color, tex);
     vec3 original_color = rt_color.rgb;
-    vec3 color_error = texture(s_color_change, v_tex).rgb;
-    vec3 color_var = texture(s_color_uncertainty, v_tex).rgb;
-    vec3 color_covar = texture(s_covariances, v_tex).rgb;
+    vec3 color_error = texture(s_color_change, tex).rgb;
+    vec3 color_var = texture(s_color_uncertainty, tex).rgb;
+    vec3 color_covar = texture(s_covariances, tex).rgb;
 
-    vec2 dir_var = texture(s_deflection, v_tex).ba;
-    float dir_covar = texture(s_covariances, v_tex).a;
+    vec2 dir_var = texture(s_deflection, tex).ba;
+    float dir_covar = texture(s_covariances, tex).a;
 
     mat3 S_col = covarMatFromVec(color_var, color_covar);
     mat2 S_pos =covarMatFromVec(dir_var, dir_covar);
@@ -220,7 +240,7 @@ void main() {
         S_pos
     );
 
-    applyBlurAndBloom(rt_color, retina_mask, ev);
+    applyBlurAndBloom(rt_color, retina_mask, ev, tex);
     applyNyctalopia(rt_color, retina_mask, ev);
     applyColorBlindness(rt_color, retina_mask, ev);
     // //glaucoma should be one of the last ones because it could decrease the brightness a lot
@@ -231,9 +251,11 @@ void main() {
         covarMatToVec(ev.S_pos, dir_var, dir_covar);
         
         vec3 color_diff = rt_color.rgb - original_color;
-        rt_color_change = vec4(texture(s_color_change, v_tex).rgb + color_diff,0.0);
+        rt_color_change = vec4(texture(s_color_change, tex).rgb + color_diff,0.0);
         rt_color_uncertainty = vec4(color_var, 0.0);
-        rt_deflection = vec4(texture(s_deflection, v_tex).rg, dir_var);
+        // displaced samples are deflected, in the units of the lens
+        vec2 deflection = (v_tex - tex) * 2.0;
+        rt_deflection = vec4(texture(s_deflection, tex).rg + deflection, dir_var);
         rt_covariances = vec4(color_covar, dir_covar);
     }
 }
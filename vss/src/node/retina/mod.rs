@@ -1,6 +1,13 @@
 mod retina_map;
 
-use self::retina_map::generate_retina_map;
+use self::retina_map::{
+    generate_distortion_map, generate_retina_cubemap, resample_equirectangular, resample_polar,
+    RetinaMapGenerator,
+};
+pub use self::retina_map::{
+    equirectangular_retina_image, generate_retina_images, BlendMode, PerimetryMap, PerimetryPoint,
+    RetinaMapSettings, BINOCULAR_VALUES, CUBEMAP_FACE_NAMES,
+};
 use super::*;
 use cgmath::{Matrix4, Point3, SquareMatrix, Vector3};
 use gfx;
@@ -24,36 +31,219 @@ gfx_defines! {
         rt_covariances: gfx::RenderTarget<Rgba32F> = "rt_covariances",
         u_achromatopsia_blur_factor: gfx::Global<f32> = "u_achromatopsia_blur_factor",
         u_track_error: gfx::Global<i32> = "u_track_error",
+        u_view_proj: gfx::Global<[[f32; 4];4]> = "u_view_proj",
+        u_metamorphopsia: gfx::Global<i32> = "u_metamorphopsia",
+        u_metamorphopsia_radius: gfx::Global<f32> = "u_metamorphopsia_radius",
+        u_metamorphopsia_amplitude: gfx::Global<f32> = "u_metamorphopsia_amplitude",
+        s_distortion: gfx::TextureSampler<[f32; 4]> = "s_distortion",
+    }
+}
+
+/// Number of previous retina maps that are kept, such that switching back and forth does not regenerate
+/// them. Retina maps take a lot of video memory, hence only one.
+const RETINA_MAP_CACHE_SIZE: usize = 1;
+
+const RETINA_MAP_PATHS: [&str; 6] = [
+    "retina_map_pos_x_path",
+    "retina_map_neg_x_path",
+    "retina_map_pos_y_path",
+    "retina_map_neg_y_path",
+    "retina_map_pos_z_path",
+    "retina_map_neg_z_path",
+];
+
+/// What a retina map was made from, i.e., the key of cached retina maps.
+#[derive(Debug, Clone, PartialEq)]
+enum RetinaMapSource {
+    /// Loaded from the paths of all six faces.
+    Files(Vec<String>),
+    /// Resampled from a single equirectangular image.
+    Equirectangular(String),
+    /// Resampled from a polar visual field image that covers the given eccentricity (in degrees).
+    Polar { path: String, fov: f64 },
+    /// Generated from the settings, at the given face resolution. Perimetry results are not loaded
+    /// for this, but referred to by path, normal sensitivity, and blend mode.
+    Generated {
+        settings: RetinaMapSettings,
+        perimetry: Option<(String, Option<Value>, BlendMode)>,
+        resolution: u32,
+    },
+}
 
+impl RetinaMapSource {
+    /// Face resolution of generated retina maps, which follows the output resolution.
+    fn resolution(&self) -> Option<u32> {
+        match self {
+            RetinaMapSource::Generated { resolution, .. } => Some(*resolution),
+            _ => None,
+        }
     }
 }
 
 pub struct Retina {
     pso: gfx::PipelineState<Resources, pipe::Meta>,
     pso_data: pipe::Data<Resources>,
+    /// Recently used retina maps, the most recent one last.
+    maps: Vec<(RetinaMapSource, gfx::handle::ShaderResourceView<Resources, [f32; 4]>)>,
+    /// Generates retina maps on the GPU, if available (otherwise on the CPU).
+    generator: Option<RetinaMapGenerator>,
+    /// Why the last retina map could not be created, such that it is reported only once.
+    failed: Option<String>,
+    /// Path of the loaded distortion map of metamorphopsia, if not generated.
+    distortion_path: Option<String>,
+}
+
+impl Retina {
+    fn retina_map_source(&self, values: &ValueMap) -> Result<RetinaMapSource, NodeError> {
+        let paths: Vec<String> = RETINA_MAP_PATHS
+            .iter()
+            .filter_map(|key| values.get(*key).and_then(Value::as_str))
+            .map(|path| path.to_string())
+            .collect();
+        if paths.len() == RETINA_MAP_PATHS.len() {
+            return Ok(RetinaMapSource::Files(paths));
+        }
+        if !paths.is_empty() {
+            let missing: Vec<&str> = RETINA_MAP_PATHS
+                .iter()
+                .filter(|key| values.get(**key).and_then(Value::as_str).is_none())
+                .cloned()
+                .collect();
+            return Err(NodeError::Resource(format!("retina map faces missing: {}", missing.join(", "))));
+        }
+        if let Some(Value::String(path)) = values.get("retina_map_path") {
+            return Ok(RetinaMapSource::Equirectangular(path.clone()));
+        }
+        if let Some(Value::String(path)) = values.get("retina_map_polar_path") {
+            let fov = values.get("retina_map_polar_fov").and_then(Value::as_f64).unwrap_or(90.0);
+            return Ok(RetinaMapSource::Polar { path: path.clone(), fov });
+        }
+        let proj_val = Value::Matrix(Matrix4::from_scale(1.0));
+        let projection = values.get("proj_matrix").unwrap_or(&proj_val).as_matrix().unwrap();
+        let res_x = (self.pso_data.u_resolution[0] * 2.0 * projection[0][0]) as f32;
+        let res_y = (self.pso_data.u_resolution[1] * 2.0 * projection[1][1]) as f32;
+        let mut settings_values = values.clone();
+        let perimetry = match settings_values.remove("perimetry_path") {
+            Some(Value::String(path)) => Some((
+                path,
+                values.get("perimetry_normal").cloned(),
+                BlendMode::from_values(values, "perimetry_blend", "perimetry_weight"),
+            )),
+            _ => None,
+        };
+        Ok(RetinaMapSource::Generated {
+            settings: RetinaMapSettings::from_values(&settings_values).map_err(NodeError::Resource)?,
+            perimetry,
+            resolution: (res_x.max(res_y) + 1.0) as u32,
+        })
+    }
+
+    fn load_image(path: &str) -> Result<image::RgbaImage, NodeError> {
+        let data = try_load(path).map_err(|e| NodeError::Resource(format!("{}: {}", path, e)))?;
+        let image = image::load_from_memory(data.get_ref())
+            .map_err(|e| NodeError::Resource(format!("{}: {}", path, e)))?;
+        Ok(image.to_rgba8())
+    }
+
+    fn create_retina_map(
+        &mut self,
+        window: &Window,
+        source: &RetinaMapSource,
+        values: &ValueMap,
+    ) -> Result<gfx::handle::ShaderResourceView<Resources, [f32; 4]>, NodeError> {
+        match source {
+            RetinaMapSource::Files(paths) => {
+                let image_data = paths
+                    .iter()
+                    .map(|path| try_load(path).map_err(|e| NodeError::Resource(format!("{}: {}", path, e))))
+                    .collect::<Result<_, _>>()?;
+                let mut factory = window.factory().borrow_mut();
+                let (_, retinamap_view) = load_cubemap(&mut factory, image_data)
+                    .map_err(|e| NodeError::Resource(format!("retina map faces: {}", e)))?;
+                Ok(retinamap_view)
+            }
+            RetinaMapSource::Equirectangular(path) => {
+                let (resolution, faces) = resample_equirectangular(&Self::load_image(path)?);
+                Self::load_faces(window, resolution, faces)
+            }
+            RetinaMapSource::Polar { path, fov } => {
+                let (resolution, faces) = resample_polar(&Self::load_image(path)?, *fov as f32);
+                Self::load_faces(window, resolution, faces)
+            }
+            RetinaMapSource::Generated { resolution, .. } => {
+                let settings = RetinaMapSettings::from_values(values).map_err(NodeError::Resource)?;
+                if let Some(generator) = &mut self.generator {
+                    return generator.generate(window, *resolution, &settings);
+                }
+                let faces = generate_retina_cubemap(*resolution, &settings);
+                Self::load_faces(window, *resolution, faces)
+            }
+        }
+    }
+
+    /// Loads the distortion map of metamorphopsia, or generates it if no path is given.
+    fn create_distortion_map(
+        window: &Window,
+        path: Option<&str>,
+    ) -> Result<gfx::handle::ShaderResourceView<Resources, [f32; 4]>, NodeError> {
+        let image = match path {
+            Some(path) => Self::load_image(path)?,
+            None => generate_distortion_map(),
+        };
+        // The first row of images is superior, but the first row of textures is at the bottom.
+        let image = image::imageops::flip_vertical(&image);
+        let mut factory = window.factory().borrow_mut();
+        let (_, view) = load_texture_from_bytes(&mut factory, image.as_raw(), image.width(), image.height())
+            .map_err(NodeError::Resource)?;
+        Ok(view)
+    }
+
+    /// Uploads faces in the order of [CUBEMAP_FACE_NAMES].
+    fn load_faces(
+        window: &Window,
+        resolution: u32,
+        faces: Vec<Box<[u8]>>,
+    ) -> Result<gfx::handle::ShaderResourceView<Resources, [f32; 4]>, NodeError> {
+        let mut factory = window.factory().borrow_mut();
+        let (_, retinamap_view) = load_cubemap_from_bytes(
+            &mut factory,
+            &[&faces[0], &faces[1], &faces[2], &faces[3], &faces[4], &faces[5]],
+            resolution,
+        )
+        .map_err(NodeError::Resource)?;
+        Ok(retinamap_view)
+    }
 }
 
 impl Node for Retina {
-    fn new(window: &Window) -> Self {
+    fn new(window: &Window) -> Result<Self, NodeError> {
+        // Fall back to generating retina maps on the CPU, e.g., if the shader is not supported.
+        let generator = match RetinaMapGenerator::new(window) {
+            Ok(generator) => Some(generator),
+            Err(e) => {
+                println!("Generating retina maps on the CPU: {}", e);
+                None
+            }
+        };
+        let distortion_view = Self::create_distortion_map(window, None)?;
         let mut factory = window.factory().borrow_mut();
         let pso = factory
             .create_pipeline_simple(
                 &include_glsl!("../mod.vert"),
                 &include_glsl!("mod.frag"),
                 pipe::new(),
-            )
-            .unwrap();
+            )?;
 
-        let (_, mask_view) = load_cubemap_from_bytes(&mut factory, &[&[255; 4]; 6], 1).unwrap();
+        let (_, mask_view) = load_cubemap_from_bytes(&mut factory, &[&[255; 4]; 6], 1).map_err(NodeError::Resource)?;
         let sampler = factory.create_sampler_linear();
 
-        let (_, src, dst) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_deflection, rt_deflection) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_color_change, rt_color_change) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_color_uncertainty, rt_color_uncertainty) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_covariances, rt_covariances) = factory.create_render_target(1, 1).unwrap();
+        let (_, src, dst) = factory.create_render_target(1, 1)?;
+        let (_, s_deflection, rt_deflection) = factory.create_render_target(1, 1)?;
+        let (_, s_color_change, rt_color_change) = factory.create_render_target(1, 1)?;
+        let (_, s_color_uncertainty, rt_color_uncertainty) = factory.create_render_target(1, 1)?;
+        let (_, s_covariances, rt_covariances) = factory.create_render_target(1, 1)?;
 
-        Retina {
+        Ok(Retina {
             pso,
             pso_data: pipe::Data {
                 u_resolution: [1.0, 1.0],
@@ -70,95 +260,126 @@ impl Node for Retina {
                 s_covariances: (s_covariances, sampler.clone()),
                 rt_covariances,
                 u_achromatopsia_blur_factor: 1.0,
-                u_track_error: 0
-
+                u_track_error: 0,
+                u_view_proj: Matrix4::from_scale(1.0).into(),
+                u_metamorphopsia: 0,
+                u_metamorphopsia_radius: 10.0,
+                u_metamorphopsia_amplitude: 0.5,
+                s_distortion: (distortion_view, sampler.clone()),
             },
-        }
+            maps: Vec::new(),
+            generator,
+            failed: None,
+            distortion_path: None,
+        })
+    }
+
+    fn schema() -> Vec<Parameter> {
+        let mut parameters = vec![
+            Parameter::path("retina_map_pos_x_path", "Retina map face in +x direction (all six faces are required)"),
+            Parameter::path("retina_map_neg_x_path", "Retina map face in -x direction (all six faces are required)"),
+            Parameter::path("retina_map_pos_y_path", "Retina map face in +y direction (all six faces are required)"),
+            Parameter::path("retina_map_neg_y_path", "Retina map face in -y direction (all six faces are required)"),
+            Parameter::path("retina_map_pos_z_path", "Retina map face in +z direction (all six faces are required)"),
+            Parameter::path("retina_map_neg_z_path", "Retina map face in -z direction (all six faces are required)"),
+            Parameter::path("retina_map_path", "Retina map as a single equirectangular image (360° wide, 180° high, fovea at the center)"),
+            Parameter::path("retina_map_polar_path", "Retina map as a polar visual field image (fovea at the center, up is superior)"),
+            Parameter::number("retina_map_polar_fov", "°", (1.0, 180.0), 90.0, "Eccentricity at the closest edge of the polar visual field image"),
+            Parameter::number("achromatopsia_blur_factor", "", (0.0, 10.0), 1.0, "Blur caused by missing cones"),
+            Parameter::matrix("proj_matrix", "Projection used to size the generated retina map, usually set by VR headsets"),
+            Parameter::toggle("metamorphopsia_onoff", "Enables metamorphopsia (straight lines look wavy), e.g., with macular degeneration"),
+            Parameter::number("metamorphopsia_radius", "°", (1.0, 45.0), 10.0, "Eccentricity up to which samples are displaced (half the extent of the distortion map)"),
+            Parameter::number("metamorphopsia_amplitude", "°", (0.0, 5.0), 0.5, "Largest displacement of samples"),
+            Parameter::path("metamorphopsia_path", "Distortion map, e.g., from Amsler grid results (red/green: horizontal/vertical displacement, 128: none)"),
+        ];
+        parameters.extend(retina_map::parameters());
+        parameters
     }
 
-    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> NodeSlots {
-        let slots = slots.to_color_input(window).to_color_output(window);
-        self.pso_data.u_resolution = slots.output_size_f32();
-        self.pso_data.s_source = slots.as_color_view();
-        self.pso_data.rt_color = slots.as_color();
-        self.pso_data.s_deflection = slots.as_deflection_view();
-        self.pso_data.rt_deflection = slots.as_deflection();
-        self.pso_data.s_color_change = slots.as_color_change_view();
-        self.pso_data.rt_color_change = slots.as_color_change();  
-        self.pso_data.s_color_uncertainty = slots.as_color_uncertainty_view();
-        self.pso_data.rt_color_uncertainty = slots.as_color_uncertainty();
-        self.pso_data.s_covariances = slots.as_covariances_view();
-        self.pso_data.rt_covariances = slots.as_covariances();
-        slots
+    fn parameters(&self) -> Vec<Parameter> {
+        Self::schema()
     }
 
+    fn output_channels(&self) -> Vec<ChannelInfo> {
+        ERROR_CHANNELS.to_vec()
+    }
+
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
+        let slots = slots.to_color_input(window)?.to_color_output(window)?;
+        self.pso_data.u_resolution = slots.output_size_f32()?;
+        self.pso_data.s_source = slots.as_color_view()?;
+        self.pso_data.rt_color = slots.as_color()?;
+        self.pso_data.s_deflection = slots.as_deflection_view()?;
+        self.pso_data.rt_deflection = slots.as_deflection()?;
+        self.pso_data.s_color_change = slots.as_color_change_view()?;
+        self.pso_data.rt_color_change = slots.as_color_change()?;  
+        self.pso_data.s_color_uncertainty = slots.as_color_uncertainty_view()?;
+        self.pso_data.rt_color_uncertainty = slots.as_color_uncertainty()?;
+        self.pso_data.s_covariances = slots.as_covariances_view()?;
+        self.pso_data.rt_covariances = slots.as_covariances()?;
+        Ok(slots)
+    }
+
+    /// Regenerates the retina map only if values that affect it (or the resolution) changed.
     fn update_values(&mut self, window: &Window, values: &ValueMap) {
-        let mut factory = window.factory().borrow_mut();
-        let mut image_data = Vec::new();
-        if let Some(Value::Image(retina_map_pos_x_path)) = values.get("retina_map_pos_x_path") {
-            image_data.push(load(retina_map_pos_x_path));
-        }
-        if let Some(Value::Image(retina_map_neg_x_path)) = values.get("retina_map_neg_x_path") {
-            image_data.push(load(retina_map_neg_x_path));
-        }
-        if let Some(Value::Image(retina_map_pos_y_path)) = values.get("retina_map_pos_y_path") {
-            image_data.push(load(retina_map_pos_y_path));
+        if let Some(Value::Number(achromatopsia_blur_factor)) = values.get("achromatopsia_blur_factor") {
+            self.pso_data.u_achromatopsia_blur_factor = *achromatopsia_blur_factor as f32;
         }
-        if let Some(Value::Image(retina_map_neg_y_path)) = values.get("retina_map_neg_y_path") {
-            image_data.push(load(retina_map_neg_y_path));
+
+        self.pso_data.u_metamorphopsia = (values.get("metamorphopsia_onoff") == Some(&Value::Bool(true))) as i32;
+        if let Some(Value::Number(radius)) = values.get("metamorphopsia_radius") {
+            self.pso_data.u_metamorphopsia_radius = *radius as f32;
         }
-        if let Some(Value::Image(retina_map_pos_z_path)) = values.get("retina_map_pos_z_path") {
-            image_data.push(load(retina_map_pos_z_path));
+        if let Some(Value::Number(amplitude)) = values.get("metamorphopsia_amplitude") {
+            self.pso_data.u_metamorphopsia_amplitude = *amplitude as f32;
         }
-        if let Some(Value::Image(retina_map_neg_z_path)) = values.get("retina_map_neg_z_path") {
-            image_data.push(load(retina_map_neg_z_path));
+        let distortion_path = values.get("metamorphopsia_path").and_then(Value::as_str).map(str::to_string);
+        if distortion_path != self.distortion_path {
+            // Keep the previous distortion map on errors, reported once per path.
+            match Self::create_distortion_map(window, distortion_path.as_deref()) {
+                Ok(view) => self.pso_data.s_distortion.0 = view,
+                Err(e) => eprintln!("error: metamorphopsia: {}", e),
+            }
+            self.distortion_path = distortion_path;
         }
-        if let Some(Value::Number(achromatopsia_blur_factor)) = values.get("achromatopsia_blur_factor") {
-            self.pso_data.u_achromatopsia_blur_factor = *achromatopsia_blur_factor as f32;
+
+        let result = self.retina_map_source(values).and_then(|source| {
+            let retinamap_view = match self.maps.iter().position(|(cached, _)| *cached == source) {
+                Some(index) => self.maps.remove(index).1,
+                None => self.create_retina_map(window, &source, values)?,
+            };
+            Ok((source, retinamap_view))
+        });
+        match result {
+            Ok((source, retinamap_view)) => {
+                self.failed = None;
+                self.pso_data.s_retina = (retinamap_view.clone(), self.pso_data.s_retina.clone().1);
+                // Maps generated for another output resolution are not used again, e.g., after resizing.
+                if let Some(resolution) = source.resolution() {
+                    self.maps.retain(|(cached, _)| cached.resolution().map_or(true, |other| other == resolution));
+                }
+                self.maps.push((source, retinamap_view));
+                if self.maps.len() > RETINA_MAP_CACHE_SIZE + 1 {
+                    self.maps.remove(0);
+                }
+            }
+            Err(e) => {
+                // Keep the previous retina map, and report each problem once.
+                let message = e.to_string();
+                if self.failed.as_ref() != Some(&message) {
+                    eprintln!("error: retina map: {}", message);
+                }
+                self.failed = Some(message);
+            }
         }
-        if image_data.len() == 6 {
-            let (_, retinamap_view) = load_cubemap(&mut factory, image_data).unwrap();
-            self.pso_data.s_retina = (retinamap_view, self.pso_data.s_retina.clone().1);
-        } else {
-            let proj_val = Value::Matrix(Matrix4::from_scale(1.0));
-            let projection = values.get("proj_matrix").unwrap_or(&proj_val).as_matrix().unwrap();
-            let res_x = (self.pso_data.u_resolution[0] * 2.0 * projection[0][0]) as f32;
-            let res_y = (self.pso_data.u_resolution[1] * 2.0 * projection[1][1]) as f32;
-            let resolution = (res_x.max(res_y) + 1.0) as u32;
-            let cubemap_resolution = (
-                resolution,
-                resolution,
-            );
-
-            //orientations directly taken from https://www.khronos.org/opengl/wiki/Cubemap_Texture
-            let retina_map_pos_x = generate_retina_map(cubemap_resolution, &[-Vector3::unit_z(), -Vector3::unit_y(),  Vector3::unit_x()], &values);
-            let retina_map_neg_x = generate_retina_map(cubemap_resolution, &[ Vector3::unit_z(),  Vector3::unit_y(), -Vector3::unit_x()], &values);
-            let retina_map_pos_y = generate_retina_map(cubemap_resolution, &[ Vector3::unit_x(),  Vector3::unit_z(),  Vector3::unit_y()], &values);
-            let retina_map_neg_y = generate_retina_map(cubemap_resolution, &[ Vector3::unit_x(), -Vector3::unit_z(), -Vector3::unit_y()], &values);
-            let retina_map_pos_z = generate_retina_map(cubemap_resolution, &[ Vector3::unit_x(), -Vector3::unit_y(),  Vector3::unit_z()], &values);
-            let retina_map_neg_z = generate_retina_map(cubemap_resolution, &[-Vector3::unit_x(), -Vector3::unit_y(), -Vector3::unit_z()], &values);
-            //save latest retina map
-            //let _ = image::save_buffer(&Path::new("last.retina_pos_x.png"), &retina_map_pos_x, cubemap_resolution.0, cubemap_resolution.1, image::ColorType::Rgba8);
-            //let _ = image::save_buffer(&Path::new("last.retina_neg_x.png"), &retina_map_neg_x, cubemap_resolution.0, cubemap_resolution.1, image::ColorType::Rgba8);
-            //let _ = image::save_buffer(&Path::new("last.retina_pos_y.png"), &retina_map_pos_y, cubemap_resolution.0, cubemap_resolution.1, image::ColorType::Rgba8);
-            //let _ = image::save_buffer(&Path::new("last.retina_neg_y.png"), &retina_map_neg_y, cubemap_resolution.0, cubemap_resolution.1, image::ColorType::Rgba8);
-            //let _ = image::save_buffer(&Path::new("last.retina_pos_z.png"), &retina_map_pos_z, cubemap_resolution.0, cubemap_resolution.1, image::ColorType::Rgba8);
-            //let _ = image::save_buffer(&Path::new("last.retina_neg_z.png"), &retina_map_neg_z, cubemap_resolution.0, cubemap_resolution.1, image::ColorType::Rgba8);
-            let (_, retinamap_view) = load_cubemap_from_bytes(
-                &mut factory,
-                &[&retina_map_pos_x, &retina_map_neg_x, &retina_map_pos_y, &retina_map_neg_y, &retina_map_pos_z, &retina_map_neg_z],
-                cubemap_resolution.0,
-            )
-            .unwrap();
-
-            self.pso_data.s_retina = (retinamap_view, self.pso_data.s_retina.clone().1);
-        };
     }
 
     fn input(&mut self, perspective: &EyePerspective, vis_param: &VisualizationParameters) -> EyePerspective {
         let gaze_rotation = Matrix4::look_to_lh(Point3::new(0.0, 0.0, 0.0), perspective.gaze, Vector3::unit_y());
         //let gaze_rotation = Matrix4::from_scale(1.0);
-        self.pso_data.u_proj = (gaze_rotation * perspective.proj.invert().unwrap()).into();
+        let proj = gaze_rotation * perspective.proj.invert().unwrap();
+        self.pso_data.u_proj = proj.into();
+        self.pso_data.u_view_proj = proj.invert().unwrap_or_else(Matrix4::identity).into();
         //self.pso_data.u_proj = (head.proj * (Matrix4::from_translation(-head.position) * head.view)).into();
         self.pso_data.u_track_error = vis_param.has_to_track_error() as i32;
         perspective.clone()
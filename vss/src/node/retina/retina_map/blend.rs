@@ -0,0 +1,219 @@
+use rayon::prelude::*;
+
+use crate::*;
+
+/// Values that configure how the map of a disease is blended, as (disease, mode, weight).
+///
+/// Maps are blended in this order, which is also the order of [super::generate_retina_map].
+pub const BLEND_VALUES: [(&str, &str, &str); 10] = [
+    ("glaucoma", "glaucoma_blend", "glaucoma_weight"),
+    ("achromatopsia", "achromatopsia_blend", "achromatopsia_weight"),
+    ("nyctalopia", "nyctalopia_blend", "nyctalopia_weight"),
+    ("colorblindness", "colorblindness_blend", "colorblindness_weight"),
+    ("maculardegeneration", "maculardegeneration_blend", "maculardegeneration_weight"),
+    ("receptordensity", "receptordensity_blend", "receptordensity_weight"),
+    ("perimetry", "perimetry_blend", "perimetry_weight"),
+    ("diabeticretinopathy", "diabeticretinopathy_blend", "diabeticretinopathy_weight"),
+    ("retinitispigmentosa", "retinitispigmentosa_blend", "retinitispigmentosa_weight"),
+    ("hemianopia", "hemianopia_blend", "hemianopia_weight"),
+];
+
+/// How the map of a disease is combined with the maps before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    /// Per-channel minimum, i.e., only the strongest loss counts.
+    Min,
+    /// Remaining cells multiply, e.g., two maps with half of the cells leave a quarter.
+    Multiply,
+    /// Like [BlendMode::Multiply], with the loss of the map scaled by a weight (0 to 1).
+    Weighted(f32),
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::Min
+    }
+}
+
+impl BlendMode {
+    /// Reads the mode from values, e.g., `glaucoma_blend` and `glaucoma_weight`.
+    pub fn from_values(values: &ValueMap, mode: &str, weight: &str) -> Self {
+        match values.get(mode).and_then(Value::as_f64).map(|mode| mode as u8) {
+            Some(1) => BlendMode::Multiply,
+            Some(2) => {
+                let weight = values.get(weight).and_then(Value::as_f64).unwrap_or(1.0);
+                BlendMode::Weighted(weight.max(0.0).min(1.0) as f32)
+            }
+            _ => BlendMode::Min,
+        }
+    }
+
+    /// Index and weight of the mode, as used by the GPU generator.
+    pub fn uniforms(self) -> (i32, f32) {
+        match self {
+            BlendMode::Min => (0, 1.0),
+            BlendMode::Multiply => (1, 1.0),
+            BlendMode::Weighted(weight) => (2, weight),
+        }
+    }
+
+    /// Combines a channel of the map with the same channel of the maps before it.
+    pub fn blend(self, cells: u8, map: u8) -> u8 {
+        match self {
+            BlendMode::Min => cells.min(map),
+            BlendMode::Multiply => ((cells as u32 * map as u32 + 127) / 255) as u8,
+            BlendMode::Weighted(weight) => {
+                let factor = 1.0 - weight * (1.0 - map as f32 / 255.0);
+                (cells as f32 * factor).round() as u8
+            }
+        }
+    }
+}
+
+/// Describes the blend mode values of all diseases, see [BLEND_VALUES].
+pub fn blend_parameters() -> Vec<Parameter> {
+    BLEND_VALUES
+        .iter()
+        .flat_map(|(_, mode, weight)| {
+            vec![
+                Parameter::integer(mode, (0.0, 2.0), 0.0, "Blending with previous maps (0: minimum, 1: multiply, 2: weighted)"),
+                Parameter::number(weight, "", (0.0, 1.0), 1.0, "Weight of the loss if blended weighted"),
+            ]
+        })
+        .collect()
+}
+
+/// Blends the maps (in order) onto a white retina map.
+pub fn merge_maps(
+    maps: Vec<(image::ImageBuffer<image::Rgba<u8>, Vec<u8>>, BlendMode)>,
+    resolution: (u32, u32),
+) -> Box<[u8]> {
+    // generate white retina map as starting point
+    let mut merged = vec![255u8; (resolution.0 * resolution.1 * 4) as usize];
+
+    // for each pixel and each channel, blend all maps at this pixel and channel
+    let row_len = (resolution.0 * 4) as usize;
+    for (map, mode) in maps {
+        merged
+            .par_chunks_mut(row_len)
+            .zip(map.as_raw().par_chunks(row_len))
+            .for_each(|(row, map_row)| {
+                for (channel, map_channel) in row.iter_mut().zip(map_row.iter()) {
+                    *channel = mode.blend(*channel, *map_channel);
+                }
+            });
+    }
+
+    merged.into_boxed_slice()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::*;
+
+    const RESOLUTION: (u32, u32) = (4, 4);
+
+    fn constant(cells: [u8; 4]) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+        image::ImageBuffer::from_pixel(RESOLUTION.0, RESOLUTION.1, image::Rgba(cells))
+    }
+
+    fn merged_pixel(maps: Vec<([u8; 4], BlendMode)>) -> [u8; 4] {
+        let merged = merge_maps(
+            maps.into_iter().map(|(cells, mode)| (constant(cells), mode)).collect(),
+            RESOLUTION,
+        );
+        assert!(merged.chunks(4).all(|pixel| pixel == &merged[0..4]));
+        [merged[0], merged[1], merged[2], merged[3]]
+    }
+
+    #[test]
+    fn no_maps_are_white() {
+        assert_eq!(merged_pixel(vec![]), [255; 4]);
+    }
+
+    #[test]
+    fn min_keeps_the_strongest_loss() {
+        let merged = merged_pixel(vec![
+            ([128, 255, 64, 255], BlendMode::Min),
+            ([128, 128, 255, 0], BlendMode::Min),
+        ]);
+        assert_eq!(merged, [128, 128, 64, 0]);
+    }
+
+    #[test]
+    fn multiply_combines_losses() {
+        let merged = merged_pixel(vec![
+            ([128, 255, 0, 255], BlendMode::Multiply),
+            ([128, 128, 255, 255], BlendMode::Multiply),
+        ]);
+        assert_eq!(merged, [64, 128, 0, 255]);
+    }
+
+    #[test]
+    fn weighted_scales_the_loss() {
+        let half = BlendMode::Weighted(0.5);
+        assert_eq!(merged_pixel(vec![([0, 51, 255, 0], half)]), [128, 153, 255, 128]);
+        assert_eq!(merged_pixel(vec![([0; 4], BlendMode::Weighted(0.0))]), [255; 4]);
+        assert_eq!(
+            merged_pixel(vec![([128; 4], BlendMode::Weighted(1.0))]),
+            merged_pixel(vec![([128; 4], BlendMode::Multiply)])
+        );
+    }
+
+    #[test]
+    fn modes_apply_in_order() {
+        let min_first = merged_pixel(vec![([64; 4], BlendMode::Min), ([128; 4], BlendMode::Multiply)]);
+        let multiply_first = merged_pixel(vec![([128; 4], BlendMode::Multiply), ([64; 4], BlendMode::Min)]);
+        assert_eq!(min_first, [32; 4]);
+        assert_eq!(multiply_first, [64; 4]);
+    }
+
+    #[test]
+    fn modes_from_values() {
+        let mut values = ValueMap::new();
+        assert_eq!(BlendMode::from_values(&values, "glaucoma_blend", "glaucoma_weight"), BlendMode::Min);
+        values.insert("glaucoma_blend".to_string(), Value::Number(1.0));
+        assert_eq!(BlendMode::from_values(&values, "glaucoma_blend", "glaucoma_weight"), BlendMode::Multiply);
+        values.insert("glaucoma_blend".to_string(), Value::Number(2.0));
+        assert_eq!(BlendMode::from_values(&values, "glaucoma_blend", "glaucoma_weight"), BlendMode::Weighted(1.0));
+        values.insert("glaucoma_weight".to_string(), Value::Number(1.5));
+        assert_eq!(BlendMode::from_values(&values, "glaucoma_blend", "glaucoma_weight"), BlendMode::Weighted(1.0));
+        values.insert("glaucoma_weight".to_string(), Value::Number(0.25));
+        assert_eq!(BlendMode::from_values(&values, "glaucoma_blend", "glaucoma_weight"), BlendMode::Weighted(0.25));
+    }
+
+    #[test]
+    fn disabled_diseases_do_not_affect_settings() {
+        let mut values = ValueMap::new();
+        values.insert("achromatopsia_onoff".to_string(), Value::Bool(true));
+        let settings = RetinaMapSettings::from_values(&values).unwrap();
+        values.insert("glaucoma_fov".to_string(), Value::Number(50.0));
+        values.insert("glaucoma_blend".to_string(), Value::Number(2.0));
+        values.insert("glaucoma_weight".to_string(), Value::Number(0.5));
+        assert_eq!(RetinaMapSettings::from_values(&values).unwrap(), settings);
+        values.insert("achromatopsia_blend".to_string(), Value::Number(1.0));
+        assert_ne!(RetinaMapSettings::from_values(&values).unwrap(), settings);
+    }
+
+    fn combined_diseases(blend: f64, weight: f64) -> Box<[u8]> {
+        let mut values = ValueMap::new();
+        values.insert("achromatopsia_onoff".to_string(), Value::Bool(true));
+        values.insert("achromatopsia_int".to_string(), Value::Number(50.0));
+        values.insert("colorblindness_onoff".to_string(), Value::Bool(true));
+        values.insert("colorblindness_type".to_string(), Value::Number(0.0));
+        values.insert("colorblindness_int".to_string(), Value::Number(50.0));
+        values.insert("colorblindness_blend".to_string(), Value::Number(blend));
+        values.insert("colorblindness_weight".to_string(), Value::Number(weight));
+        let settings = RetinaMapSettings::from_values(&values).unwrap();
+        generate_retina_map(RESOLUTION, &cubemap_orientations()[5], &settings)
+    }
+
+    #[test]
+    fn combined_diseases_follow_the_blend_mode() {
+        // Both diseases leave 128 of the red cones, achromatopsia leaves 128 of the other cones.
+        assert_eq!(&combined_diseases(0.0, 1.0)[0..4], &[128, 128, 128, 255]);
+        assert_eq!(&combined_diseases(1.0, 1.0)[0..4], &[64, 128, 128, 255]);
+        assert_eq!(&combined_diseases(2.0, 0.5)[0..4], &[96, 128, 128, 255]);
+    }
+}
@@ -0,0 +1,160 @@
+use super::smoothstep;
+use cgmath::{InnerSpace, Vector3};
+
+use crate::*;
+
+/// Eccentricity (in degrees) up to which lesions are scattered, i.e., the posterior pole and the
+/// mid-periphery.
+const MAX_ECCENTRICITY: f32 = 50.0;
+
+/// Number of micro-scotomas, number of blot haemorrhages, and the largest haemorrhage radius (in
+/// degrees) per stage: mild NPDR, moderate NPDR, severe NPDR, and PDR.
+const STAGES: [(usize, usize, f32); 4] = [(12, 2, 1.5), (30, 8, 2.5), (60, 20, 3.0), (80, 30, 5.0)];
+
+/// A roughly circular region of the visual field with reduced cells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lesion {
+    pub direction: Vector3<f32>,
+    /// Angular radius in radians.
+    pub radius: f32,
+    /// Remaining cones and rods at the center (0 to 1).
+    pub cells: [f32; 2],
+    /// Relative width of the border, from sharp (close to 0) to fading from the center (1).
+    pub softness: f32,
+}
+
+impl Lesion {
+    /// Remaining cones and rods in the given direction (normalized).
+    pub fn cells(&self, direction: Vector3<f32>) -> [f32; 2] {
+        let distance = self.direction.dot(direction).max(-1.0).min(1.0).acos();
+        let t = smoothstep(self.radius * (1.0 - self.softness), self.radius, distance);
+        [
+            self.cells[0] + (1.0 - self.cells[0]) * t,
+            self.cells[1] + (1.0 - self.cells[1]) * t,
+        ]
+    }
+}
+
+/// Direction of a position on the visual field in degrees, see [super::perimetry::field_position].
+fn field_direction(x: f32, y: f32) -> Vector3<f32> {
+    let eccentricity = x.hypot(y).to_radians();
+    let azimuth = y.atan2(x);
+    Vector3::new(
+        eccentricity.sin() * azimuth.cos(),
+        eccentricity.sin() * azimuth.sin(),
+        -eccentricity.cos(),
+    )
+}
+
+/// A random position on the visual field (in degrees), uniformly distributed up to an eccentricity.
+fn random_position(random: &mut SeededRandom, max_eccentricity: f32) -> (f32, f32) {
+    let eccentricity = max_eccentricity * random.next_f32().sqrt();
+    let azimuth = random.range(0.0, 2.0 * std::f32::consts::PI);
+    (eccentricity * azimuth.cos(), eccentricity * azimuth.sin())
+}
+
+///
+/// Scatters the lesions of diabetic retinopathy over the visual field.
+///
+/// # Arguments
+///
+/// - `stage` - 0: mild NPDR, 1: moderate NPDR, 2: severe NPDR, 3: PDR
+/// - `edema` - adds macular edema, which blurs central vision
+/// - `seed`  - the same seed always results in the same lesions
+///
+pub fn lesions(stage: u8, edema: bool, seed: u64) -> Vec<Lesion> {
+    let mut random = SeededRandom::new(seed);
+    let (scotomas, haemorrhages, max_radius) = STAGES[(stage as usize).min(STAGES.len() - 1)];
+    let mut lesions = Vec::new();
+
+    // micro-scotomas, e.g., from microaneurysms and capillary dropout
+    for _ in 0..scotomas {
+        let (x, y) = random_position(&mut random, MAX_ECCENTRICITY);
+        lesions.push(Lesion {
+            direction: field_direction(x, y),
+            radius: random.range(0.3, 0.8).to_radians(),
+            cells: [0.0, 0.0],
+            softness: 0.2,
+        });
+    }
+
+    // blot haemorrhages, made of a few overlapping blobs to look irregular
+    for _ in 0..haemorrhages {
+        let (x, y) = random_position(&mut random, MAX_ECCENTRICITY);
+        let radius = random.range(1.0, max_radius);
+        for _ in 0..3 {
+            let (dx, dy) = random_position(&mut random, 0.5 * radius);
+            lesions.push(Lesion {
+                direction: field_direction(x + dx, y + dy),
+                radius: (radius * random.range(0.5, 1.0)).to_radians(),
+                cells: [0.1, 0.1],
+                softness: 0.5,
+            });
+        }
+    }
+
+    // vitreous haemorrhages of proliferative retinopathy, which veil (and blur) larger regions
+    if stage >= 3 {
+        for _ in 0..3 {
+            let (x, y) = random_position(&mut random, MAX_ECCENTRICITY);
+            lesions.push(Lesion {
+                direction: field_direction(x, y),
+                radius: random.range(6.0, 12.0).to_radians(),
+                cells: [0.35, 0.35],
+                softness: 1.0,
+            });
+        }
+    }
+
+    // macular edema, blurring (but not darkening) the center
+    if edema {
+        let (x, y) = random_position(&mut random, 2.0);
+        lesions.push(Lesion {
+            direction: field_direction(x, y),
+            radius: random.range(4.0, 7.0).to_radians(),
+            cells: [0.45, 1.0],
+            softness: 1.0,
+        });
+    }
+
+    lesions
+}
+
+///
+/// Creates a retina map that can be used to simulate diabetic retinopathy.
+///
+/// # Arguments
+///
+/// - `res`         - resolution of the returned retina map
+/// - `orientation` - right/up/forward vectors
+/// - `lesions`     - lesions as returned by [lesions]
+///
+pub fn generate(
+    res: (u32, u32),
+    orientation: &[Vector3<f32>; 3],
+    lesions: &[Lesion],
+) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+    let mut mapbuffer = image::ImageBuffer::new(res.0, res.1);
+    // skips lesions that do not cover a direction, without computing the angle
+    let bounds: Vec<f32> = lesions.iter().map(|lesion| lesion.radius.cos()).collect();
+
+    for (x, y, pixel) in mapbuffer.enumerate_pixels_mut() {
+        let right = (((x as f32 + 0.5) / res.0 as f32) * 2.0 - 1.0) * orientation[0];
+        let up = (((y as f32 + 0.5) / res.1 as f32) * 2.0 - 1.0) * orientation[1];
+        let direction = (right + up + orientation[2]).normalize();
+
+        let mut cells = [1.0f32, 1.0];
+        for (lesion, bound) in lesions.iter().zip(bounds.iter()) {
+            if lesion.direction.dot(direction) < *bound {
+                continue;
+            }
+            let lesion_cells = lesion.cells(direction);
+            cells = [cells[0].min(lesion_cells[0]), cells[1].min(lesion_cells[1])];
+        }
+        let cones = (cells[0] * 255.0) as u8;
+        let rods = (cells[1] * 255.0) as u8;
+        *pixel = image::Rgba([cones, cones, cones, rods]);
+    }
+
+    mapbuffer
+}
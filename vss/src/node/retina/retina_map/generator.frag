@@ -0,0 +1,217 @@
+// Generates one face of a retina map, see retina_map/mod.rs for the CPU version.
+
+#include "common.glsl"
+
+#define MACULAR_SIZE 0.3199770295315
+// Width of the border of the ring scotoma in degrees, see retinitis_pigmentosa.rs.
+#define RETINITIS_PIGMENTOSA_BORDER 2.0
+
+uniform vec3 u_right;
+uniform vec3 u_up;
+uniform vec3 u_forward;
+uniform float u_resolution;
+
+uniform int u_glaucoma;
+uniform float u_glaucoma_severity;
+uniform int u_achromatopsia;
+uniform float u_achromatopsia_severity;
+uniform int u_nyctalopia;
+uniform float u_nyctalopia_severity;
+uniform int u_colorblindness;
+uniform int u_colorblindness_type;
+uniform float u_colorblindness_severity;
+uniform int u_macular_degeneration;
+uniform float u_macular_degeneration_radius;
+uniform float u_macular_degeneration_intensity;
+uniform int u_receptor_density;
+uniform int u_perimetry;
+uniform float u_perimetry_extent;
+// Number of lesions of diabetic retinopathy.
+uniform int u_diabetic_retinopathy;
+uniform int u_retinitis_pigmentosa;
+uniform float u_retinitis_pigmentosa_inner;
+uniform float u_retinitis_pigmentosa_outer;
+uniform float u_retinitis_pigmentosa_rods;
+uniform int u_hemianopia;
+// Affected side (-1: left, 1: right) and quadrant (0: both, 1: superior, -1: inferior).
+uniform float u_hemianopia_side;
+uniform float u_hemianopia_quadrant;
+uniform float u_hemianopia_sparing;
+uniform float u_hemianopia_border;
+
+// Blend modes (0: minimum, 1: multiply, 2: weighted) and weights of the maps, see BlendMode.
+uniform int u_glaucoma_blend;
+uniform float u_glaucoma_weight;
+uniform int u_achromatopsia_blend;
+uniform float u_achromatopsia_weight;
+uniform int u_nyctalopia_blend;
+uniform float u_nyctalopia_weight;
+uniform int u_colorblindness_blend;
+uniform float u_colorblindness_weight;
+uniform int u_macular_degeneration_blend;
+uniform float u_macular_degeneration_weight;
+uniform int u_receptor_density_blend;
+uniform float u_receptor_density_weight;
+uniform int u_perimetry_blend;
+uniform float u_perimetry_weight;
+uniform int u_diabetic_retinopathy_blend;
+uniform float u_diabetic_retinopathy_weight;
+uniform int u_retinitis_pigmentosa_blend;
+uniform float u_retinitis_pigmentosa_weight;
+uniform int u_hemianopia_blend;
+uniform float u_hemianopia_weight;
+
+// Normalized cone (r) and rod (g) densities from -110 to 110 degrees eccentricity.
+uniform sampler2D s_osterberg;
+// Interpolated perimetry results (r), covering -u_perimetry_extent to u_perimetry_extent degrees.
+uniform sampler2D s_perimetry;
+// Lesions, one per column: direction and radius (first row), cones, rods, and softness (second row).
+uniform highp sampler2D s_lesions;
+
+in vec2 v_tex;
+out vec4 rt_color;
+
+float gaussian(float x, float sigma) {
+    return exp(-x * x / (2.0 * sigma * sigma));
+}
+
+float glaucoma(float angle) {
+    if (u_glaucoma_severity > 98.0) {
+        return 0.0;
+    }
+    float severity = u_glaucoma_severity / 100.0;
+    float border_width = 0.3;
+    if (severity >= 0.8) {
+        border_width = 0.3 * (1.0 - severity);
+    } else if (severity < 0.2) {
+        border_width = 0.3 * severity;
+    }
+    float factor = 1.0 - severity;
+    if (angle <= factor) {
+        return 1.0;
+    }
+    // partly affected cells just outside the healthy circle
+    return border_width > 0.0 ? clamp((border_width - (angle - factor)) / border_width, 0.0, 1.0) : 0.0;
+}
+
+float macular_degeneration(float angle) {
+    float affected_angle = u_macular_degeneration_radius * MACULAR_SIZE / 2.0;
+    // enlarges the black spot in the center.
+    float spot_factor = 1.78;
+    // ensures the outer areas are zero.
+    float tail_factor = 0.72;
+    float relative_falloff = 1.0 - max(affected_angle - angle, 0.0) / affected_angle;
+    float x = spot_factor * exp(-relative_falloff) - tail_factor;
+    return 1.0 - clamp(x * u_macular_degeneration_intensity, 0.0, 1.0);
+}
+
+vec4 receptor_density(vec2 tu) {
+    vec2 density = texture(s_osterberg, vec2(tu.x, 0.5)).rg;
+
+    //XXX: this is crude, but okay for now.
+    float alpha = gaussian(tu.y * 2.0 - 1.0, 0.125);
+    float base_density = max(gaussian(tu.x * 2.0 - 1.0, 0.35) - 0.14, 0.0)
+        * max(gaussian(tu.y * 2.0 - 1.0, 0.5) - 0.14, 0.0);
+    float c = alpha * density.r + (1.0 - alpha) * base_density;
+    float r = alpha * density.g + (1.0 - alpha) * base_density;
+    return vec4(c, c, c, r);
+}
+
+// Combines a map with the maps before it, see BlendMode::blend.
+vec4 blend(vec4 cells, vec4 map, int mode, float weight) {
+    if (mode == 0) {
+        return min(cells, map);
+    }
+    return cells * (1.0 - weight * (1.0 - map));
+}
+
+vec4 retinitis_pigmentosa(float angle) {
+    float eccentricity = degrees(angle);
+    float inner = u_retinitis_pigmentosa_inner;
+    float outer = u_retinitis_pigmentosa_outer;
+    float scotoma = smoothstep(inner - RETINITIS_PIGMENTOSA_BORDER, inner, eccentricity)
+        * (1.0 - smoothstep(outer, outer + RETINITIS_PIGMENTOSA_BORDER, eccentricity));
+    float cones = 1.0 - scotoma;
+    // rods degenerate first, causing night blindness also outside the ring
+    return vec4(vec3(cones), min(cones, 1.0 - u_retinitis_pigmentosa_rods));
+}
+
+// Remaining cones and rods, see diabetic_retinopathy::Lesion::cells.
+vec2 lesions(highp vec3 direction) {
+    vec2 cells = vec2(1.0);
+    for (int i = 0; i < u_diabetic_retinopathy; i++) {
+        highp vec4 lesion = texelFetch(s_lesions, ivec2(i, 0), 0);
+        if (dot(lesion.xyz, direction) < cos(lesion.w)) {
+            continue;
+        }
+        vec4 lesion_cells = texelFetch(s_lesions, ivec2(i, 1), 0);
+        highp float distance = acos(clamp(dot(lesion.xyz, direction), -1.0, 1.0));
+        float t = smoothstep(lesion.w * (1.0 - lesion_cells.z), lesion.w, distance);
+        cells = min(cells, mix(lesion_cells.xy, vec2(1.0), t));
+    }
+    return cells;
+}
+
+// Remaining cells, see hemianopia.rs.
+float hemianopia(vec2 field) {
+    float border = u_hemianopia_border / 2.0;
+    float loss = smoothstep(-border, border, u_hemianopia_side * field.x);
+    if (u_hemianopia_quadrant != 0.0) {
+        loss *= smoothstep(-border, border, u_hemianopia_quadrant * field.y);
+    }
+    if (u_hemianopia_sparing > 0.0) {
+        loss *= smoothstep(u_hemianopia_sparing, u_hemianopia_sparing + u_hemianopia_border, length(field));
+    }
+    return 1.0 - loss;
+}
+
+void main() {
+    vec2 pos = v_tex * 2.0 - 1.0;
+    vec3 direction = normalize(pos.x * u_right + pos.y * u_up + u_forward);
+    // Result of OpenGL being right-handed
+    float angle = acos(clamp(dot(vec3(0.0, 0.0, -1.0), direction), -1.0, 1.0));
+
+    // blend all maps in order
+    vec4 cells = vec4(1.0);
+    if (u_glaucoma != 0) {
+        cells = blend(cells, vec4(glaucoma(angle)), u_glaucoma_blend, u_glaucoma_weight);
+    }
+    if (u_achromatopsia != 0) {
+        vec4 achromatopsia = vec4(vec3(1.0 - u_achromatopsia_severity / 100.0), 1.0);
+        cells = blend(cells, achromatopsia, u_achromatopsia_blend, u_achromatopsia_weight);
+    }
+    if (u_nyctalopia != 0) {
+        vec4 nyctalopia = vec4(vec3(1.0), 1.0 - u_nyctalopia_severity / 100.0);
+        cells = blend(cells, nyctalopia, u_nyctalopia_blend, u_nyctalopia_weight);
+    }
+    if (u_colorblindness != 0) {
+        vec4 colorblindness = vec4(1.0);
+        colorblindness[u_colorblindness_type] = 1.0 - u_colorblindness_severity / 100.0;
+        cells = blend(cells, colorblindness, u_colorblindness_blend, u_colorblindness_weight);
+    }
+    if (u_macular_degeneration != 0) {
+        vec4 macula = vec4(macular_degeneration(angle));
+        cells = blend(cells, macula, u_macular_degeneration_blend, u_macular_degeneration_weight);
+    }
+    if (u_receptor_density != 0) {
+        vec4 density = receptor_density(floor(gl_FragCoord.xy) / u_resolution);
+        cells = blend(cells, density, u_receptor_density_blend, u_receptor_density_weight);
+    }
+    if (u_perimetry != 0) {
+        vec2 uv = field_position(direction) / (2.0 * u_perimetry_extent) + 0.5;
+        cells = blend(cells, vec4(texture(s_perimetry, uv).r), u_perimetry_blend, u_perimetry_weight);
+    }
+    if (u_diabetic_retinopathy != 0) {
+        vec2 retinopathy = lesions(direction);
+        cells = blend(cells, vec4(retinopathy.xxx, retinopathy.y), u_diabetic_retinopathy_blend, u_diabetic_retinopathy_weight);
+    }
+    if (u_retinitis_pigmentosa != 0) {
+        vec4 ring = retinitis_pigmentosa(angle);
+        cells = blend(cells, ring, u_retinitis_pigmentosa_blend, u_retinitis_pigmentosa_weight);
+    }
+    if (u_hemianopia != 0) {
+        vec4 field_loss = vec4(hemianopia(field_position(direction)));
+        cells = blend(cells, field_loss, u_hemianopia_blend, u_hemianopia_weight);
+    }
+    rt_color = cells;
+}
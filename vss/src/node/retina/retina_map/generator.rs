@@ -0,0 +1,345 @@
+use super::osterberg::*;
+use super::*;
+use crate::node::*;
+use gfx;
+use gfx::format::R8_G8_B8_A8;
+use gfx::memory::Typed;
+use gfx::texture;
+
+/// Width of the lookup texture for receptor densities.
+const OSTERBERG_SAMPLES: usize = 2048;
+
+gfx_defines! {
+    pipeline pipe {
+        u_right: gfx::Global<[f32; 3]> = "u_right",
+        u_up: gfx::Global<[f32; 3]> = "u_up",
+        u_forward: gfx::Global<[f32; 3]> = "u_forward",
+        u_resolution: gfx::Global<f32> = "u_resolution",
+        u_glaucoma: gfx::Global<i32> = "u_glaucoma",
+        u_glaucoma_severity: gfx::Global<f32> = "u_glaucoma_severity",
+        u_achromatopsia: gfx::Global<i32> = "u_achromatopsia",
+        u_achromatopsia_severity: gfx::Global<f32> = "u_achromatopsia_severity",
+        u_nyctalopia: gfx::Global<i32> = "u_nyctalopia",
+        u_nyctalopia_severity: gfx::Global<f32> = "u_nyctalopia_severity",
+        u_colorblindness: gfx::Global<i32> = "u_colorblindness",
+        u_colorblindness_type: gfx::Global<i32> = "u_colorblindness_type",
+        u_colorblindness_severity: gfx::Global<f32> = "u_colorblindness_severity",
+        u_macular_degeneration: gfx::Global<i32> = "u_macular_degeneration",
+        u_macular_degeneration_radius: gfx::Global<f32> = "u_macular_degeneration_radius",
+        u_macular_degeneration_intensity: gfx::Global<f32> = "u_macular_degeneration_intensity",
+        u_receptor_density: gfx::Global<i32> = "u_receptor_density",
+        u_perimetry: gfx::Global<i32> = "u_perimetry",
+        u_perimetry_extent: gfx::Global<f32> = "u_perimetry_extent",
+        u_glaucoma_blend: gfx::Global<i32> = "u_glaucoma_blend",
+        u_glaucoma_weight: gfx::Global<f32> = "u_glaucoma_weight",
+        u_achromatopsia_blend: gfx::Global<i32> = "u_achromatopsia_blend",
+        u_achromatopsia_weight: gfx::Global<f32> = "u_achromatopsia_weight",
+        u_nyctalopia_blend: gfx::Global<i32> = "u_nyctalopia_blend",
+        u_nyctalopia_weight: gfx::Global<f32> = "u_nyctalopia_weight",
+        u_colorblindness_blend: gfx::Global<i32> = "u_colorblindness_blend",
+        u_colorblindness_weight: gfx::Global<f32> = "u_colorblindness_weight",
+        u_macular_degeneration_blend: gfx::Global<i32> = "u_macular_degeneration_blend",
+        u_macular_degeneration_weight: gfx::Global<f32> = "u_macular_degeneration_weight",
+        u_receptor_density_blend: gfx::Global<i32> = "u_receptor_density_blend",
+        u_receptor_density_weight: gfx::Global<f32> = "u_receptor_density_weight",
+        u_perimetry_blend: gfx::Global<i32> = "u_perimetry_blend",
+        u_perimetry_weight: gfx::Global<f32> = "u_perimetry_weight",
+        u_diabetic_retinopathy: gfx::Global<i32> = "u_diabetic_retinopathy",
+        u_diabetic_retinopathy_blend: gfx::Global<i32> = "u_diabetic_retinopathy_blend",
+        u_diabetic_retinopathy_weight: gfx::Global<f32> = "u_diabetic_retinopathy_weight",
+        u_retinitis_pigmentosa: gfx::Global<i32> = "u_retinitis_pigmentosa",
+        u_retinitis_pigmentosa_inner: gfx::Global<f32> = "u_retinitis_pigmentosa_inner",
+        u_retinitis_pigmentosa_outer: gfx::Global<f32> = "u_retinitis_pigmentosa_outer",
+        u_retinitis_pigmentosa_rods: gfx::Global<f32> = "u_retinitis_pigmentosa_rods",
+        u_retinitis_pigmentosa_blend: gfx::Global<i32> = "u_retinitis_pigmentosa_blend",
+        u_retinitis_pigmentosa_weight: gfx::Global<f32> = "u_retinitis_pigmentosa_weight",
+        u_hemianopia: gfx::Global<i32> = "u_hemianopia",
+        u_hemianopia_side: gfx::Global<f32> = "u_hemianopia_side",
+        u_hemianopia_quadrant: gfx::Global<f32> = "u_hemianopia_quadrant",
+        u_hemianopia_sparing: gfx::Global<f32> = "u_hemianopia_sparing",
+        u_hemianopia_border: gfx::Global<f32> = "u_hemianopia_border",
+        u_hemianopia_blend: gfx::Global<i32> = "u_hemianopia_blend",
+        u_hemianopia_weight: gfx::Global<f32> = "u_hemianopia_weight",
+        s_osterberg: gfx::TextureSampler<[f32; 4]> = "s_osterberg",
+        s_perimetry: gfx::TextureSampler<[f32; 4]> = "s_perimetry",
+        s_lesions: gfx::TextureSampler<[f32; 4]> = "s_lesions",
+        rt_color: gfx::RenderTarget<ColorFormat> = "rt_color",
+    }
+}
+
+/// Generates retina maps on the GPU, such that changing severities takes effect within a frame.
+///
+/// Each face is rendered to a face-sized target and then copied into the cubemap (on the GPU),
+/// because rendering to cubemap faces directly requires OpenGL 4.5.
+pub struct RetinaMapGenerator {
+    pso: gfx::PipelineState<Resources, pipe::Meta>,
+    pso_data: pipe::Data<Resources>,
+    face: Option<gfx::handle::Texture<Resources, R8_G8_B8_A8>>,
+}
+
+impl RetinaMapGenerator {
+    pub fn new(window: &Window) -> Result<Self, NodeError> {
+        let mut factory = window.factory().borrow_mut();
+        let pso = factory.create_pipeline_simple(
+            &include_glsl!("../../mod.vert"),
+            &include_glsl!("generator.frag"),
+            pipe::new(),
+        )?;
+
+        let mut densities = Vec::with_capacity(OSTERBERG_SAMPLES * 4);
+        for i in 0..OSTERBERG_SAMPLES {
+            let t = (i as f32 + 0.5) / OSTERBERG_SAMPLES as f32;
+            let (cone_density, rod_density) = osterberg(t * 220.0 - 110.0);
+            densities.push((cone_density / CONE_DENSITY_MAX * 255.0) as u8);
+            densities.push((rod_density / ROD_DENSITY_MAX * 255.0) as u8);
+            densities.extend_from_slice(&[0, 255]);
+        }
+        let (_, osterberg_view) = load_texture_from_bytes(&mut factory, &densities, OSTERBERG_SAMPLES as u32, 1)
+            .map_err(NodeError::Resource)?;
+        let (_, perimetry_view) = load_texture_from_bytes(&mut factory, &[255; 4], 1, 1).map_err(NodeError::Resource)?;
+        let (_, lesions_view) = Self::lesion_texture(&mut factory, &[])?;
+        let (_, _, dst) = factory.create_render_target(1, 1)?;
+        let sampler = factory.create_sampler_linear();
+        let nearest_sampler = create_sampler_nearest(&mut factory);
+
+        Ok(RetinaMapGenerator {
+            pso,
+            pso_data: pipe::Data {
+                u_right: [1.0, 0.0, 0.0],
+                u_up: [0.0, 1.0, 0.0],
+                u_forward: [0.0, 0.0, -1.0],
+                u_resolution: 1.0,
+                u_glaucoma: 0,
+                u_glaucoma_severity: 0.0,
+                u_achromatopsia: 0,
+                u_achromatopsia_severity: 0.0,
+                u_nyctalopia: 0,
+                u_nyctalopia_severity: 0.0,
+                u_colorblindness: 0,
+                u_colorblindness_type: 0,
+                u_colorblindness_severity: 0.0,
+                u_macular_degeneration: 0,
+                u_macular_degeneration_radius: 0.0,
+                u_macular_degeneration_intensity: 0.0,
+                u_receptor_density: 0,
+                u_perimetry: 0,
+                u_perimetry_extent: 90.0,
+                u_glaucoma_blend: 0,
+                u_glaucoma_weight: 1.0,
+                u_achromatopsia_blend: 0,
+                u_achromatopsia_weight: 1.0,
+                u_nyctalopia_blend: 0,
+                u_nyctalopia_weight: 1.0,
+                u_colorblindness_blend: 0,
+                u_colorblindness_weight: 1.0,
+                u_macular_degeneration_blend: 0,
+                u_macular_degeneration_weight: 1.0,
+                u_receptor_density_blend: 0,
+                u_receptor_density_weight: 1.0,
+                u_perimetry_blend: 0,
+                u_perimetry_weight: 1.0,
+                u_diabetic_retinopathy: 0,
+                u_diabetic_retinopathy_blend: 0,
+                u_diabetic_retinopathy_weight: 1.0,
+                u_retinitis_pigmentosa: 0,
+                u_retinitis_pigmentosa_inner: 0.0,
+                u_retinitis_pigmentosa_outer: 0.0,
+                u_retinitis_pigmentosa_rods: 0.0,
+                u_retinitis_pigmentosa_blend: 0,
+                u_retinitis_pigmentosa_weight: 1.0,
+                u_hemianopia: 0,
+                u_hemianopia_side: -1.0,
+                u_hemianopia_quadrant: 0.0,
+                u_hemianopia_sparing: 0.0,
+                u_hemianopia_border: 1.0,
+                u_hemianopia_blend: 0,
+                u_hemianopia_weight: 1.0,
+                s_osterberg: (osterberg_view, sampler.clone()),
+                s_perimetry: (perimetry_view, sampler.clone()),
+                s_lesions: (lesions_view, nearest_sampler),
+                rt_color: dst,
+            },
+            face: None,
+        })
+    }
+
+    /// Renders a retina map with faces of the given size, see [generate_retina_cubemap].
+    pub fn generate(
+        &mut self,
+        window: &Window,
+        resolution: u32,
+        settings: &RetinaMapSettings,
+    ) -> Result<gfx::handle::ShaderResourceView<Resources, [f32; 4]>, NodeError> {
+        let mut factory = window.factory().borrow_mut();
+        let size = resolution as texture::Size;
+        let cubemap = factory
+            .create_texture::<R8_G8_B8_A8>(
+                texture::Kind::Cube(size),
+                1,
+                gfx::memory::Bind::SHADER_RESOURCE | gfx::memory::Bind::TRANSFER_DST,
+                gfx::memory::Usage::Data,
+                Some(gfx::format::ChannelType::Unorm),
+            )
+            .map_err(gfx::CombinedError::from)?;
+        let view = factory
+            .view_texture_as_shader_resource::<ColorFormat>(&cubemap, (0, 0), gfx::format::Swizzle::new())
+            .map_err(gfx::CombinedError::from)?;
+
+        let face_size = self.face.as_ref().map(|face| face.get_info().kind.get_dimensions().0);
+        if face_size != Some(size) {
+            let face = factory
+                .create_texture::<R8_G8_B8_A8>(
+                    texture::Kind::D2(size, size, texture::AaMode::Single),
+                    1,
+                    gfx::memory::Bind::RENDER_TARGET | gfx::memory::Bind::TRANSFER_SRC,
+                    gfx::memory::Usage::Data,
+                    Some(gfx::format::ChannelType::Unorm),
+                )
+                .map_err(gfx::CombinedError::from)?;
+            self.pso_data.rt_color = factory
+                .view_texture_as_render_target::<ColorFormat>(&face, 0, None)
+                .map_err(gfx::CombinedError::from)?;
+            self.face = Some(face);
+        }
+        let face = self.face.as_ref().unwrap();
+
+        if let Some(map) = &settings.perimetry {
+            let cells: Vec<u8> = map.cells.iter().flat_map(|cells| vec![*cells; 4]).collect();
+            let (_, perimetry_view) =
+                load_texture_from_bytes(&mut factory, &cells, map.size, map.size).map_err(NodeError::Resource)?;
+            self.pso_data.s_perimetry.0 = perimetry_view;
+            self.pso_data.u_perimetry_extent = map.extent;
+        }
+        self.pso_data.u_perimetry = settings.perimetry.is_some() as i32;
+
+        let lesions = settings.diabetic_retinopathy_lesions().unwrap_or_default();
+        if !lesions.is_empty() {
+            let (_, lesions_view) = Self::lesion_texture(&mut factory, &lesions)?;
+            self.pso_data.s_lesions.0 = lesions_view;
+        }
+        self.pso_data.u_diabetic_retinopathy = lesions.len() as i32;
+
+        self.pso_data.u_retinitis_pigmentosa = settings.retinitis_pigmentosa.is_some() as i32;
+        let (inner, outer, rod_loss) = settings.retinitis_pigmentosa.unwrap_or((0.0, 0.0, 0.0));
+        self.pso_data.u_retinitis_pigmentosa_inner = inner;
+        self.pso_data.u_retinitis_pigmentosa_outer = outer;
+        self.pso_data.u_retinitis_pigmentosa_rods = rod_loss;
+
+        self.pso_data.u_hemianopia = settings.hemianopia.is_some() as i32;
+        let (side, quadrant, sparing, sharpness) = settings.hemianopia.unwrap_or((0, 0, 0.0, 1.0));
+        self.pso_data.u_hemianopia_side = if side == 0 { -1.0 } else { 1.0 };
+        self.pso_data.u_hemianopia_quadrant = match quadrant {
+            0 => 0.0,
+            1 => 1.0,
+            _ => -1.0,
+        };
+        self.pso_data.u_hemianopia_sparing = sparing;
+        self.pso_data.u_hemianopia_border = hemianopia::border_width(sharpness);
+
+        self.pso_data.u_resolution = resolution as f32;
+        let (glaucoma, glaucoma_severity) = Self::severity(settings.glaucoma);
+        self.pso_data.u_glaucoma = glaucoma;
+        self.pso_data.u_glaucoma_severity = glaucoma_severity;
+        let (achromatopsia, achromatopsia_severity) = Self::severity(settings.achromatopsia);
+        self.pso_data.u_achromatopsia = achromatopsia;
+        self.pso_data.u_achromatopsia_severity = achromatopsia_severity;
+        let (nyctalopia, nyctalopia_severity) = Self::severity(settings.nyctalopia);
+        self.pso_data.u_nyctalopia = nyctalopia;
+        self.pso_data.u_nyctalopia_severity = nyctalopia_severity;
+        // Other types have no effect, as in the CPU version.
+        let colorblindness = settings.colorblindness.filter(|(ctype, _)| *ctype <= 2);
+        self.pso_data.u_colorblindness = colorblindness.is_some() as i32;
+        self.pso_data.u_colorblindness_type = colorblindness.map_or(0, |(ctype, _)| ctype as i32);
+        self.pso_data.u_colorblindness_severity = colorblindness.map_or(0.0, |(_, severity)| severity as f32);
+        self.pso_data.u_macular_degeneration = settings.macular_degeneration.is_some() as i32;
+        let (radius, intensity) = settings.macular_degeneration.unwrap_or((0.0, 0.0));
+        self.pso_data.u_macular_degeneration_radius = radius as f32;
+        self.pso_data.u_macular_degeneration_intensity = intensity as f32;
+        self.pso_data.u_receptor_density = settings.receptor_density as i32;
+
+        let (blend, weight) = settings.blend_mode("glaucoma").uniforms();
+        self.pso_data.u_glaucoma_blend = blend;
+        self.pso_data.u_glaucoma_weight = weight;
+        let (blend, weight) = settings.blend_mode("achromatopsia").uniforms();
+        self.pso_data.u_achromatopsia_blend = blend;
+        self.pso_data.u_achromatopsia_weight = weight;
+        let (blend, weight) = settings.blend_mode("nyctalopia").uniforms();
+        self.pso_data.u_nyctalopia_blend = blend;
+        self.pso_data.u_nyctalopia_weight = weight;
+        let (blend, weight) = settings.blend_mode("colorblindness").uniforms();
+        self.pso_data.u_colorblindness_blend = blend;
+        self.pso_data.u_colorblindness_weight = weight;
+        let (blend, weight) = settings.blend_mode("maculardegeneration").uniforms();
+        self.pso_data.u_macular_degeneration_blend = blend;
+        self.pso_data.u_macular_degeneration_weight = weight;
+        let (blend, weight) = settings.blend_mode("receptordensity").uniforms();
+        self.pso_data.u_receptor_density_blend = blend;
+        self.pso_data.u_receptor_density_weight = weight;
+        let (blend, weight) = settings.blend_mode("perimetry").uniforms();
+        self.pso_data.u_perimetry_blend = blend;
+        self.pso_data.u_perimetry_weight = weight;
+        let (blend, weight) = settings.blend_mode("diabeticretinopathy").uniforms();
+        self.pso_data.u_diabetic_retinopathy_blend = blend;
+        self.pso_data.u_diabetic_retinopathy_weight = weight;
+        let (blend, weight) = settings.blend_mode("retinitispigmentosa").uniforms();
+        self.pso_data.u_retinitis_pigmentosa_blend = blend;
+        self.pso_data.u_retinitis_pigmentosa_weight = weight;
+        let (blend, weight) = settings.blend_mode("hemianopia").uniforms();
+        self.pso_data.u_hemianopia_blend = blend;
+        self.pso_data.u_hemianopia_weight = weight;
+
+        let mut encoder = window.encoder().borrow_mut();
+        let faces = [
+            texture::CubeFace::PosX,
+            texture::CubeFace::NegX,
+            texture::CubeFace::PosY,
+            texture::CubeFace::NegY,
+            texture::CubeFace::PosZ,
+            texture::CubeFace::NegZ,
+        ];
+        for (cube_face, orientation) in faces.iter().zip(cubemap_orientations().iter()) {
+            self.pso_data.u_right = orientation[0].into();
+            self.pso_data.u_up = orientation[1].into();
+            self.pso_data.u_forward = orientation[2].into();
+            encoder.draw(&gfx::Slice::from_vertex_count(6), &self.pso, &self.pso_data);
+
+            let image_info = face.get_info().to_raw_image_info(gfx::format::ChannelType::Unorm, 0);
+            encoder
+                .copy_texture_to_texture_raw(
+                    face.raw(),
+                    None,
+                    image_info,
+                    cubemap.raw(),
+                    Some(*cube_face),
+                    image_info,
+                )
+                .map_err(|e| NodeError::Resource(format!("{:?}", e)))?;
+        }
+        Ok(view)
+    }
+
+    /// Stores lesions in a float texture, see `s_lesions` in generator.frag.
+    fn lesion_texture(
+        factory: &mut gfx_device_gl::Factory,
+        lesions: &[Lesion],
+    ) -> Result<
+        (
+            gfx::handle::Texture<Resources, gfx::format::R32_G32_B32_A32>,
+            gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+        ),
+        NodeError,
+    > {
+        // A placeholder if there are no lesions.
+        let mut values = vec![[0.0; 4]; 2 * lesions.len().max(1)];
+        for (i, lesion) in lesions.iter().enumerate() {
+            let direction = lesion.direction;
+            values[i] = [direction.x, direction.y, direction.z, lesion.radius];
+            values[lesions.len() + i] = [lesion.cells[0], lesion.cells[1], lesion.softness, 0.0];
+        }
+        load_float_texture(factory, &values, lesions.len().max(1) as u32, 2).map_err(NodeError::Resource)
+    }
+
+    fn severity(severity: Option<u8>) -> (i32, f32) {
+        (severity.is_some() as i32, severity.unwrap_or(0) as f32)
+    }
+}
@@ -0,0 +1,60 @@
+use super::perimetry::field_position;
+use super::smoothstep;
+use cgmath::{InnerSpace, Vector3};
+
+/// Width of the border between the affected and the unaffected field (in degrees), from the
+/// sharpness (0 to 1).
+pub fn border_width(sharpness: f32) -> f32 {
+    0.1 + 9.9 * (1.0 - sharpness.max(0.0).min(1.0))
+}
+
+/// Lost fraction of cells at a position on the visual field (in degrees), see [generate].
+fn loss(x: f32, y: f32, side: u8, quadrant: u8, sparing: f32, border: f32) -> f32 {
+    let horizontal = if side == 0 { -x } else { x };
+    let mut loss = smoothstep(-border / 2.0, border / 2.0, horizontal);
+    if quadrant != 0 {
+        let vertical = if quadrant == 1 { y } else { -y };
+        loss *= smoothstep(-border / 2.0, border / 2.0, vertical);
+    }
+    if sparing > 0.0 {
+        loss *= smoothstep(sparing, sparing + border, x.hypot(y));
+    }
+    loss
+}
+
+///
+/// Creates a retina map that can be used to simulate homonymous hemianopia and quadrantanopia.
+///
+/// The loss is described on the visual field, such that the same parameters result in the same
+/// loss in both eyes.
+///
+/// # Arguments
+///
+/// - `res`         - resolution of the returned retina map
+/// - `orientation` - right/up/forward vectors
+/// - `side`        - affected half of the visual field (0: left, 1: right)
+/// - `quadrant`    - affected quadrant of that half (0: both, 1: superior, 2: inferior)
+/// - `sparing`     - eccentricity up to which the macula is spared (in degrees)
+/// - `sharpness`   - sharpness of the border, value between 0 and 1
+///
+pub fn generate(
+    res: (u32, u32),
+    orientation: &[Vector3<f32>; 3],
+    side: u8,
+    quadrant: u8,
+    sparing: f32,
+    sharpness: f32,
+) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+    let mut mapbuffer = image::ImageBuffer::new(res.0, res.1);
+    let border = border_width(sharpness);
+
+    for (x, y, pixel) in mapbuffer.enumerate_pixels_mut() {
+        let right = (((x as f32 + 0.5) / res.0 as f32) * 2.0 - 1.0) * orientation[0];
+        let up = (((y as f32 + 0.5) / res.1 as f32) * 2.0 - 1.0) * orientation[1];
+        let (field_x, field_y) = field_position((right + up + orientation[2]).normalize());
+        let cells = ((1.0 - loss(field_x, field_y, side, quadrant, sparing, border)) * 255.0) as u8;
+        *pixel = image::Rgba([cells, cells, cells, cells]);
+    }
+
+    mapbuffer
+}
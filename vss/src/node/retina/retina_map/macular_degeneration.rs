@@ -3,24 +3,19 @@ use cgmath::{InnerSpace, Vector3};
 const MACULAR_SIZE:f64 = 0.3199770295315; //angular size of macular in radians
 
 ///
-/// Creates a retina map that can be used to simulate macular degeneration.
+/// Converts the severity of the simple model to the radius and intensity of [generate].
 ///
 /// # Arguments
 ///
-/// - `res`      - resolution of the returned retina map
 /// - `severity` - the severity of the disease, value between 0 and 100
 ///
-pub fn generate_simple(
-    res: (u32, u32),
-    orientation: &[Vector3<f32>; 3],
-    severity: u8,
-) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+pub fn simple_parameters(severity: u8) -> (f64, f64) {
     // convert severity from int between 0 and 100 to float between 0.5 and 1
     let severity = severity as f64 / 100.0;
     let intensity = 1.0 - 0.5 * (1.0 - severity).powi(2);
     let radius = severity.powi(2) * 100.0;
 
-    generate(res, orientation, radius, intensity)
+    (radius, intensity)
 }
 
 ///
@@ -0,0 +1,29 @@
+use std::f32::consts::PI;
+
+/// Size of the generated distortion map.
+pub const DISTORTION_MAP_SIZE: u32 = 64;
+
+///
+/// Creates a distortion map that can be used to simulate metamorphopsia, e.g., with macular
+/// degeneration.
+///
+/// The map covers the visual field from `-radius` to `radius` in both directions (first row is
+/// superior), see `metamorphopsia_radius`. Red and green encode the horizontal and vertical
+/// displacement of samples, relative to `metamorphopsia_amplitude` (128: none, 0 and 255: full
+/// amplitude), which makes straight lines look wavy. Maps of patients, e.g., derived from Amsler
+/// grids, use the same encoding.
+///
+pub fn generate(size: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(size, size, |x, y| {
+        // position relative to the radius, positive is right and superior
+        let u = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+        let v = 1.0 - (y as f32 + 0.5) / size as f32 * 2.0;
+        let r = u.hypot(v);
+        // fades out towards the radius
+        let window = (1.0 - r * r).max(0.0).powi(2);
+        let dx = window * (3.0 * PI * v + 0.7).sin() * (1.6 * PI * u).cos();
+        let dy = window * (2.6 * PI * u + 1.9).sin() * (1.4 * PI * v).cos();
+        let encode = |d: f32| (128.0 + 127.0 * d.max(-1.0).min(1.0)).round() as u8;
+        image::Rgba([encode(dx), encode(dy), 128, 255])
+    })
+}
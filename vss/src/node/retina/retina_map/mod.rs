@@ -1,107 +1,417 @@
+mod blend;
 mod colorblindness;
+mod diabetic_retinopathy;
+mod generator;
 mod glaucoma;
+mod hemianopia;
 mod macular_degeneration;
+mod metamorphopsia;
 mod nyctalopia;
 mod osterberg;
+mod perimetry;
 mod receptor_density;
+mod retinitis_pigmentosa;
 
-use cgmath::Vector3;
+pub use self::blend::BlendMode;
+pub use self::diabetic_retinopathy::Lesion;
+use self::blend::*;
+pub use self::generator::*;
+pub use self::perimetry::{PerimetryMap, PerimetryPoint};
+
+use cgmath::{InnerSpace, Vector3};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
 
 use crate::*;
 
-pub fn generate_retina_map(resolution: (u32, u32), orientation: &[Vector3<f32>; 3], values: &ValueMap) -> Box<[u8]> {
-    let mut maps: Vec<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>> = Vec::new();
+/// Diseases that affect the generated retina map, as read from values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetinaMapSettings {
+    /// Severity of glaucoma (0 to 100).
+    pub glaucoma: Option<u8>,
+    /// Severity of achromatopsia (0 to 100).
+    pub achromatopsia: Option<u8>,
+    /// Severity of nyctalopia (0 to 100).
+    pub nyctalopia: Option<u8>,
+    /// Type of the missing cones and severity (0 to 100).
+    pub colorblindness: Option<(u8, u8)>,
+    /// Radius and intensity of macular degeneration.
+    pub macular_degeneration: Option<(f64, f64)>,
+    pub receptor_density: bool,
+    /// Measured visual field loss.
+    pub perimetry: Option<PerimetryMap>,
+    /// Stage of diabetic retinopathy (0 to 3), macular edema, and the seed of the lesions.
+    pub diabetic_retinopathy: Option<(u8, bool, u64)>,
+    /// Inner and outer eccentricity of the ring scotoma (in degrees) and rod loss (0 to 1).
+    pub retinitis_pigmentosa: Option<(f32, f32, f32)>,
+    /// Affected side and quadrant, macular sparing (in degrees), and border sharpness (0 to 1).
+    pub hemianopia: Option<(u8, u8, f32, f32)>,
+    /// How the map of each disease is blended, see [BLEND_VALUES].
+    pub blend_modes: BTreeMap<&'static str, BlendMode>,
+}
 
-    // glaucoma
-    if let Some(Value::Bool(true)) = values.get("glaucoma_onoff") {
-        let severity = values.get("glaucoma_fov").unwrap().as_f64().unwrap() as u8;
-        //let glaucoma_scotomasize = values.get("glaucoma_scotomasize"].as_u64().unwrap();
-        let glaucoma = glaucoma::generate_simple(resolution, orientation, severity);
-        maps.push(glaucoma);
-    }
+impl RetinaMapSettings {
+    /// Reads settings from values, fails if perimetry results cannot be loaded.
+    pub fn from_values(values: &ValueMap) -> Result<Self, String> {
+        let enabled = |name: &str| values.get(name) == Some(&Value::Bool(true));
+        let mut settings = RetinaMapSettings::default();
 
-    // achromatopsia
-    if let Some(Value::Bool(true)) = values.get("achromatopsia_onoff") {
-        let severity = values.get("achromatopsia_int").unwrap().as_f64().unwrap() as u8;
-        let achromatopsia = colorblindness::generate_achromatopsia(resolution, severity);
-        maps.push(achromatopsia);
-    }
+        // glaucoma
+        if enabled("glaucoma_onoff") {
+            //let glaucoma_scotomasize = values.get("glaucoma_scotomasize"].as_u64().unwrap();
+            settings.glaucoma = Some(number(values, "glaucoma_fov") as u8);
+        }
+
+        // achromatopsia
+        if enabled("achromatopsia_onoff") {
+            settings.achromatopsia = Some(number(values, "achromatopsia_int") as u8);
+        }
+
+        // nyctalopia
+        if enabled("nyctalopia_onoff") {
+            settings.nyctalopia = Some(number(values, "nyctalopia_int") as u8);
+        }
+
+        // colorblindness
+        if enabled("colorblindness_onoff") {
+            let ctype = number(values, "colorblindness_type") as u8;
+            let severity = number(values, "colorblindness_int") as u8;
+            settings.colorblindness = Some((ctype, severity));
+        }
+
+        // macular degeneration
+        if enabled("maculardegeneration_onoff") {
+            if enabled("maculardegeneration_veasy") {
+                // parameters set in easy easy mode
+                let severity = number(values, "maculardegeneration_inteasy") as u8;
+                settings.macular_degeneration = Some(macular_degeneration::simple_parameters(severity));
+            } else if enabled("maculardegeneration_vadvanced") {
+                // parameters set in advanced mode
+                let radius = number(values, "maculardegeneration_radius");
+                let severity = number(values, "maculardegeneration_intadvanced");
+                // interpret parameters
+                let severity = 1.0 - 0.5 * (1.0 - severity / 100.0).powi(2);
+                settings.macular_degeneration = Some((radius / 100.0, severity));
+            }
+        }
+
+        // receptor density
+        settings.receptor_density = enabled("receptordensity_onoff");
+
+        // perimetry
+        if let Some(Value::String(path)) = values.get("perimetry_path") {
+            let points = perimetry::load(path).map_err(|e| format!("perimetry_path: {}", e))?;
+            let normal_sensitivity = number(values, "perimetry_normal") as f32;
+            settings.perimetry = Some(perimetry::interpolate(&points, normal_sensitivity.max(1.0)));
+        }
 
-    // nyctalopia
-    if let Some(Value::Bool(true)) = values.get("nyctalopia_onoff") {
-        let severity = values.get("nyctalopia_int").unwrap().as_f64().unwrap() as u8;
-        let nyctalopia = nyctalopia::generate(resolution, severity);
-        maps.push(nyctalopia);
+        // diabetic retinopathy
+        if enabled("diabeticretinopathy_onoff") {
+            let stage = number(values, "diabeticretinopathy_stage") as u8;
+            let seed = number(values, "diabeticretinopathy_seed") as u64;
+            settings.diabetic_retinopathy = Some((stage, enabled("diabeticretinopathy_edema"), seed));
+        }
+
+        // retinitis pigmentosa
+        if enabled("retinitispigmentosa_onoff") {
+            let inner = number(values, "retinitispigmentosa_inner") as f32;
+            let outer = number(values, "retinitispigmentosa_outer") as f32;
+            let rod_loss = number(values, "retinitispigmentosa_rods") as f32 / 100.0;
+            settings.retinitis_pigmentosa = Some((inner, outer, rod_loss));
+        }
+
+        // homonymous hemianopia and quadrantanopia
+        if enabled("hemianopia_onoff") {
+            let side = number(values, "hemianopia_side") as u8;
+            let quadrant = number(values, "hemianopia_quadrant") as u8;
+            let sparing = number(values, "hemianopia_sparing") as f32;
+            let sharpness = number(values, "hemianopia_sharpness") as f32 / 100.0;
+            settings.hemianopia = Some((side, quadrant, sparing, sharpness));
+        }
+
+        // blend modes of the enabled diseases (in the order of BLEND_VALUES), others do not affect the map
+        let enabled_diseases = [
+            settings.glaucoma.is_some(),
+            settings.achromatopsia.is_some(),
+            settings.nyctalopia.is_some(),
+            settings.colorblindness.is_some(),
+            settings.macular_degeneration.is_some(),
+            settings.receptor_density,
+            settings.perimetry.is_some(),
+            settings.diabetic_retinopathy.is_some(),
+            settings.retinitis_pigmentosa.is_some(),
+            settings.hemianopia.is_some(),
+        ];
+        for ((disease, mode, weight), enabled) in BLEND_VALUES.iter().zip(enabled_diseases.iter()) {
+            if *enabled {
+                settings.blend_modes.insert(disease, BlendMode::from_values(values, mode, weight));
+            }
+        }
+
+        Ok(settings)
     }
 
-    // colorblindness
-    if let Some(Value::Bool(true)) = values.get("colorblindness_onoff") {
-        let ctype = values.get("colorblindness_type").unwrap().as_f64().unwrap() as u8;
-        let severity = values.get("colorblindness_int").unwrap().as_f64().unwrap() as u8;
-        let colorblindness = colorblindness::generate_colorblindness(resolution, ctype, severity);
-        maps.push(colorblindness);
+    /// Lesions of diabetic retinopathy, if enabled.
+    pub fn diabetic_retinopathy_lesions(&self) -> Option<Vec<Lesion>> {
+        self.diabetic_retinopathy
+            .map(|(stage, edema, seed)| diabetic_retinopathy::lesions(stage, edema, seed))
     }
 
-    // macular degeneration
-    if let Some(Value::Bool(true)) = values.get("maculardegeneration_onoff") {
-        if let Some(Value::Bool(true)) = values.get("maculardegeneration_veasy") {
-            // parameters set in easy easy mode
-            let severity = values
-                .get("maculardegeneration_inteasy")
-                .unwrap()
-                .as_f64()
-                .unwrap() as u8;
-            let macular_degeneration = macular_degeneration::generate_simple(resolution, orientation, severity);
-            maps.push(macular_degeneration);
-        } else if let Some(Value::Bool(true)) = values.get("maculardegeneration_vadvanced") {
-            // parameters set in advanced mode
-            let radius = values
-                .get("maculardegeneration_radius")
-                .unwrap()
-                .as_f64()
-                .unwrap();
-            let severity = values
-                .get("maculardegeneration_intadvanced")
-                .unwrap()
-                .as_f64()
-                .unwrap();
-            // interpret parameters
-            let severity = 1.0 - 0.5 * (1.0 - severity / 100.0).powi(2);
-            let macular_degeneration = macular_degeneration::generate(resolution, orientation, radius/100.0, severity);
-            maps.push(macular_degeneration);
-        }
+    /// How the map of a disease (as named in [BLEND_VALUES]) is blended.
+    pub fn blend_mode(&self, disease: &str) -> BlendMode {
+        self.blend_modes.get(disease).copied().unwrap_or_default()
     }
+}
+
+/// Right/up/forward vectors of the cubemap faces, in the order +x, -x, +y, -y, +z, -z.
+pub fn cubemap_orientations() -> [[Vector3<f32>; 3]; 6] {
+    //orientations directly taken from https://www.khronos.org/opengl/wiki/Cubemap_Texture
+    [
+        [-Vector3::unit_z(), -Vector3::unit_y(),  Vector3::unit_x()],
+        [ Vector3::unit_z(),  Vector3::unit_y(), -Vector3::unit_x()],
+        [ Vector3::unit_x(),  Vector3::unit_z(),  Vector3::unit_y()],
+        [ Vector3::unit_x(), -Vector3::unit_z(), -Vector3::unit_y()],
+        [ Vector3::unit_x(), -Vector3::unit_y(),  Vector3::unit_z()],
+        [-Vector3::unit_x(), -Vector3::unit_y(), -Vector3::unit_z()],
+    ]
+}
+
+/// Generates all six faces of a retina map on the CPU, in parallel (see [cubemap_orientations]).
+pub fn generate_retina_cubemap(resolution: u32, settings: &RetinaMapSettings) -> Vec<Box<[u8]>> {
+    cubemap_orientations()
+        .par_iter()
+        .map(|orientation| generate_retina_map((resolution, resolution), orientation, settings))
+        .collect()
+}
+
+/// Suffixes of the faces in [cubemap_orientations], e.g., for `glaucoma.retina_pos_x.png`.
+pub const CUBEMAP_FACE_NAMES: [&str; 6] = ["pos_x", "neg_x", "pos_y", "neg_y", "pos_z", "neg_z"];
 
-    // receptor density
-    if let Some(Value::Bool(true)) = values.get("receptordensity_onoff") {
-        let receptor_density = receptor_density::generate(resolution);
-        maps.push(receptor_density);
+/// Generates all six faces of a retina map as images that can be loaded with `retina_map_*_path`,
+/// e.g., to hand-edit them.
+pub fn generate_retina_images(resolution: u32, settings: &RetinaMapSettings) -> Vec<image::RgbaImage> {
+    generate_retina_cubemap(resolution, settings)
+        .into_iter()
+        .map(|face| {
+            let face = image::RgbaImage::from_raw(resolution, resolution, face.into_vec()).unwrap();
+            // Images are flipped when loaded, as their first row is at the top.
+            image::imageops::flip_vertical(&face)
+        })
+        .collect()
+}
+
+/// Projects the faces of a retina map (see [generate_retina_images]) onto a single equirectangular
+/// image of the given width, with the fovea at the center.
+pub fn equirectangular_retina_image(faces: &[image::RgbaImage], width: u32) -> image::RgbaImage {
+    let height = (width / 2).max(1);
+    image::RgbaImage::from_fn(width, height, |x, y| {
+        let longitude = ((x as f32 + 0.5) / width as f32 * 2.0 - 1.0) * std::f32::consts::PI;
+        let latitude = (0.5 - (y as f32 + 0.5) / height as f32) * std::f32::consts::PI;
+        let direction = Vector3::new(
+            longitude.sin() * latitude.cos(),
+            latitude.sin(),
+            -longitude.cos() * latitude.cos(),
+        );
+
+        // Select the face by the major axis, see the OpenGL specification on cube map textures.
+        let (face, major) = cubemap_orientations()
+            .iter()
+            .enumerate()
+            .map(|(face, orientation)| (face, direction.dot(orientation[2])))
+            .fold((0, f32::MIN), |best, current| if current.1 > best.1 { current } else { best });
+        let orientation = cubemap_orientations()[face];
+        let s = (direction.dot(orientation[0]) / major + 1.0) / 2.0;
+        let t = (direction.dot(orientation[1]) / major + 1.0) / 2.0;
+
+        let image = &faces[face];
+        let (face_width, face_height) = image.dimensions();
+        let column = ((s * face_width as f32) as u32).min(face_width - 1);
+        let row = ((t * face_height as f32) as u32).min(face_height - 1);
+        // The first row is at the top, see generate_retina_images.
+        *image.get_pixel(column, face_height - 1 - row)
+    })
+}
+
+/// Samples the given function for the pixel directions of all six faces, in parallel.
+fn resample_cubemap<F>(resolution: u32, sample: F) -> Vec<Box<[u8]>>
+where
+    F: Fn(Vector3<f32>) -> image::Rgba<u8> + Sync,
+{
+    cubemap_orientations()
+        .par_iter()
+        .map(|orientation| {
+            let face = image::RgbaImage::from_fn(resolution, resolution, |x, y| {
+                let right = (((x as f32 + 0.5) / resolution as f32) * 2.0 - 1.0) * orientation[0];
+                let up = (((y as f32 + 0.5) / resolution as f32) * 2.0 - 1.0) * orientation[1];
+                sample((right + up + orientation[2]).normalize())
+            });
+            face.into_raw().into_boxed_slice()
+        })
+        .collect()
+}
+
+/// Resamples an equirectangular image (360° wide, 180° high, fovea at the center) into the faces
+/// of a retina map, see [equirectangular_retina_image] for the inverse.
+pub fn resample_equirectangular(image: &image::RgbaImage) -> (u32, Vec<Box<[u8]>>) {
+    let (width, height) = image.dimensions();
+    // A face covers a quarter of the width.
+    let resolution = (width / 4).max(1);
+    let faces = resample_cubemap(resolution, |direction| {
+        let longitude = direction.x.atan2(-direction.z);
+        let latitude = direction.y.max(-1.0).min(1.0).asin();
+        let x = (longitude / std::f32::consts::PI + 1.0) / 2.0 * width as f32;
+        let y = (0.5 - latitude / std::f32::consts::PI) * height as f32;
+        *image.get_pixel((x as u32).min(width - 1), (y as u32).min(height - 1))
+    });
+    (resolution, faces)
+}
+
+/// Resamples a polar visual field image into the faces of a retina map.
+///
+/// The fovea is at the center of the image, up is superior, and the eccentricity grows linearly
+/// up to `fov` degrees at the closest edge. Directions beyond that repeat the outermost pixels.
+pub fn resample_polar(image: &image::RgbaImage, fov: f32) -> (u32, Vec<Box<[u8]>>) {
+    let (width, height) = image.dimensions();
+    let radius = width.min(height) as f32 / 2.0;
+    // A face covers 45 degrees from its center.
+    let resolution = ((radius * 45.0 / fov) as u32 * 2).max(1).min(4096);
+    let global_forward = -Vector3::unit_z();
+    let faces = resample_cubemap(resolution, |direction| {
+        let eccentricity = global_forward.angle(direction).0.to_degrees().min(fov);
+        let azimuth = direction.y.atan2(direction.x);
+        let distance = eccentricity / fov * radius;
+        let x = width as f32 / 2.0 + distance * azimuth.cos();
+        let y = height as f32 / 2.0 - distance * azimuth.sin();
+        *image.get_pixel((x.max(0.0) as u32).min(width - 1), (y.max(0.0) as u32).min(height - 1))
+    });
+    (resolution, faces)
+}
+
+pub fn generate_retina_map(resolution: (u32, u32), orientation: &[Vector3<f32>; 3], settings: &RetinaMapSettings) -> Box<[u8]> {
+    let mut maps = Vec::new();
+
+    if let Some(severity) = settings.glaucoma {
+        maps.push((glaucoma::generate_simple(resolution, orientation, severity), settings.blend_mode("glaucoma")));
+    }
+    if let Some(severity) = settings.achromatopsia {
+        maps.push((colorblindness::generate_achromatopsia(resolution, severity), settings.blend_mode("achromatopsia")));
+    }
+    if let Some(severity) = settings.nyctalopia {
+        maps.push((nyctalopia::generate(resolution, severity), settings.blend_mode("nyctalopia")));
+    }
+    if let Some((ctype, severity)) = settings.colorblindness {
+        maps.push((
+            colorblindness::generate_colorblindness(resolution, ctype, severity),
+            settings.blend_mode("colorblindness"),
+        ));
+    }
+    if let Some((radius, intensity)) = settings.macular_degeneration {
+        maps.push((
+            macular_degeneration::generate(resolution, orientation, radius, intensity),
+            settings.blend_mode("maculardegeneration"),
+        ));
+    }
+    if settings.receptor_density {
+        maps.push((receptor_density::generate(resolution), settings.blend_mode("receptordensity")));
+    }
+    if let Some(map) = &settings.perimetry {
+        maps.push((perimetry::generate(resolution, orientation, map), settings.blend_mode("perimetry")));
+    }
+    if let Some(lesions) = settings.diabetic_retinopathy_lesions() {
+        maps.push((
+            diabetic_retinopathy::generate(resolution, orientation, &lesions),
+            settings.blend_mode("diabeticretinopathy"),
+        ));
+    }
+    if let Some((inner, outer, rod_loss)) = settings.retinitis_pigmentosa {
+        maps.push((
+            retinitis_pigmentosa::generate(resolution, orientation, inner, outer, rod_loss),
+            settings.blend_mode("retinitispigmentosa"),
+        ));
+    }
+    if let Some((side, quadrant, sparing, sharpness)) = settings.hemianopia {
+        maps.push((
+            hemianopia::generate(resolution, orientation, side, quadrant, sparing, sharpness),
+            settings.blend_mode("hemianopia"),
+        ));
     }
 
     merge_maps(maps, resolution)
 }
 
-fn merge_maps(
-    maps: Vec<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>>,
-    resolution: (u32, u32),
-) -> Box<[u8]> {
-    // generate white retina map as starting point
-    let mut merged = image::ImageBuffer::new(resolution.0, resolution.1);
-    for (_, _, pixel) in merged.enumerate_pixels_mut() {
-        *pixel = image::Rgba([255 as u8, 255 as u8, 255 as u8, 255 as u8]);
-    }
+/// As in GLSL, see generator.frag.
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).max(0.0).min(1.0);
+    t * t * (3.0 - 2.0 * t)
+}
 
-    // for each pixel and each channel, take the minimum of all maps at this pixel and channel
-    for map in maps {
-        for (x, y, pixel) in merged.enumerate_pixels_mut() {
-            let new_pixel = map.get_pixel(x, y);
-            let r = new_pixel[0].min(pixel[0]);
-            let g = new_pixel[1].min(pixel[1]);
-            let b = new_pixel[2].min(pixel[2]);
-            let a = new_pixel[3].min(pixel[3]);
-            *pixel = image::Rgba([r as u8, g as u8, b as u8, a as u8]);
-        }
-    }
+/// Generates the distortion map of metamorphopsia that is used unless one is loaded.
+pub fn generate_distortion_map() -> image::RgbaImage {
+    metamorphopsia::generate(metamorphopsia::DISTORTION_MAP_SIZE)
+}
+
+/// Describes the values that control the generated retina map.
+pub fn parameters() -> Vec<Parameter> {
+    let mut parameters = vec![
+        Parameter::toggle("glaucoma_onoff", "Enables glaucoma"),
+        Parameter::number("glaucoma_fov", "%", (0.0, 100.0), 0.0, "Severity of the peripheral field loss"),
+        Parameter::toggle("achromatopsia_onoff", "Enables achromatopsia"),
+        Parameter::number("achromatopsia_int", "%", (0.0, 100.0), 0.0, "Severity of the cone loss"),
+        Parameter::toggle("nyctalopia_onoff", "Enables nyctalopia (night blindness)"),
+        Parameter::number("nyctalopia_int", "%", (0.0, 100.0), 0.0, "Severity of the rod loss"),
+        Parameter::toggle("colorblindness_onoff", "Enables color blindness based on cone loss"),
+        Parameter::integer("colorblindness_type", (0.0, 2.0), 0.0, "Missing cones (0: red, 1: green, 2: blue)"),
+        Parameter::number("colorblindness_int", "%", (0.0, 100.0), 0.0, "Severity of the cone loss"),
+        Parameter::toggle("maculardegeneration_onoff", "Enables macular degeneration"),
+        Parameter::toggle("maculardegeneration_veasy", "Uses the simple macular degeneration model"),
+        Parameter::number("maculardegeneration_inteasy", "%", (0.0, 100.0), 0.0, "Severity (simple model)"),
+        Parameter::toggle("maculardegeneration_vadvanced", "Uses the advanced macular degeneration model"),
+        Parameter::number("maculardegeneration_radius", "%", (0.0, 100.0), 0.0, "Radius of the affected area (advanced model)"),
+        Parameter::number("maculardegeneration_intadvanced", "%", (0.0, 100.0), 0.0, "Severity (advanced model)"),
+        Parameter::toggle("receptordensity_onoff", "Reduces acuity according to the receptor density (Osterberg)"),
+        Parameter::path("perimetry_path", "Perimetry results as CSV or JSON (x and y in degrees, sensitivity in dB), set per eye for both eyes"),
+        Parameter::number("perimetry_normal", "dB", (1.0, 50.0), 30.0, "Sensitivity of a normal visual field, lower measured sensitivities reduce cells"),
+        Parameter::toggle("diabeticretinopathy_onoff", "Enables diabetic retinopathy"),
+        Parameter::integer("diabeticretinopathy_stage", (0.0, 3.0), 0.0, "Stage (0: mild NPDR, 1: moderate NPDR, 2: severe NPDR, 3: PDR)"),
+        Parameter::toggle("diabeticretinopathy_edema", "Adds macular edema, which blurs central vision"),
+        Parameter::integer("diabeticretinopathy_seed", (0.0, 4294967295.0), 0.0, "Seed of the scattered lesions"),
+        Parameter::toggle("retinitispigmentosa_onoff", "Enables retinitis pigmentosa"),
+        Parameter::number("retinitispigmentosa_inner", "°", (0.0, 110.0), 20.0, "Eccentricity where the ring scotoma starts"),
+        Parameter::number("retinitispigmentosa_outer", "°", (0.0, 110.0), 40.0, "Eccentricity where the ring scotoma ends"),
+        Parameter::number("retinitispigmentosa_rods", "%", (0.0, 100.0), 50.0, "Rod loss outside the ring (night blindness)"),
+        Parameter::toggle("hemianopia_onoff", "Enables homonymous hemianopia or quadrantanopia (the same for both eyes)"),
+        Parameter::integer("hemianopia_side", (0.0, 1.0), 0.0, "Affected half of the visual field (0: left, 1: right)"),
+        Parameter::integer("hemianopia_quadrant", (0.0, 2.0), 0.0, "Affected quadrant (0: the whole half, 1: superior, 2: inferior)"),
+        Parameter::number("hemianopia_sparing", "°", (0.0, 30.0), 0.0, "Eccentricity up to which the macula is spared"),
+        Parameter::number("hemianopia_sharpness", "%", (0.0, 100.0), 90.0, "Sharpness of the border along the meridians"),
+    ];
+    parameters.extend(blend_parameters());
+    parameters
+}
+
+/// Values that describe the visual field of both eyes and thus must not differ between the eyes,
+/// see [share_binocular_values].
+pub const BINOCULAR_VALUES: [&str; 7] = [
+    "hemianopia_onoff",
+    "hemianopia_side",
+    "hemianopia_quadrant",
+    "hemianopia_sparing",
+    "hemianopia_sharpness",
+    "hemianopia_blend",
+    "hemianopia_weight",
+];
 
-    merged.into_raw().into_boxed_slice()
+/// Looks up a number, falling back to the parameter's default if it is missing or not a number.
+fn number(values: &ValueMap, name: &str) -> f64 {
+    values
+        .get(name)
+        .and_then(Value::as_f64)
+        .or_else(|| {
+            parameters()
+                .iter()
+                .find(|parameter| parameter.name == name)
+                .and_then(|parameter| parameter.default.as_ref())
+                .and_then(Value::as_f64)
+        })
+        .unwrap_or(0.0)
 }
@@ -0,0 +1,247 @@
+use super::smoothstep;
+use cgmath::{InnerSpace, Vector3};
+use std::path::Path;
+
+/// Degrees per cell of an interpolated [PerimetryMap].
+const DEGREES_PER_CELL: f32 = 1.0;
+
+/// Largest eccentricity of test locations (horizontally and vertically) in degrees.
+const MAX_ECCENTRICITY: f32 = 90.0;
+
+/// A test location of automated perimetry (e.g., Humphrey 24-2 or 30-2).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerimetryPoint {
+    /// Horizontal position in degrees, positive to the right (as seen by the patient).
+    pub x: f32,
+    /// Vertical position in degrees, positive is superior.
+    pub y: f32,
+    /// Measured sensitivity in dB.
+    pub sensitivity: f32,
+}
+
+/// Perimetry results interpolated over the visual field, see [interpolate].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerimetryMap {
+    /// Number of cells per row and column.
+    pub size: u32,
+    /// Eccentricity at the edges of the map in degrees, i.e., the map covers [-extent, extent].
+    pub extent: f32,
+    /// Relative sensitivity per cell (0: blind, 255: normal), the first row is inferior.
+    pub cells: Vec<u8>,
+}
+
+impl PerimetryMap {
+    /// Samples the map (bilinearly) at a position in degrees, positions outside repeat the edges.
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        let to_cell = |v: f32| ((v + self.extent) / (2.0 * self.extent) * self.size as f32 - 0.5).max(0.0).min((self.size - 1) as f32);
+        let (u, v) = (to_cell(x), to_cell(y));
+        let (u0, v0) = (u.floor() as u32, v.floor() as u32);
+        let (u1, v1) = ((u0 + 1).min(self.size - 1), (v0 + 1).min(self.size - 1));
+        let (fu, fv) = (u - u0 as f32, v - v0 as f32);
+        let cell = |u: u32, v: u32| self.cells[(v * self.size + u) as usize] as f32;
+        let bottom = cell(u0, v0) * (1.0 - fu) + cell(u1, v0) * fu;
+        let top = cell(u0, v1) * (1.0 - fu) + cell(u1, v1) * fu;
+        bottom * (1.0 - fv) + top * fv
+    }
+}
+
+/// Loads perimetry results from CSV (`x,y,sensitivity` per line) or JSON (an array of
+/// `{"x": .., "y": .., "sensitivity": ..}` objects or `[x, y, sensitivity]` arrays).
+///
+/// Positions are in degrees and sensitivities in dB, values like `<0` count as 0 dB.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<PerimetryPoint>, String> {
+    let path = path.as_ref();
+    let data = crate::try_load(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let data = String::from_utf8(data.into_inner()).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let points = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => parse_json(&data),
+        _ => parse_csv(&data),
+    }
+    .map_err(|e| format!("{}: {}", path.display(), e))?;
+    if points.is_empty() {
+        return Err(format!("{}: no test locations", path.display()));
+    }
+    Ok(points)
+}
+
+fn parse_sensitivity(value: &str) -> Result<f32, String> {
+    let value = value.trim();
+    if value.starts_with('<') {
+        return Ok(0.0);
+    }
+    value.parse::<f32>().map_err(|_| format!("invalid sensitivity \"{}\"", value))
+}
+
+/// A test location, if its position is within the visual field and its sensitivity is finite.
+fn point(x: f32, y: f32, sensitivity: f32) -> Result<PerimetryPoint, String> {
+    if !x.is_finite() || !y.is_finite() {
+        return Err(format!("invalid position ({}, {})", x, y));
+    }
+    if x.abs() > MAX_ECCENTRICITY || y.abs() > MAX_ECCENTRICITY {
+        return Err(format!("position ({}, {}) is outside of ±{} degrees", x, y, MAX_ECCENTRICITY));
+    }
+    if !sensitivity.is_finite() {
+        return Err(format!("invalid sensitivity {}", sensitivity));
+    }
+    Ok(PerimetryPoint {
+        x,
+        y,
+        sensitivity: sensitivity.max(0.0),
+    })
+}
+
+fn parse_csv(data: &str) -> Result<Vec<PerimetryPoint>, String> {
+    let mut points = Vec::new();
+    for (index, line) in data.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line
+            .split(|c: char| c == ',' || c == ';' || c == '\t')
+            .map(str::trim)
+            .collect();
+        if fields.len() != 3 {
+            return Err(format!("line {}: expected 3 fields, found {}", index + 1, fields.len()));
+        }
+        let x = fields[0].parse::<f32>();
+        let y = fields[1].parse::<f32>();
+        match (x, y) {
+            (Ok(x), Ok(y)) => points.push(
+                parse_sensitivity(fields[2])
+                    .and_then(|sensitivity| point(x, y, sensitivity))
+                    .map_err(|e| format!("line {}: {}", index + 1, e))?,
+            ),
+            // A header.
+            _ if points.is_empty() => continue,
+            _ => return Err(format!("line {}: invalid position", index + 1)),
+        }
+    }
+    Ok(points)
+}
+
+fn parse_json(data: &str) -> Result<Vec<PerimetryPoint>, String> {
+    let json: serde_json::Value = serde_json::from_str(data).map_err(|e| e.to_string())?;
+    let items = json.as_array().ok_or("expected an array of test locations")?;
+    let number = |value: Option<&serde_json::Value>| -> Result<f32, String> {
+        match value {
+            Some(serde_json::Value::Number(n)) => Ok(n.as_f64().unwrap_or(0.0) as f32),
+            Some(serde_json::Value::String(s)) => parse_sensitivity(s),
+            _ => Err("expected x, y, and sensitivity".to_string()),
+        }
+    };
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let (x, y, sensitivity) = match item {
+                serde_json::Value::Array(values) if values.len() == 3 => (values.get(0), values.get(1), values.get(2)),
+                serde_json::Value::Object(object) => (object.get("x"), object.get("y"), object.get("sensitivity")),
+                _ => (None, None, None),
+            };
+            number(x)
+                .and_then(|x| Ok((x, number(y)?, number(sensitivity)?)))
+                .and_then(|(x, y, sensitivity)| point(x, y, sensitivity))
+                .map_err(|e| format!("test location {}: {}", index + 1, e))
+        })
+        .collect()
+}
+
+/// Typical distance between test locations (in degrees), i.e., the median distance to the closest
+/// other test location. Grids like 24-2 and 30-2 use 6 degrees, which is also the fallback.
+fn grid_spacing(points: &[PerimetryPoint]) -> f32 {
+    let mut distances: Vec<f32> = points
+        .iter()
+        .enumerate()
+        .filter_map(|(i, point)| {
+            points
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, other)| (other.x - point.x).hypot(other.y - point.y))
+                .filter(|distance| *distance > 0.0)
+                .fold(None, |closest: Option<f32>, distance| Some(closest.map_or(distance, |c| c.min(distance))))
+        })
+        .collect();
+    if distances.is_empty() {
+        return 6.0;
+    }
+    distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    distances[distances.len() / 2]
+}
+
+/// Interpolates the sensitivities of all test locations over the visual field, relative to the
+/// sensitivity of a normal field (in dB).
+///
+/// Test locations are weighted by the inverse fourth power of their distance, such that cells close
+/// to the tested area follow the closest test locations. Outside of it, the map fades to normal
+/// sensitivity within about one grid spacing, since unmeasured areas cannot be told apart from
+/// healthy ones. The map covers at least 90 degrees in each direction.
+pub fn interpolate(points: &[PerimetryPoint], normal_sensitivity: f32) -> PerimetryMap {
+    let extent = points
+        .iter()
+        .map(|point| point.x.abs().max(point.y.abs()))
+        .fold(90.0f32, f32::max)
+        .ceil();
+    let spacing = grid_spacing(points);
+    let size = (2.0 * extent / DEGREES_PER_CELL) as u32;
+    let mut cells = Vec::with_capacity((size * size) as usize);
+    for row in 0..size {
+        for column in 0..size {
+            let x = (column as f32 + 0.5) * DEGREES_PER_CELL - extent;
+            let y = (row as f32 + 0.5) * DEGREES_PER_CELL - extent;
+            let mut weights = 0.0;
+            let mut sensitivity = 0.0;
+            let mut closest = f32::INFINITY;
+            for point in points {
+                let distance_squared = (point.x - x).powi(2) + (point.y - y).powi(2);
+                let weight = 1.0 / distance_squared.max(1e-3).powi(2);
+                weights += weight;
+                sensitivity += weight * point.sensitivity;
+                closest = closest.min(distance_squared);
+            }
+            let relative = (sensitivity / weights / normal_sensitivity).max(0.0).min(1.0);
+            // Within a grid, no cell is farther than about 0.71 spacings from a test location.
+            let untested = smoothstep(0.75 * spacing, 1.5 * spacing, closest.sqrt());
+            let relative = relative + (1.0 - relative) * untested;
+            cells.push((relative * 255.0) as u8);
+        }
+    }
+    PerimetryMap { size, extent, cells }
+}
+
+/// Position of a direction on the visual field in degrees, as used by [PerimetryMap::sample].
+///
+/// Eccentricity and angle around the fovea are kept, i.e., an azimuthal equidistant projection.
+pub fn field_position(direction: Vector3<f32>) -> (f32, f32) {
+    let global_forward = -Vector3::unit_z(); //Result of OpenGL being Right-handed
+    let eccentricity = global_forward.angle(direction).0.to_degrees();
+    let azimuth = direction.y.atan2(direction.x);
+    (eccentricity * azimuth.cos(), eccentricity * azimuth.sin())
+}
+
+///
+/// Creates a retina map from interpolated perimetry results.
+///
+/// # Arguments
+///
+/// - `res`         - resolution of the returned retina map
+/// - `orientation` - right/up/forward vectors
+/// - `map`         - interpolated perimetry results
+///
+pub fn generate(
+    res: (u32, u32),
+    orientation: &[Vector3<f32>; 3],
+    map: &PerimetryMap,
+) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+    let mut mapbuffer = image::ImageBuffer::new(res.0, res.1);
+
+    for (x, y, pixel) in mapbuffer.enumerate_pixels_mut() {
+        let right = (((x as f32 + 0.5) / res.0 as f32) * 2.0 - 1.0) * orientation[0];
+        let up = (((y as f32 + 0.5) / res.1 as f32) * 2.0 - 1.0) * orientation[1];
+        let (field_x, field_y) = field_position((right + up + orientation[2]).normalize());
+        let cells = map.sample(field_x, field_y) as u8;
+        *pixel = image::Rgba([cells, cells, cells, cells]);
+    }
+
+    mapbuffer
+}
@@ -0,0 +1,54 @@
+use super::smoothstep;
+use cgmath::{InnerSpace, Vector3};
+
+/// Width of the partly affected border of the ring (in degrees).
+const BORDER_WIDTH: f32 = 2.0;
+
+/// Remaining cells at an eccentricity (in degrees), i.e., zero within the ring scotoma.
+fn ring(eccentricity: f32, inner: f32, outer: f32) -> f32 {
+    let scotoma = smoothstep(inner - BORDER_WIDTH, inner, eccentricity)
+        * (1.0 - smoothstep(outer, outer + BORDER_WIDTH, eccentricity));
+    1.0 - scotoma
+}
+
+///
+/// Creates a retina map that can be used to simulate retinitis pigmentosa.
+///
+/// # Arguments
+///
+/// - `res`         - resolution of the returned retina map
+/// - `orientation` - right/up/forward vectors
+/// - `inner`       - eccentricity where the ring scotoma starts (in degrees)
+/// - `outer`       - eccentricity where the ring scotoma ends (in degrees)
+/// - `rod_loss`    - loss of rods outside the ring, value between 0 and 1
+///
+pub fn generate(
+    res: (u32, u32),
+    orientation: &[Vector3<f32>; 3],
+    inner: f32,
+    outer: f32,
+    rod_loss: f32,
+) -> image::ImageBuffer<image::Rgba<u8>, Vec<u8>> {
+    let mut mapbuffer = image::ImageBuffer::new(res.0, res.1);
+
+    let global_forward = -Vector3::unit_z(); //Result of OpenGL being Right-handed
+
+    for (x, y, pixel) in mapbuffer.enumerate_pixels_mut() {
+        let right = (((x as f32 + 0.5) / res.0 as f32) * 2.0 - 1.0) * orientation[0];
+        let up = (((y as f32 + 0.5) / res.1 as f32) * 2.0 - 1.0) * orientation[1];
+        let direction = (right + up + orientation[2]).normalize();
+        let eccentricity = global_forward.angle(direction).0.to_degrees();
+
+        let cones = ring(eccentricity, inner, outer);
+        // rods degenerate first, causing night blindness also outside the ring
+        let rods = cones.min(1.0 - rod_loss);
+        *pixel = image::Rgba([
+            (cones * 255.0) as u8,
+            (cones * 255.0) as u8,
+            (cones * 255.0) as u8,
+            (rods * 255.0) as u8,
+        ]);
+    }
+
+    mapbuffer
+}
@@ -45,7 +45,7 @@ impl DownloadRgbBuffer {
 }
 
 impl Node for DownloadRgbBuffer {
-    fn new(_window: &Window) -> Self {
+    fn new(_window: &Window) -> Result<Self, NodeError> {
         let (tx, rx) = std::sync::mpsc::channel::<Message>();
         std::thread::spawn(move || {
             let mut callback: Option<RgbBufferCb> = None;
@@ -63,22 +63,22 @@ impl Node for DownloadRgbBuffer {
             }
         });
 
-        DownloadRgbBuffer {
+        Ok(DownloadRgbBuffer {
             input: Slot::Empty,
             tx,
             res: [0.0,0.0]
-        }
+        })
     }
 
-    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> NodeSlots {
-        self.res = slots.clone().to_color_input(window).input_size_f32();
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
+        self.res = slots.clone().to_color_input(window)?.input_size_f32()?;
         self.input = slots.clone().take_input();
-        slots.to_passthrough()
+        Ok(slots.to_passthrough())
     }
 
     fn render(&mut self, window: &Window) {
-        match &self.input {
-            Slot::Rgb { color, .. } | Slot::RgbDepth { color, .. } => {
+        match self.input.color_target() {
+            Some(color) => {
                 use gfx::format::Formatted;
                 use gfx::memory::Typed;
 
@@ -133,7 +133,7 @@ impl Node for DownloadRgbBuffer {
 
                 self.tx.send(Message::Buffer(rgb_buffer)).unwrap();
             }
-            _ => {}
+            None => {}
         }
     }
 }
@@ -120,7 +120,7 @@ impl UploadRgbBuffer {
 }
 
 impl Node for UploadRgbBuffer {
-    fn new(window: &Window) -> Self {
+    fn new(window: &Window) -> Result<Self, NodeError> {
         let mut factory = window.factory().borrow_mut();
 
         let pso = factory
@@ -128,20 +128,19 @@ impl Node for UploadRgbBuffer {
                 &include_glsl!("../mod.vert"),
                 &include_glsl!("upload.frag"),
                 pipe::new(),
-            )
-            .unwrap();
+            )?;
 
         let sampler = factory.create_sampler_linear();
-        let (_, rgb_view) = load_texture_from_bytes(&mut factory, &[0; 4], 1, 1).unwrap();
-        let (_, _, rt_color) = factory.create_render_target(1, 1).unwrap();
-        let (_, _, rt_depth) = factory.create_render_target(1, 1).unwrap();
-        let (_, _, rt_deflection) = factory.create_render_target(1, 1).unwrap();
-        let (_, _, rt_color_change) = factory.create_render_target(1, 1).unwrap();
-        let (_, _, rt_color_uncertainty) = factory.create_render_target(1, 1).unwrap();
-        let (_,  _,rt_covariances) = factory.create_render_target(1, 1).unwrap();
+        let (_, rgb_view) = load_texture_from_bytes(&mut factory, &[0; 4], 1, 1).map_err(NodeError::Resource)?;
+        let (_, _, rt_color) = factory.create_render_target(1, 1)?;
+        let (_, _, rt_depth) = factory.create_render_target(1, 1)?;
+        let (_, _, rt_deflection) = factory.create_render_target(1, 1)?;
+        let (_, _, rt_color_change) = factory.create_render_target(1, 1)?;
+        let (_, _, rt_color_uncertainty) = factory.create_render_target(1, 1)?;
+        let (_,  _,rt_covariances) = factory.create_render_target(1, 1)?;
 
 
-        UploadRgbBuffer {
+        Ok(UploadRgbBuffer {
             buffer_next: RgbBuffer::default(),
             buffer_upload: false,
             texture: None,
@@ -161,10 +160,14 @@ impl Node for UploadRgbBuffer {
                 rt_color_uncertainty,
                 rt_covariances
             },
-        }
+        })
     }
 
-    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> NodeSlots {
+    fn output_channels(&self) -> Vec<ChannelInfo> {
+        ERROR_CHANNELS.to_vec()
+    }
+
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
         if self.buffer_upload {
             let mut factory = window.factory().borrow_mut();
             let (texture, view) = load_texture_from_bytes(
@@ -173,7 +176,7 @@ impl Node for UploadRgbBuffer {
                 self.buffer_next.width as u32,
                 self.buffer_next.height as u32,
             )
-            .unwrap();
+            .map_err(NodeError::Resource)?;
             self.texture = Some(texture);
 
             let sampler = factory.create_sampler_linear();
@@ -202,8 +205,8 @@ impl Node for UploadRgbBuffer {
         self.pso_data.u_fov[1] =
             2.0 * ((self.pso_data.u_fov[0] / 2.0).tan() * height as f32 / width as f32).atan();
 
-        let slots = slots.emplace_color_depth_output(window, width, height);
-        let (color, depth, deflection, color_change, color_uncertainty, covariances) = slots.as_all_output();
+        let slots = slots.emplace_color_depth_output(window, width, height)?;
+        let (color, depth, deflection, color_change, color_uncertainty, covariances) = slots.as_all_output()?;
         self.pso_data.rt_color = color;
         self.pso_data.rt_depth = depth;
         self.pso_data.rt_deflection = deflection;
@@ -211,7 +214,7 @@ impl Node for UploadRgbBuffer {
         self.pso_data.rt_color_uncertainty = color_uncertainty;
         self.pso_data.rt_covariances = covariances;
 
-        slots
+        Ok(slots)
     }
 
     fn input(&mut self, perspective: &EyePerspective, _vis_param: &VisualizationParameters) -> EyePerspective {
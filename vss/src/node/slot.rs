@@ -1,70 +1,249 @@
 use super::*;
 use gfx;
 use gfx::format::Rgba32F;
+use gfx::memory::Typed;
 use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
 
 
 pub type ColorFormat = (gfx::format::R8_G8_B8_A8, gfx::format::Unorm);
 pub type HighpFormat = (gfx::format::R32_G32_B32, gfx::format::Float);
 pub type DepthFormat = (gfx::format::R32, gfx::format::Float);
 
+/// Color, present in all non-empty slots.
+pub const CHANNEL_COLOR: &str = "color";
+/// Depth, present in slots of kind [SlotKind::ColorDepth].
+pub const CHANNEL_DEPTH: &str = "depth";
+/// Deflection of light rays (error tracking).
+pub const CHANNEL_DEFLECTION: &str = "deflection";
+/// Change of color (error tracking).
+pub const CHANNEL_COLOR_CHANGE: &str = "color_change";
+/// Uncertainty of color (error tracking).
+pub const CHANNEL_COLOR_UNCERTAINTY: &str = "color_uncertainty";
+/// Covariances of color and deflection (error tracking).
+pub const CHANNEL_COVARIANCES: &str = "covariances";
+
+/// Pixel format of a [Channel].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelFormat {
+    /// 8-bit RGBA, see [ColorFormat].
+    Color,
+    /// 32-bit float, see [DepthFormat].
+    Depth,
+    /// 32-bit float RGBA.
+    Highp,
+}
+
+/// Describes a channel that a node reads or writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelInfo {
+    pub name: &'static str,
+    pub format: ChannelFormat,
+}
+
+impl ChannelInfo {
+    pub const fn new(name: &'static str, format: ChannelFormat) -> Self {
+        ChannelInfo { name, format }
+    }
+}
+
+/// Channels used for tracking errors and uncertainty, see [VisualizationParameters::has_to_track_error].
+pub const ERROR_CHANNELS: [ChannelInfo; 4] = [
+    ChannelInfo::new(CHANNEL_DEFLECTION, ChannelFormat::Highp),
+    ChannelInfo::new(CHANNEL_COLOR_CHANGE, ChannelFormat::Highp),
+    ChannelInfo::new(CHANNEL_COLOR_UNCERTAINTY, ChannelFormat::Highp),
+    ChannelInfo::new(CHANNEL_COVARIANCES, ChannelFormat::Highp),
+];
+
+/// A per-pixel quantity, which nodes render to and other nodes sample from.
 #[derive(Clone)]
-pub enum Slot {
-    Empty,
-    Rgb {
-        color: gfx::handle::RenderTargetView<gfx_device_gl::Resources, ColorFormat>,
-        color_view: Option<gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>>, //TODO: drop last component?
-        deflection: gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F>,
-        deflection_view: gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>,
-        color_change: gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F>,
-        color_change_view: gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>,
-        color_uncertainty: gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F>,
-        color_uncertainty_view: gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>,
-        covariances: gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F>,
-        covariances_view: gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>,
+pub enum Channel {
+    Color {
+        target: gfx::handle::RenderTargetView<Resources, ColorFormat>,
+        /// Missing for targets that cannot be sampled, e.g., the window.
+        view: Option<gfx::handle::ShaderResourceView<Resources, [f32; 4]>>, //TODO: drop last component?
+    },
+    Depth {
+        target: gfx::handle::RenderTargetView<Resources, DepthFormat>,
+        view: gfx::handle::ShaderResourceView<Resources, f32>,
     },
-    RgbDepth {
-        color: gfx::handle::RenderTargetView<gfx_device_gl::Resources, ColorFormat>,
-        color_view: gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>, //TODO: drop last component?
-        depth: gfx::handle::RenderTargetView<gfx_device_gl::Resources, DepthFormat>,
-        depth_view: gfx::handle::ShaderResourceView<gfx_device_gl::Resources, f32>,
-        deflection: gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F>,
-        deflection_view: gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>,
-        color_change: gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F>,
-        color_change_view: gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>,
-        color_uncertainty: gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F>,
-        color_uncertainty_view: gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>,
-        covariances: gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F>,
-        covariances_view: gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>,
+    Highp {
+        target: gfx::handle::RenderTargetView<Resources, Rgba32F>,
+        view: gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
     },
-    // XXX: Stereo
 }
+
+impl Channel {
+    pub fn create(factory: &mut DeviceFactory, format: ChannelFormat, width: u32, height: u32) -> Result<Self, NodeError> {
+        Ok(match format {
+            ChannelFormat::Color => {
+                let (target, view) = create_texture_render_target::<ColorFormat>(factory, width, height)?;
+                Channel::Color { target, view: Some(view) }
+            }
+            ChannelFormat::Depth => {
+                let (target, view) = create_texture_render_target::<DepthFormat>(factory, width, height)?;
+                Channel::Depth { target, view }
+            }
+            ChannelFormat::Highp => {
+                let (target, view) = create_texture_render_target::<Rgba32F>(factory, width, height)?;
+                Channel::Highp { target, view }
+            }
+        })
+    }
+
+    pub fn format(&self) -> ChannelFormat {
+        match self {
+            Channel::Color { .. } => ChannelFormat::Color,
+            Channel::Depth { .. } => ChannelFormat::Depth,
+            Channel::Highp { .. } => ChannelFormat::Highp,
+        }
+    }
+
+    fn raw_target(&self) -> &gfx::handle::RawRenderTargetView<Resources> {
+        match self {
+            Channel::Color { target, .. } => target.raw(),
+            Channel::Depth { target, .. } => target.raw(),
+            Channel::Highp { target, .. } => target.raw(),
+        }
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        let (width, height, ..) = self.raw_target().get_dimensions();
+        [width as u32, height as u32]
+    }
+
+    /// Whether both channels render to the same target.
+    pub fn shares_target(&self, other: &Channel) -> bool {
+        self.raw_target() == other.raw_target()
+    }
+}
+
+/// Channels of a [Slot] by name.
+pub type Channels = BTreeMap<&'static str, Channel>;
+
+#[derive(Clone)]
+pub enum Slot {
+    Empty,
+    /// Channels of the same size, including [CHANNEL_COLOR].
+    Image(Channels),
+    /// One image per eye (right eye first, like flows), see [PerEye].
+    Stereo(Vec<Channels>),
+}
+
+impl Slot {
+    /// Kind of this slot (or of its eyes), `None` if it is empty.
+    pub fn kind(&self) -> Option<SlotKind> {
+        match self {
+            Slot::Empty => None,
+            Slot::Image(channels) if channels.contains_key(CHANNEL_DEPTH) => Some(SlotKind::ColorDepth),
+            Slot::Image(_) => Some(SlotKind::Color),
+            Slot::Stereo(eyes) => eyes.first().and_then(|channels| Slot::Image(channels.clone()).kind()),
+        }
+    }
+
+    /// A channel of an image, `None` for stereo slots (see [Slot::eyes]).
+    pub fn channel(&self, name: &str) -> Option<&Channel> {
+        match self {
+            Slot::Image(channels) => channels.get(name),
+            _ => None,
+        }
+    }
+
+    /// Images of both eyes, i.e., the image itself for both eyes if it is not stereo.
+    pub fn eyes(&self) -> Vec<Slot> {
+        match self {
+            Slot::Stereo(eyes) => eyes.iter().cloned().map(Slot::Image).collect(),
+            slot => vec![slot.clone(); EYES],
+        }
+    }
+
+    /// Combines the images of both eyes, empty unless all of them are images.
+    pub fn stereo(eyes: Vec<Slot>) -> Slot {
+        let mut images = Vec::with_capacity(eyes.len());
+        for eye in eyes {
+            match eye {
+                Slot::Image(channels) => images.push(channels),
+                _ => return Slot::Empty,
+            }
+        }
+        Slot::Stereo(images)
+    }
+
+    /// The color render target, e.g., for reading back pixels.
+    pub fn color_target(&self) -> Option<&gfx::handle::RenderTargetView<Resources, ColorFormat>> {
+        match self.channel(CHANNEL_COLOR) {
+            Some(Channel::Color { target, .. }) => Some(target),
+            _ => None,
+        }
+    }
+
+    /// All channels of this slot.
+    pub fn channels(&self) -> Vec<&Channel> {
+        match self {
+            Slot::Empty => Vec::new(),
+            Slot::Image(channels) => channels.values().collect(),
+            Slot::Stereo(eyes) => eyes.iter().flat_map(|channels| channels.values()).collect(),
+        }
+    }
+
+    fn size(&self) -> Option<[u32; 2]> {
+        match self {
+            Slot::Stereo(eyes) => eyes.first().and_then(|channels| channels.get(CHANNEL_COLOR)).map(Channel::size),
+            slot => slot.channel(CHANNEL_COLOR).map(Channel::size),
+        }
+    }
+
+    /// Whether this slot renders to the target of `channel`.
+    pub fn contains_target(&self, channel: &Channel) -> bool {
+        self.channels().iter().any(|other| other.shares_target(channel))
+    }
+
+    /// Whether both slots render to a common target, e.g., after passthrough nodes.
+    pub fn shares_target(&self, other: &Slot) -> bool {
+        other.channels().iter().any(|channel| self.contains_target(channel))
+    }
+}
+
 impl Default for Slot {
     fn default() -> Self {
         Slot::Empty
     }
 }
 
+/// Input and output of a node, plus auxiliary inputs for nodes that merge branches of a [Flow].
+///
+/// Outputs are completed by the `to_*_output` and `emplace_*_output` methods: they contain color
+/// (and depth), the channels written by the node, and the channels requested by succeeding nodes,
+/// which are forwarded from the input (see [Node::input_channels] and [Node::output_channels]).
+/// Written channels that no one reads render to scratch targets, and channels that are missing in
+/// the input read as zero (see [TexturePool]).
 #[derive(Clone)]
 pub struct NodeSlots {
     input: Slot,
     output: Slot,
+    aux_inputs: Vec<Slot>,
+    written: Vec<ChannelInfo>,
+    requested: Vec<ChannelInfo>,
+    pool: Rc<RefCell<TexturePool>>,
+    blank: Option<gfx::handle::ShaderResourceView<Resources, [f32; 4]>>,
     sampler: gfx::handle::Sampler<gfx_device_gl::Resources>,
 }
 
 impl NodeSlots {
     pub fn new(window: &Window) -> Self {
-        Self {
-            input: Slot::default(),
-            output: Slot::default(),
-            sampler: window.factory().borrow_mut().create_sampler_linear(),
-        }
+        Self::new_io(window, Slot::default(), Slot::default())
     }
 
     pub fn new_io(window: &Window, input: Slot, output: Slot) -> Self {
         Self {
             input,
             output,
+            aux_inputs: Vec::new(),
+            written: Vec::new(),
+            requested: Vec::new(),
+            pool: Rc::new(RefCell::new(TexturePool::new())),
+            blank: None,
             sampler: window.factory().borrow_mut().create_sampler_linear(),
         }
     }
@@ -77,598 +256,506 @@ impl NodeSlots {
         std::mem::take(&mut self.output)
     }
 
-    pub fn to_passthrough(self) -> Self {
+    pub fn output(&self) -> &Slot {
+        &self.output
+    }
+
+    pub fn with_output(self, output: Slot) -> Self {
+        Self { output, ..self }
+    }
+
+    pub fn with_aux_inputs(self, aux_inputs: Vec<Slot>) -> Self {
+        Self { aux_inputs, ..self }
+    }
+
+    /// Sets the channels the node writes and the channels succeeding nodes read.
+    pub fn with_channels(self, written: Vec<ChannelInfo>, requested: Vec<ChannelInfo>) -> Self {
         Self {
-            input: Slot::Empty,
-            output: self.input,
-            sampler: self.sampler,
+            written,
+            requested,
+            ..self
         }
     }
 
-    pub fn to_color_input(self, _window: &Window) -> Self {
-        match self.input {
-            Slot::Empty => {
-                panic!("Input expected");
-            }
-            Slot::Rgb { .. } => self,
-            Slot::RgbDepth {
-                color, color_view, deflection, deflection_view, color_change, color_change_view, color_uncertainty, color_uncertainty_view, covariances, covariances_view, ..
-            } => Self {
-                input: Slot::Rgb {
-                    color,
-                    color_view: Some(color_view),
-                    deflection: deflection,
-                    deflection_view,
-                    color_change, 
-                    color_change_view, 
-                    color_uncertainty, 
-                    color_uncertainty_view,
-                    covariances,
-                    covariances_view
-                },
-                output: self.output,
-                sampler: self.sampler,
-            },
-        }
+    /// Acquires output targets from the given pool (instead of a pool of its own).
+    pub fn with_pool(self, pool: Rc<RefCell<TexturePool>>) -> Self {
+        Self { pool, ..self }
     }
 
-    pub fn to_color_depth_input(self, _window: &Window) -> Self {
-        match self.input {
-            Slot::Empty => {
-                panic!("Input expected");
-            }
-            Slot::Rgb { .. } => {
-                panic!("RGB input cannot be extended with depth");
-            }
-            Slot::RgbDepth { .. } => self,
-        }
+    pub fn take_aux_inputs(&mut self) -> Vec<Slot> {
+        std::mem::take(&mut self.aux_inputs)
+    }
+
+    pub fn aux_inputs_len(&self) -> usize {
+        self.aux_inputs.len()
+    }
+
+    /// Splits these slots into the slots of each eye, such that nodes can be run once per eye.
+    ///
+    /// Inputs that are not stereo are read by both eyes.
+    pub fn split_eyes(self) -> Vec<NodeSlots> {
+        let outputs = match &self.output {
+            Slot::Stereo(_) => self.output.eyes(),
+            // Eyes cannot render to the same target.
+            _ => vec![Slot::Empty; EYES],
+        };
+        let aux_inputs: Vec<Vec<Slot>> = self.aux_inputs.iter().map(Slot::eyes).collect();
+        self.input
+            .eyes()
+            .into_iter()
+            .zip(outputs)
+            .enumerate()
+            .map(|(eye, (input, output))| Self {
+                input,
+                output,
+                aux_inputs: aux_inputs.iter().map(|eyes| eyes[eye].clone()).collect(),
+                ..self.clone()
+            })
+            .collect()
     }
 
-    pub fn to_color_output(self, window: &Window) -> Self {
-        match self.output {
-            Slot::Empty => {
-                // Guess output size, based on input.
-                let (width, height, ..) = match &self.input {
-                    Slot::Empty => {
-                        panic!("Input expected");
-                    }
-                    Slot::Rgb { color, .. } => color.get_dimensions(),
-                    Slot::RgbDepth { color, .. } => color.get_dimensions(),
-                };
-                let mut factory = window.factory().borrow_mut();
-                let (color, color_view) = create_texture_render_target::<ColorFormat>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-                let (deflection, deflection_view) = create_texture_render_target::<Rgba32F>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-                let (color_change, color_change_view) = create_texture_render_target::<Rgba32F>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-                let (color_uncertainty, color_uncertainty_view) = create_texture_render_target::<Rgba32F>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-                let (covariances, covariances_view) = create_texture_render_target::<Rgba32F>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-
-                Self {
-                    input: self.input,
-                    output: Slot::Rgb {
-                        color,
-                        color_view: Some(color_view),
-                        deflection,
-                        deflection_view,
-                        color_change, 
-                        color_change_view, 
-                        color_uncertainty, 
-                        color_uncertainty_view,
-                        covariances,
-                        covariances_view
-                    },
-                    sampler: self.sampler,
+    /// Merges the slots of each eye into stereo slots, see [NodeSlots::split_eyes].
+    pub fn merge_eyes(eyes: Vec<NodeSlots>) -> Result<Self, NodeError> {
+        let mut inputs = Vec::with_capacity(eyes.len());
+        let mut outputs = Vec::with_capacity(eyes.len());
+        let mut aux_inputs: Vec<Vec<Slot>> = Vec::new();
+        let mut merged = None;
+        for mut slots in eyes {
+            inputs.push(slots.take_input());
+            outputs.push(slots.take_output());
+            for (index, aux_input) in slots.take_aux_inputs().into_iter().enumerate() {
+                match aux_inputs.get_mut(index) {
+                    Some(eyes) => eyes.push(aux_input),
+                    None => aux_inputs.push(vec![aux_input]),
                 }
             }
-            Slot::Rgb { .. } => self,
-            Slot::RgbDepth {
-                color, color_view, deflection, deflection_view, color_change, color_change_view, color_uncertainty, color_uncertainty_view, covariances, covariances_view, ..
-            } => {
-                Self {
-                input: self.input,
-                output: Slot::Rgb {
-                    color,
-                    color_view: Some(color_view),                        
-                    deflection,
-                    deflection_view,
-                    color_change, 
-                    color_change_view, 
-                    color_uncertainty, 
-                    color_uncertainty_view,
-                    covariances,
-                    covariances_view
-                },
-                sampler: self.sampler,
-            }},
+            merged.get_or_insert(slots);
         }
+        let merged = merged.ok_or(NodeError::MissingOutput)?;
+        Ok(Self {
+            input: Slot::stereo(inputs),
+            output: Slot::stereo(outputs),
+            aux_inputs: aux_inputs.into_iter().map(Slot::stereo).collect(),
+            ..merged
+        })
     }
 
-    pub fn to_color_depth_output(self, window: &Window) -> Self {
-        match self.output {
-            Slot::Empty => {
-                // Guess output, based on input.
-                let (width, height, ..) = match &self.input {
-                    Slot::Empty => {
-                        panic!("Input expected");
-                    }
-                    Slot::Rgb { color, .. } => color.get_dimensions(),
-                    Slot::RgbDepth { color, .. } => color.get_dimensions(),
-                };
-                let mut factory = window.factory().borrow_mut();
-                let (color, color_view) = create_texture_render_target::<ColorFormat>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-                let (depth, depth_view) = create_texture_render_target::<DepthFormat>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-                let (deflection, deflection_view) = create_texture_render_target::<Rgba32F>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-                let (color_change, color_change_view) = create_texture_render_target::<Rgba32F>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-                let (color_uncertainty, color_uncertainty_view) = create_texture_render_target::<Rgba32F>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-                let (covariances, covariances_view) = create_texture_render_target::<Rgba32F>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-
-                Self {
-                    input: self.input,
-                    output: Slot::RgbDepth {
-                        color,
-                        color_view: color_view,
-                        depth,
-                        depth_view,
-                        deflection,
-                        deflection_view,
-                        color_change, 
-                        color_change_view, 
-                        color_uncertainty, 
-                        color_uncertainty_view,                    
-                        covariances,
-                        covariances_view
-                    },
-                    sampler: self.sampler,
-                }
+    pub fn to_passthrough(self) -> Self {
+        Self {
+            input: Slot::Empty,
+            output: self.input,
+            aux_inputs: Vec::new(),
+            ..self
+        }
+    }
+
+    pub fn to_color_input(mut self, window: &Window) -> Result<Self, NodeError> {
+        match &mut self.input {
+            Slot::Empty => Err(NodeError::MissingInput),
+            Slot::Image(channels) => {
+                channels.remove(CHANNEL_DEPTH);
+                self.with_blank(window)
             }
-            Slot::Rgb {                 
-                color, color_view, deflection, deflection_view, color_change, color_change_view, color_uncertainty, color_uncertainty_view, covariances, covariances_view, ..
-            } => {
-                // Guess missing depth, based on color.
-                let mut factory = window.factory().borrow_mut();
-                let (width, height, ..) = color.get_dimensions();
-                let (depth, depth_view) = create_texture_render_target::<DepthFormat>(
-                    &mut factory,
-                    width as u32,
-                    height as u32,
-                );
-                Self {
-                    input: self.input,
-                    output: Slot::RgbDepth {
-                        color,
-                        color_view: color_view.expect("Shader resource expected"),
-                        depth,
-                        depth_view,
-                        deflection,
-                        deflection_view,
-                        color_change, 
-                        color_change_view, 
-                        color_uncertainty, 
-                        color_uncertainty_view,
-                        covariances,
-                        covariances_view
-                    },
-                    sampler: self.sampler,
+            Slot::Stereo(eyes) => {
+                for channels in eyes.iter_mut() {
+                    channels.remove(CHANNEL_DEPTH);
                 }
+                self.with_blank(window)
             }
-            Slot::RgbDepth { .. } => self,
         }
     }
 
-    pub fn emplace_color_output(self, window: &Window, width: u32, height: u32) -> Self {
-        let mut factory = window.factory().borrow_mut();
-        let (color, color_view) = create_texture_render_target::<ColorFormat>(
-            &mut factory,
-            width,
-            height,
-        );
-        let (deflection, deflection_view) = create_texture_render_target::<Rgba32F>(
-            &mut factory,
-            width as u32,
-            height as u32,
-        );
-        let (color_change, color_change_view) = create_texture_render_target::<Rgba32F>(
-            &mut factory,
-            width as u32,
-            height as u32,
-        );
-        let (color_uncertainty, color_uncertainty_view) = create_texture_render_target::<Rgba32F>(
-            &mut factory,
-            width as u32,
-            height as u32,
-        );
-        let (covariances, covariances_view) = create_texture_render_target::<Rgba32F>(
-            &mut factory,
-            width as u32,
-            height as u32,
-        );
-
-        Self {
-            input: self.input,
-            output: Slot::Rgb {
-                color,
-                color_view: Some(color_view),
-                deflection,
-                deflection_view,
-                color_change, 
-                color_change_view, 
-                color_uncertainty, 
-                color_uncertainty_view,
-                covariances,
-                covariances_view
-            },
-            sampler: self.sampler,
+    pub fn to_color_depth_input(self, window: &Window) -> Result<Self, NodeError> {
+        match self.input.kind() {
+            None => Err(NodeError::MissingInput),
+            // RGB input cannot be extended with depth.
+            Some(SlotKind::ColorDepth) => self.with_blank(window),
+            Some(provided) => Err(NodeError::IncompatibleInput {
+                expected: SlotKind::ColorDepth,
+                provided,
+            }),
         }
     }
 
-    pub fn emplace_color_depth_output(self, window: &Window, width: u32, height: u32) -> Self {
-        let mut factory = window.factory().borrow_mut();
+    fn with_blank(mut self, window: &Window) -> Result<Self, NodeError> {
+        let blank = self
+            .pool
+            .borrow_mut()
+            .blank(&mut window.factory().borrow_mut(), &mut window.encoder().borrow_mut())?;
+        self.blank = Some(blank);
+        Ok(self)
+    }
 
-        let (color, color_view) = create_texture_render_target::<ColorFormat>(
-            &mut factory,
-            width,
-            height,
-        );
-        let (depth, depth_view) = create_texture_render_target::<DepthFormat>(
-            &mut factory,
-            width,
-            height,
-        );
-        let (deflection, deflection_view) = create_texture_render_target::<Rgba32F>(
-            &mut factory,
-            width as u32,
-            height as u32,
-        );
-        let (color_change, color_change_view) = create_texture_render_target::<Rgba32F>(
-            &mut factory,
-            width as u32,
-            height as u32,
-        );
-        let (color_uncertainty, color_uncertainty_view) = create_texture_render_target::<Rgba32F>(
-            &mut factory,
-            width as u32,
-            height as u32,
-        );
-        let (covariances, covariances_view) = create_texture_render_target::<Rgba32F>(
-            &mut factory,
-            width as u32,
-            height as u32,
-        );
+    pub fn to_color_output(self, window: &Window) -> Result<Self, NodeError> {
+        self.complete_output(window, false, None)
+    }
 
-        Self {
-            input: self.input,
-            output: Slot::RgbDepth {
-                color,
-                color_view,
-                depth,
-                depth_view,
-                deflection,
-                deflection_view,
-                color_change, 
-                color_change_view, 
-                color_uncertainty, 
-                color_uncertainty_view,
-                covariances,
-                covariances_view
+    pub fn to_color_depth_output(self, window: &Window) -> Result<Self, NodeError> {
+        self.complete_output(window, true, None)
+    }
+
+    pub fn emplace_color_output(self, window: &Window, width: u32, height: u32) -> Result<Self, NodeError> {
+        self.complete_output(window, false, Some([width, height]))
+    }
+
+    pub fn emplace_color_depth_output(self, window: &Window, width: u32, height: u32) -> Result<Self, NodeError> {
+        self.complete_output(window, true, Some([width, height]))
+    }
+
+    /// Allocates missing output channels, either with the given size or re-using the suggested output.
+    fn complete_output(mut self, window: &Window, depth: bool, size: Option<[u32; 2]>) -> Result<Self, NodeError> {
+        let mut channels = match (size, self.take_output()) {
+            (None, Slot::Image(channels)) => channels,
+            _ => Channels::new(),
+        };
+        // Guess output size, based on input.
+        let [width, height] = match size {
+            Some(size) => size,
+            None => match channels.get(CHANNEL_COLOR) {
+                Some(color) => color.size(),
+                None => self.input.size().ok_or(NodeError::MissingInput)?,
             },
-            sampler: self.sampler,
+        };
+        if depth && size.is_none() {
+            // Guess missing depth, based on color (which must be readable).
+            if let Some(Channel::Color { view: None, .. }) = channels.get(CHANNEL_COLOR) {
+                return Err(NodeError::UnreadableInput);
+            }
         }
-    }
 
-    pub fn as_deflection(&self) -> gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F> {
-        match &self.output {
-            Slot::Empty => {
-                panic!("RGB output expected");
+        let mut required = vec![ChannelInfo::new(CHANNEL_COLOR, ChannelFormat::Color)];
+        if depth {
+            required.push(ChannelInfo::new(CHANNEL_DEPTH, ChannelFormat::Depth));
+        }
+        required.extend(self.written.iter().filter(|info| !required.contains(info)).copied().collect::<Vec<_>>());
+        let forwarded: Vec<ChannelInfo> = self
+            .requested
+            .iter()
+            .filter(|info| !required.iter().any(|other| other.name == info.name))
+            .copied()
+            .collect();
+
+        // Drop channels of suggested outputs that are not rendered to.
+        channels.retain(|name, channel| {
+            required
+                .iter()
+                .any(|info| info.name == *name && info.format == channel.format())
+        });
+        let mut factory = window.factory().borrow_mut();
+        let mut pool = self.pool.borrow_mut();
+        for info in required.iter() {
+            if channels.contains_key(info.name) {
+                continue;
             }
-            Slot::RgbDepth { deflection, .. } => deflection.clone(),
-            Slot::Rgb { deflection, .. } => deflection.clone(),
+            let is_read = info.name == CHANNEL_COLOR
+                || info.name == CHANNEL_DEPTH
+                || self.requested.iter().any(|other| other.name == info.name);
+            let channel = if is_read {
+                pool.acquire(&mut factory, info.format, width, height)?
+            } else {
+                pool.scratch(&mut factory, *info, width, height)?
+            };
+            channels.insert(info.name, channel);
+        }
+        for info in forwarded.iter() {
+            let channel = match self.input.channel(info.name) {
+                Some(channel) if channel.format() == info.format => channel.clone(),
+                // Nobody writes this channel, provide it anyway.
+                _ => pool.acquire(&mut factory, info.format, width, height)?,
+            };
+            channels.insert(info.name, channel);
         }
+        drop(pool);
+
+        self.output = Slot::Image(channels);
+        Ok(self)
     }
 
-    pub fn as_color_change(&self) -> gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F> {
+    fn output_channel(&self, name: &str) -> Result<&Channel, NodeError> {
         match &self.output {
-            Slot::Empty => {
-                panic!("RGB output expected");
-            }
-            Slot::RgbDepth { color_change, .. } => color_change.clone(),
-            Slot::Rgb { color_change, .. } => color_change.clone(),
+            Slot::Empty => Err(NodeError::MissingOutput),
+            slot => slot.channel(name).ok_or_else(|| NodeError::MissingChannel(name.to_string())),
         }
     }
 
-    pub fn as_color_uncertainty(&self) -> gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F> {
-        match &self.output {
-            Slot::Empty => {
-                panic!("RGB output expected");
-            }
-            Slot::RgbDepth { color_uncertainty, .. } => color_uncertainty.clone(),
-            Slot::Rgb { color_uncertainty, .. } => color_uncertainty.clone(),
+    fn input_channel(&self, name: &str) -> Result<&Channel, NodeError> {
+        match &self.input {
+            Slot::Empty => Err(NodeError::MissingInput),
+            slot => slot.channel(name).ok_or_else(|| NodeError::MissingChannel(name.to_string())),
         }
     }
 
-    pub fn as_covariances(&self) -> gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F> {
-        match &self.output {
-            Slot::Empty => {
-                panic!("RGB output expected");
-            }
-            Slot::RgbDepth { covariances, .. } => covariances.clone(),
-            Slot::Rgb { covariances, .. } => covariances.clone(),
+    /// Render target of a 32-bit float RGBA output channel.
+    pub fn as_highp(&self, name: &str) -> Result<gfx::handle::RenderTargetView<Resources, Rgba32F>, NodeError> {
+        match self.output_channel(name)? {
+            Channel::Highp { target, .. } => Ok(target.clone()),
+            channel => Err(NodeError::IncompatibleChannel {
+                name: name.to_string(),
+                expected: ChannelFormat::Highp,
+                provided: channel.format(),
+            }),
         }
     }
 
-    pub fn as_color(&self) -> gfx::handle::RenderTargetView<gfx_device_gl::Resources, ColorFormat> {
-        match &self.output {
-            Slot::Empty | Slot::RgbDepth { .. } => {
-                panic!("RGB output expected");
-            }
-            Slot::Rgb { color, .. } => color.clone(),
+    /// Shader resource of a 32-bit float RGBA input channel, zero if the input lacks the channel.
+    pub fn as_highp_view(
+        &self,
+        name: &str,
+    ) -> Result<
+        (
+            gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+            gfx::handle::Sampler<Resources>,
+        ),
+        NodeError,
+    > {
+        let channel = match (self.input_channel(name), &self.blank) {
+            (Err(NodeError::MissingChannel(_)), Some(blank)) => return Ok((blank.clone(), self.sampler.clone())),
+            (channel, _) => channel?,
+        };
+        match channel {
+            Channel::Highp { view, .. } => Ok((view.clone(), self.sampler.clone())),
+            channel => Err(NodeError::IncompatibleChannel {
+                name: name.to_string(),
+                expected: ChannelFormat::Highp,
+                provided: channel.format(),
+            }),
+        }
+    }
+
+    pub fn as_deflection(&self) -> Result<gfx::handle::RenderTargetView<Resources, Rgba32F>, NodeError> {
+        self.as_highp(CHANNEL_DEFLECTION)
+    }
+
+    pub fn as_color_change(&self) -> Result<gfx::handle::RenderTargetView<Resources, Rgba32F>, NodeError> {
+        self.as_highp(CHANNEL_COLOR_CHANGE)
+    }
+
+    pub fn as_color_uncertainty(&self) -> Result<gfx::handle::RenderTargetView<Resources, Rgba32F>, NodeError> {
+        self.as_highp(CHANNEL_COLOR_UNCERTAINTY)
+    }
+
+    pub fn as_covariances(&self) -> Result<gfx::handle::RenderTargetView<Resources, Rgba32F>, NodeError> {
+        self.as_highp(CHANNEL_COVARIANCES)
+    }
+
+    pub fn as_color(&self) -> Result<gfx::handle::RenderTargetView<Resources, ColorFormat>, NodeError> {
+        match (self.output.kind(), self.output_channel(CHANNEL_COLOR)?) {
+            (Some(SlotKind::ColorDepth), _) => Err(NodeError::IncompatibleOutput {
+                expected: SlotKind::Color,
+                provided: SlotKind::ColorDepth,
+            }),
+            (_, Channel::Color { target, .. }) => Ok(target.clone()),
+            (_, channel) => Err(NodeError::IncompatibleChannel {
+                name: CHANNEL_COLOR.to_string(),
+                expected: ChannelFormat::Color,
+                provided: channel.format(),
+            }),
         }
     }
 
     pub fn as_color_view(
         &self,
-    ) -> (
-        gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>,
-        gfx::handle::Sampler<gfx_device_gl::Resources>,
-    ) {
-        match &self.input {
-            Slot::Empty | Slot::RgbDepth { .. } => {
-                panic!("RGB input expected");
-            }
-            Slot::Rgb { color_view, .. } => (
-                color_view.clone().expect("Shader resource expected"),
-                self.sampler.clone(),
-            ),
+    ) -> Result<
+        (
+            gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+            gfx::handle::Sampler<Resources>,
+        ),
+        NodeError,
+    > {
+        if let Some(SlotKind::ColorDepth) = self.input.kind() {
+            return Err(NodeError::IncompatibleInput {
+                expected: SlotKind::Color,
+                provided: SlotKind::ColorDepth,
+            });
         }
+        Self::color_view(self.input_channel(CHANNEL_COLOR)?, &self.sampler)
     }
 
-    pub fn as_deflection_view(
+    /// Color of one eye of the input (with or without depth), see [Slot::eyes].
+    pub fn as_eye_color_view(
         &self,
-    ) -> (
-        gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>,
-        gfx::handle::Sampler<gfx_device_gl::Resources>,
-    ) {
-        match &self.input {
-            Slot::Empty => {
-                panic!("RGB input expected");
-            }
-            Slot::Rgb { deflection_view, .. } => (
-                deflection_view.clone(),
-                self.sampler.clone(),
-            ),
-            Slot::RgbDepth { deflection_view,.. } => (
-                deflection_view.clone(),
-                self.sampler.clone(),
-            ),
+        eye: usize,
+    ) -> Result<
+        (
+            gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+            gfx::handle::Sampler<Resources>,
+        ),
+        NodeError,
+    > {
+        let eyes = self.input.eyes();
+        let channel = eyes
+            .get(eye)
+            .and_then(|slot| slot.channel(CHANNEL_COLOR))
+            .ok_or(NodeError::MissingInput)?;
+        Self::color_view(channel, &self.sampler)
+    }
+
+    /// Color of an auxiliary input (with or without depth).
+    pub fn as_aux_color_view(
+        &self,
+        index: usize,
+    ) -> Result<
+        (
+            gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+            gfx::handle::Sampler<Resources>,
+        ),
+        NodeError,
+    > {
+        let channel = self
+            .aux_inputs
+            .get(index)
+            .and_then(|slot| slot.channel(CHANNEL_COLOR))
+            .ok_or(NodeError::MissingInput)?;
+        Self::color_view(channel, &self.sampler)
+    }
+
+    fn color_view(
+        channel: &Channel,
+        sampler: &gfx::handle::Sampler<Resources>,
+    ) -> Result<
+        (
+            gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+            gfx::handle::Sampler<Resources>,
+        ),
+        NodeError,
+    > {
+        match channel {
+            Channel::Color { view, .. } => Ok((view.clone().ok_or(NodeError::UnreadableInput)?, sampler.clone())),
+            channel => Err(NodeError::IncompatibleChannel {
+                name: CHANNEL_COLOR.to_string(),
+                expected: ChannelFormat::Color,
+                provided: channel.format(),
+            }),
         }
     }
 
+    pub fn as_deflection_view(
+        &self,
+    ) -> Result<
+        (
+            gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+            gfx::handle::Sampler<Resources>,
+        ),
+        NodeError,
+    > {
+        self.as_highp_view(CHANNEL_DEFLECTION)
+    }
+
     pub fn as_color_change_view(
         &self,
-    ) -> (
-        gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>,
-        gfx::handle::Sampler<gfx_device_gl::Resources>,
-    ) {
-        match &self.input {
-            Slot::Empty => {
-                panic!("RGB input expected");
-            }
-            Slot::Rgb { color_change_view, .. } => (
-                color_change_view.clone(),
-                self.sampler.clone(),
-            ),
-            Slot::RgbDepth { color_change_view,.. } => (
-                color_change_view.clone(),
-                self.sampler.clone(),
-            ),
-        }
+    ) -> Result<
+        (
+            gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+            gfx::handle::Sampler<Resources>,
+        ),
+        NodeError,
+    > {
+        self.as_highp_view(CHANNEL_COLOR_CHANGE)
     }
+
     pub fn as_color_uncertainty_view(
         &self,
-    ) -> (
-        gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>,
-        gfx::handle::Sampler<gfx_device_gl::Resources>,
-    ) {
-        match &self.input {
-            Slot::Empty => {
-                panic!("RGB input expected");
-            }
-            Slot::Rgb { color_uncertainty_view, .. } => (
-                color_uncertainty_view.clone(),
-                self.sampler.clone(),
-            ),
-            Slot::RgbDepth { color_uncertainty_view,.. } => (
-                color_uncertainty_view.clone(),
-                self.sampler.clone(),
-            ),
-        }
+    ) -> Result<
+        (
+            gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+            gfx::handle::Sampler<Resources>,
+        ),
+        NodeError,
+    > {
+        self.as_highp_view(CHANNEL_COLOR_UNCERTAINTY)
     }
 
     pub fn as_covariances_view(
         &self,
-    ) -> (
-        gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>,
-        gfx::handle::Sampler<gfx_device_gl::Resources>,
-    ) {
-        match &self.input {
-            Slot::Empty => {
-                panic!("RGB input expected");
-            }
-            Slot::Rgb { covariances_view, .. } => (
-                covariances_view.clone(),
-                self.sampler.clone(),
-            ),
-            Slot::RgbDepth { covariances_view,.. } => (
-                covariances_view.clone(),
-                self.sampler.clone(),
-            ),
-        }
+    ) -> Result<
+        (
+            gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+            gfx::handle::Sampler<Resources>,
+        ),
+        NodeError,
+    > {
+        self.as_highp_view(CHANNEL_COVARIANCES)
     }
 
     pub fn as_color_depth(
         &self,
-    ) -> (
-        gfx::handle::RenderTargetView<gfx_device_gl::Resources, ColorFormat>,
-        gfx::handle::RenderTargetView<gfx_device_gl::Resources, DepthFormat>,
-    ) {
-        match &self.output {
-            Slot::Empty | Slot::Rgb { .. } => {
-                panic!("RGBD output expected");
+    ) -> Result<
+        (
+            gfx::handle::RenderTargetView<Resources, ColorFormat>,
+            gfx::handle::RenderTargetView<Resources, DepthFormat>,
+        ),
+        NodeError,
+    > {
+        match (self.output_channel(CHANNEL_COLOR)?, self.output.channel(CHANNEL_DEPTH)) {
+            (Channel::Color { target: color, .. }, Some(Channel::Depth { target: depth, .. })) => {
+                Ok((color.clone(), depth.clone()))
             }
-            Slot::RgbDepth { color, depth, .. } => (color.clone(), depth.clone()),
+            _ => Err(NodeError::IncompatibleOutput {
+                expected: SlotKind::ColorDepth,
+                provided: SlotKind::Color,
+            }),
         }
     }
 
     pub fn as_all_output(
         &self,
-    ) -> (
-        gfx::handle::RenderTargetView<gfx_device_gl::Resources, ColorFormat>,
-        gfx::handle::RenderTargetView<gfx_device_gl::Resources, DepthFormat>,
-        gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F>,
-        gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F>,
-        gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F>,
-        gfx::handle::RenderTargetView<gfx_device_gl::Resources, Rgba32F>,
-
-    ) {
-        match &self.output {
-            Slot::Empty | Slot::Rgb { .. } => {
-                panic!("RGBD output expected");
-            }
-            Slot::RgbDepth { color, depth, deflection, color_change, color_uncertainty, covariances, .. } 
-            => (color.clone(), depth.clone(), deflection.clone(), color_change.clone(), color_uncertainty.clone(),covariances.clone()),
-        }
+    ) -> Result<
+        (
+            gfx::handle::RenderTargetView<Resources, ColorFormat>,
+            gfx::handle::RenderTargetView<Resources, DepthFormat>,
+            gfx::handle::RenderTargetView<Resources, Rgba32F>,
+            gfx::handle::RenderTargetView<Resources, Rgba32F>,
+            gfx::handle::RenderTargetView<Resources, Rgba32F>,
+            gfx::handle::RenderTargetView<Resources, Rgba32F>,
+        ),
+        NodeError,
+    > {
+        let (color, depth) = self.as_color_depth()?;
+        Ok((
+            color,
+            depth,
+            self.as_deflection()?,
+            self.as_color_change()?,
+            self.as_color_uncertainty()?,
+            self.as_covariances()?,
+        ))
     }
 
     pub fn as_color_depth_view(
         &self,
-    ) -> (
+    ) -> Result<
         (
-            gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>,
-            gfx::handle::Sampler<gfx_device_gl::Resources>,
-        ),
-        (
-            gfx::handle::ShaderResourceView<gfx_device_gl::Resources, f32>,
-            gfx::handle::Sampler<gfx_device_gl::Resources>,
+            (
+                gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+                gfx::handle::Sampler<Resources>,
+            ),
+            (
+                gfx::handle::ShaderResourceView<Resources, f32>,
+                gfx::handle::Sampler<Resources>,
+            ),
         ),
-    ) {
-        match &self.input {
-            Slot::Empty | Slot::Rgb { .. } => {
-                panic!("RGBD input expected");
-            }
-            Slot::RgbDepth {
-                color_view,
-                depth_view,
-                ..
-            } => (
+        NodeError,
+    > {
+        match (self.input_channel(CHANNEL_COLOR)?, self.input.channel(CHANNEL_DEPTH)) {
+            (Channel::Color { view: Some(color_view), .. }, Some(Channel::Depth { view: depth_view, .. })) => Ok((
                 (color_view.clone(), self.sampler.clone()),
                 (depth_view.clone(), self.sampler.clone()),
-            ),
+            )),
+            (Channel::Color { view: None, .. }, Some(_)) => Err(NodeError::UnreadableInput),
+            _ => Err(NodeError::IncompatibleInput {
+                expected: SlotKind::ColorDepth,
+                provided: SlotKind::Color,
+            }),
         }
     }
 
-    fn output_size(&self) -> [u32; 2] {
-        let dimensions = match &self.output {
-            Slot::Empty => {
-                panic!("Output expected");
-            }
-            Slot::Rgb { color, .. } => color.get_dimensions(),
-            Slot::RgbDepth { color, .. } => color.get_dimensions(),
-        };
-
-        [dimensions.0 as u32, dimensions.1 as u32]
-    }
-
-    pub fn output_size_f32(&self) -> [f32; 2] {
-        let size = self.output_size();
-        [size[0] as f32, size[1] as f32]
+    pub fn output_size_f32(&self) -> Result<[f32; 2], NodeError> {
+        let size = self.output.size().ok_or(NodeError::MissingOutput)?;
+        Ok([size[0] as f32, size[1] as f32])
     }
 
-    pub fn input_size_f32(&self) -> [f32; 2] {
-        let dimensions = match &self.input {
-            Slot::Empty => {
-                panic!("Output expected");
-            }
-            Slot::Rgb { color, .. } => color.get_dimensions(),
-            Slot::RgbDepth { color, .. } => color.get_dimensions(),
-        };
-
-        [dimensions.0 as f32, dimensions.1 as f32]
+    pub fn input_size_f32(&self) -> Result<[f32; 2], NodeError> {
+        let size = self.input.size().ok_or(NodeError::MissingInput)?;
+        Ok([size[0] as f32, size[1] as f32])
     }
-
-    
 }
-
-pub struct WellKnownSlots{
-    original_image: RefCell<Option<(gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>, gfx::handle::Sampler<gfx_device_gl::Resources>)>>
-
-} 
-
-impl WellKnownSlots{
-    pub fn new() -> Self{
-        WellKnownSlots{
-            original_image: RefCell::new(None)
-        }
-    }
-
-    pub fn get_original(
-        &self,
-    ) -> Option<(gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>, gfx::handle::Sampler<gfx_device_gl::Resources>)>
-     {
-        let guard =  RefCell::borrow(&self.original_image);
-        match *guard{
-            Some(ref original_image) => {
-                Some(original_image.clone())
-            },
-            None => None
-        }
-    }
-    pub fn set_original(
-        &self,
-        view: (gfx::handle::ShaderResourceView<gfx_device_gl::Resources, [f32; 4]>, gfx::handle::Sampler<gfx_device_gl::Resources>)
-    ) {       
-        let mut guard = RefCell::borrow_mut(&self.original_image);
-        *guard =  Some(view.clone());
-    }
-}
\ No newline at end of file
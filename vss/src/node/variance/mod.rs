@@ -1,7 +1,7 @@
 use std::borrow::BorrowMut;
 use std::collections::HashMap;
 use std::io::Write;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 
 use super::*;
 use gfx;
@@ -33,6 +33,7 @@ pub struct VarianceMeasure {
     pso: gfx::PipelineState<Resources, pipe::Meta>,
     pso_data: pipe::Data<Resources>,
     pub log_file: Option<File>,
+    log_path: Option<String>,
     last_info: f32,
 }
 
@@ -111,7 +112,7 @@ impl VarianceMeasure{
 
 
 impl Node for VarianceMeasure {
-    fn new(window: &Window) -> Self {
+    fn new(window: &Window) -> Result<Self, NodeError> {
         let mut factory = window.factory().borrow_mut();
 
         let pso = factory
@@ -119,20 +120,19 @@ impl Node for VarianceMeasure {
                 &include_glsl!("../mod.vert"),
                 &include_glsl!("mod.frag"),
                 pipe::new(),
-            )
-            .unwrap();
+            )?;
 
         let sampler = factory.create_sampler_linear();
-        let (_, color_view) = load_texture_from_bytes(&mut factory, &[0; 4], 1, 1).unwrap();
-        let (_, capture_view) = load_texture_from_bytes(&mut factory, &[0; 4], 1, 1).unwrap();
-        let (_, _, rt_color) = factory.create_render_target(1, 1).unwrap();
-        let (_, _, rt_measure) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_deflection, rt_deflection) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_color_change, rt_color_change) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_color_uncertainty, rt_color_uncertainty) = factory.create_render_target(1, 1).unwrap();
-        let (_, s_covariances, rt_covariances) = factory.create_render_target(1, 1).unwrap();
-
-        VarianceMeasure {
+        let (_, color_view) = load_texture_from_bytes(&mut factory, &[0; 4], 1, 1).map_err(NodeError::Resource)?;
+        let (_, capture_view) = load_texture_from_bytes(&mut factory, &[0; 4], 1, 1).map_err(NodeError::Resource)?;
+        let (_, _, rt_color) = factory.create_render_target(1, 1)?;
+        let (_, _, rt_measure) = factory.create_render_target(1, 1)?;
+        let (_, s_deflection, rt_deflection) = factory.create_render_target(1, 1)?;
+        let (_, s_color_change, rt_color_change) = factory.create_render_target(1, 1)?;
+        let (_, s_color_uncertainty, rt_color_uncertainty) = factory.create_render_target(1, 1)?;
+        let (_, s_covariances, rt_covariances) = factory.create_render_target(1, 1)?;
+
+        Ok(VarianceMeasure {
             pso,
             pso_data: pipe::Data {
                 u_resolution: [1.0, 1.0],
@@ -154,35 +154,64 @@ impl Node for VarianceMeasure {
                 u_color_space: 0,
             },
             log_file: None,
+            log_path: None,
             last_info: 1.0,
-        }
+        })
+    }
+
+    fn schema() -> Vec<Parameter> {
+        vec![Parameter::path("variance_log", "File to which variance metrics are appended every frame")]
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        Self::schema()
     }
 
-    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> NodeSlots {
+    fn output_channels(&self) -> Vec<ChannelInfo> {
+        ERROR_CHANNELS.to_vec()
+    }
+
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
         let slots = slots
-            .to_color_input(window)
-            .to_color_output(window);
-
-        self.pso_data.u_resolution = slots.output_size_f32();
-        self.pso_data.s_color = slots.as_color_view();
-        self.pso_data.rt_color = slots.as_color();
-        self.pso_data.s_deflection = slots.as_deflection_view();
-        self.pso_data.rt_deflection = slots.as_deflection();
-        self.pso_data.s_color_change = slots.as_color_change_view();
-        self.pso_data.rt_color_change = slots.as_color_change();  
-        self.pso_data.s_color_uncertainty = slots.as_color_uncertainty_view();
-        self.pso_data.rt_color_uncertainty = slots.as_color_uncertainty();
-        self.pso_data.s_covariances = slots.as_covariances_view();
-        self.pso_data.rt_covariances = slots.as_covariances();
+            .to_color_input(window)?
+            .to_color_output(window)?;
+
+        self.pso_data.u_resolution = slots.output_size_f32()?;
+        self.pso_data.s_color = slots.as_color_view()?;
+        self.pso_data.rt_color = slots.as_color()?;
+        self.pso_data.s_deflection = slots.as_deflection_view()?;
+        self.pso_data.rt_deflection = slots.as_deflection()?;
+        self.pso_data.s_color_change = slots.as_color_change_view()?;
+        self.pso_data.rt_color_change = slots.as_color_change()?;  
+        self.pso_data.s_color_uncertainty = slots.as_color_uncertainty_view()?;
+        self.pso_data.rt_color_uncertainty = slots.as_color_uncertainty()?;
+        self.pso_data.s_covariances = slots.as_covariances_view()?;
+        self.pso_data.rt_covariances = slots.as_covariances()?;
             
         let (color, _) = create_texture_render_target::<HighpFormat>(
             &mut window.factory().borrow_mut(),
             self.pso_data.u_resolution[0] as u32,
             self.pso_data.u_resolution[1] as u32,
-        );
+        )?;
         self.pso_data.rt_measure = color;
 
-        slots
+        Ok(slots)
+    }
+
+    fn update_values(&mut self, _window: &Window, values: &ValueMap) {
+        if let Some(Value::String(log_path)) = values.get("variance_log") {
+            if self.log_path.as_ref() != Some(log_path) {
+                // Report each path that cannot be opened once.
+                self.log_file = match OpenOptions::new().append(true).create(true).open(log_path) {
+                    Ok(file) => Some(file),
+                    Err(e) => {
+                        eprintln!("error: variance_log: {}: {}", log_path, e);
+                        None
+                    }
+                };
+                self.log_path = Some(log_path.clone());
+            }
+        }
     }
 
     fn input(&mut self, perspective: &EyePerspective, vis_param: &VisualizationParameters) -> EyePerspective {
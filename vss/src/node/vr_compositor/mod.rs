@@ -22,7 +22,7 @@ impl VRCompositor{
 }
 
 impl Node for VRCompositor {
-    fn new(window: &Window) -> Self {
+    fn new(window: &Window) -> Result<Self, NodeError> {
         let mut factory = window.factory().borrow_mut();
 
         let pso = factory
@@ -30,13 +30,12 @@ impl Node for VRCompositor {
                 &include_glsl!("mod.vert"),
                 &include_glsl!("mod.frag"),
                 pipe::new(),
-            )
-            .unwrap();
+            )?;
 
         let sampler = factory.create_sampler_linear();
-        let (_, src, dst) = factory.create_render_target(1, 1).unwrap();
+        let (_, src, dst) = factory.create_render_target(1, 1)?;
 
-        VRCompositor {
+        Ok(VRCompositor {
             pso,
             pso_data: pipe::Data {
                 u_resolution_out: [1.0, 1.0],
@@ -44,16 +43,16 @@ impl Node for VRCompositor {
                 s_source: (src, sampler),
                 rt_color: dst,
             },
-        }
+        })
     }
 
-    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> NodeSlots {
-        let slots = slots.to_color_input(window).to_color_output(window);
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
+        let slots = slots.to_color_input(window)?.to_color_output(window)?;
 
-        self.pso_data.u_resolution_out = slots.output_size_f32();
-        self.pso_data.s_source = slots.as_color_view();
-        self.pso_data.rt_color = slots.as_color();
-        slots
+        self.pso_data.u_resolution_out = slots.output_size_f32()?;
+        self.pso_data.s_source = slots.as_color_view()?;
+        self.pso_data.rt_color = slots.as_color()?;
+        Ok(slots)
     }
 
     fn render(&mut self, window: &Window) {
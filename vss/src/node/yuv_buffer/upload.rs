@@ -62,7 +62,7 @@ impl UploadYuvBuffer {
 }
 
 impl Node for UploadYuvBuffer {
-    fn new(window: &Window) -> Self {
+    fn new(window: &Window) -> Result<Self, NodeError> {
         let mut factory = window.factory().borrow_mut();
 
         let pso = factory
@@ -70,18 +70,17 @@ impl Node for UploadYuvBuffer {
                 &include_glsl!("../mod.vert"),
                 &include_glsl!("upload.frag"),
                 pipe::new(),
-            )
-            .unwrap();
+            )?;
 
         let sampler = factory.create_sampler_linear();
         let (_, srv, _): (
             _,
             _,
             gfx::handle::RenderTargetView<gfx_device_gl::Resources, f32>,
-        ) = factory.create_render_target(1, 1).unwrap();
-        let (_, _, rtv) = factory.create_render_target(1, 1).unwrap();
+        ) = factory.create_render_target(1, 1)?;
+        let (_, _, rtv) = factory.create_render_target(1, 1)?;
 
-        UploadYuvBuffer {
+        Ok(UploadYuvBuffer {
             buffer_next: None,
             texture_y: None,
             texture_u: None,
@@ -95,10 +94,10 @@ impl Node for UploadYuvBuffer {
                 s_v: (srv, sampler),
                 rt_color: rtv,
             },
-        }
+        })
     }
 
-    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> NodeSlots {
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
         if let Some(buffer) = &self.buffer_next {
             let mut factory = window.factory().borrow_mut();
 
@@ -108,21 +107,21 @@ impl Node for UploadYuvBuffer {
                 buffer.width as u32,
                 buffer.height as u32,
             )
-            .unwrap();
+            .map_err(NodeError::Resource)?;
             let (texture_u, view_u) = load_single_channel_texture_from_bytes(
                 &mut factory,
                 &buffer.pixels_u,
                 (buffer.width / 2) as u32,
                 (buffer.height / 2) as u32,
             )
-            .unwrap();
+            .map_err(NodeError::Resource)?;
             let (texture_v, view_v) = load_single_channel_texture_from_bytes(
                 &mut factory,
                 &buffer.pixels_v,
                 (buffer.width / 2) as u32,
                 (buffer.height / 2) as u32,
             )
-            .unwrap();
+            .map_err(NodeError::Resource)?;
 
             self.texture_y = Some(texture_y);
             self.texture_u = Some(texture_u);
@@ -142,10 +141,10 @@ impl Node for UploadYuvBuffer {
             height = info.height as u32;
         }
 
-        let slots = slots.emplace_color_output(window, width, height);
-        self.pso_data.rt_color = slots.as_color();
+        let slots = slots.emplace_color_output(window, width, height)?;
+        self.pso_data.rt_color = slots.as_color()?;
 
-        slots
+        Ok(slots)
     }
 
     fn render(&mut self, window: &Window) {
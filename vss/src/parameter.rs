@@ -0,0 +1,144 @@
+use crate::*;
+use std::fmt;
+
+/// Type of the [Value] expected for a parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterType {
+    Bool,
+    Number,
+    /// A number without fractional part, e.g., an enumeration index.
+    Integer,
+    /// A file path.
+    Path,
+    Matrix,
+    /// A list of names, e.g., of nodes.
+    List,
+}
+
+/// Describes a value that is read from the [ValueMap], e.g., by a [Node].
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub name: &'static str,
+    pub kind: ParameterType,
+    /// Unit of numbers (empty if unit-less).
+    pub unit: &'static str,
+    /// Inclusive range of numbers.
+    pub range: Option<(f64, f64)>,
+    /// Value that is assumed if the parameter is not set.
+    pub default: Option<Value>,
+    pub help: &'static str,
+}
+
+impl Parameter {
+    /// A switch that is off by default.
+    pub fn toggle(name: &'static str, help: &'static str) -> Self {
+        Parameter {
+            name,
+            kind: ParameterType::Bool,
+            unit: "",
+            range: None,
+            default: Some(Value::Bool(false)),
+            help,
+        }
+    }
+
+    pub fn number(name: &'static str, unit: &'static str, range: (f64, f64), default: f64, help: &'static str) -> Self {
+        Parameter {
+            name,
+            kind: ParameterType::Number,
+            unit,
+            range: Some(range),
+            default: Some(Value::Number(default)),
+            help,
+        }
+    }
+
+    pub fn integer(name: &'static str, range: (f64, f64), default: f64, help: &'static str) -> Self {
+        Parameter {
+            name,
+            kind: ParameterType::Integer,
+            unit: "",
+            range: Some(range),
+            default: Some(Value::Number(default)),
+            help,
+        }
+    }
+
+    /// A path to a file, unset by default.
+    pub fn path(name: &'static str, help: &'static str) -> Self {
+        Parameter {
+            name,
+            kind: ParameterType::Path,
+            unit: "",
+            range: None,
+            default: None,
+            help,
+        }
+    }
+
+    /// A 4x4 matrix, unset by default.
+    pub fn matrix(name: &'static str, help: &'static str) -> Self {
+        Parameter {
+            name,
+            kind: ParameterType::Matrix,
+            unit: "",
+            range: None,
+            default: None,
+            help,
+        }
+    }
+
+    /// A list of names, empty by default.
+    pub fn list(name: &'static str, help: &'static str) -> Self {
+        Parameter {
+            name,
+            kind: ParameterType::List,
+            unit: "",
+            range: None,
+            default: Some(Value::Array(Vec::new())),
+            help,
+        }
+    }
+
+    /// Looks up the value of this parameter, falling back to the default.
+    pub fn get<'a>(&'a self, values: &'a ValueMap) -> Option<&'a Value> {
+        values.get(self.name).or(self.default.as_ref())
+    }
+}
+
+impl fmt::Display for Parameter {
+    /// Formats the parameter as single line, e.g., for `--help` output.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            ParameterType::Bool => "bool",
+            ParameterType::Number => "number",
+            ParameterType::Integer => "integer",
+            ParameterType::Path => "path",
+            ParameterType::Matrix => "matrix",
+            ParameterType::List => "list",
+        };
+        write!(f, "{:<36} {:<8}", self.name, kind)?;
+        match self.range {
+            Some((min, max)) => write!(f, " [{}, {}]", min, max)?,
+            None => {}
+        }
+        if !self.unit.is_empty() {
+            write!(f, " {}", self.unit)?;
+        }
+        match &self.default {
+            Some(Value::Bool(b)) => write!(f, " (default: {})", b)?,
+            Some(Value::Number(n)) => write!(f, " (default: {})", n)?,
+            _ => {}
+        }
+        write!(f, " - {}", self.help)
+    }
+}
+
+/// Appends parameters to a list, skipping those whose name is already listed.
+pub fn extend_parameters(parameters: &mut Vec<Parameter>, additional: Vec<Parameter>) {
+    for parameter in additional {
+        if !parameters.iter().any(|p| p.name == parameter.name) {
+            parameters.push(parameter);
+        }
+    }
+}
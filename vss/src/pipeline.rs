@@ -0,0 +1,345 @@
+use crate::*;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+/// A single entry of a [Pipeline].
+#[derive(Debug, Clone)]
+pub struct PipelineNode {
+    /// Name of the node, e.g., `retina`.
+    pub name: String,
+    /// Values that override the flow's values for this node only.
+    pub values: ValueMap,
+    /// Identifier for reading this node's output from later nodes.
+    pub id: Option<String>,
+    /// Identifiers of the nodes to read from (the predecessor, if empty), see [Flow::add_linked_node].
+    pub inputs: Vec<String>,
+}
+
+impl PipelineNode {
+    /// An entry that reads its predecessor.
+    pub fn new(name: &str) -> Self {
+        PipelineNode {
+            name: name.to_string(),
+            values: ValueMap::new(),
+            id: None,
+            inputs: Vec::new(),
+        }
+    }
+}
+
+/// Describes which nodes make up a [Flow], in which order, and with which per-node values.
+///
+/// Pipelines are usually loaded from JSON, e.g.:
+/// ```json
+/// {
+///     "nodes": [
+///         "cataract",
+///         { "node": "lens", "values": { "rays": 2 } },
+///         "display"
+///     ]
+/// }
+/// ```
+///
+/// Entries with an `"id"` can be read by later entries using `"inputs"`, e.g., to compare the
+/// simulation with the unmodified input (which has the id `"input"`):
+/// ```json
+/// {
+///     "nodes": [
+///         "cataract",
+///         { "node": "compare", "inputs": ["cataract", "input"] },
+///         "display"
+///     ]
+/// }
+/// ```
+/// Entries without an id can be read using their node name.
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    pub nodes: Vec<PipelineNode>,
+}
+
+#[derive(Debug)]
+pub enum PipelineError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// The description is valid JSON, but not a valid pipeline.
+    Format(String),
+    /// A node name is not known (with position in the pipeline and all known names).
+    UnknownNode { index: usize, name: String, known: Vec<String> },
+    /// A node cannot consume the output of its predecessor.
+    IncompatibleSlots { index: usize, name: String, expected: SlotKind, provided: SlotKind },
+    /// A node could not be created.
+    Node { index: usize, name: String, error: NodeError },
+    /// A node reads from an identifier that no preceding node has.
+    UnknownInput { index: usize, name: String, input: String },
+    /// Two nodes have the same identifier.
+    DuplicateId { index: usize, id: String },
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PipelineError::Io(err) => write!(f, "cannot read pipeline: {}", err),
+            PipelineError::Json(err) => write!(f, "cannot parse pipeline: {}", err),
+            PipelineError::Format(msg) => write!(f, "invalid pipeline: {}", msg),
+            PipelineError::UnknownNode { index, name, known } => write!(
+                f,
+                "unknown node \"{}\" at position {} (known nodes: {})",
+                name,
+                index,
+                known.join(", ")
+            ),
+            PipelineError::IncompatibleSlots {
+                index,
+                name,
+                expected,
+                provided,
+            } => write!(
+                f,
+                "node \"{}\" at position {} expects {:?} input, but its predecessor provides {:?}",
+                name, index, expected, provided
+            ),
+            PipelineError::Node { index, name, error } => {
+                write!(f, "cannot create node \"{}\" at position {}: {}", name, index, error)
+            }
+            PipelineError::UnknownInput { index, name, input } => write!(
+                f,
+                "node \"{}\" at position {} reads from unknown node \"{}\"",
+                name, index, input
+            ),
+            PipelineError::DuplicateId { index, id } => {
+                write!(f, "id \"{}\" at position {} is already used", id, index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+impl From<std::io::Error> for PipelineError {
+    fn from(err: std::io::Error) -> Self {
+        PipelineError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for PipelineError {
+    fn from(err: serde_json::Error) -> Self {
+        PipelineError::Json(err)
+    }
+}
+
+impl Pipeline {
+    /// Parses a pipeline from a JSON string.
+    pub fn from_json(json: &str) -> Result<Self, PipelineError> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let entries = value
+            .get("nodes")
+            .and_then(|nodes| nodes.as_array())
+            .ok_or_else(|| PipelineError::Format("expected an object with a \"nodes\" array".to_string()))?;
+
+        let mut nodes = Vec::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let node = match entry {
+                serde_json::Value::String(name) => PipelineNode::new(name),
+                serde_json::Value::Object(object) => {
+                    let name = object.get("node").and_then(|name| name.as_str()).ok_or_else(|| {
+                        PipelineError::Format(format!("node at position {} has no \"node\" name", index))
+                    })?;
+                    let values = match object.get("values") {
+                        None => ValueMap::new(),
+                        Some(serde_json::Value::Object(values)) => value_map_from_json(values)?,
+                        Some(_) => {
+                            return Err(PipelineError::Format(format!(
+                                "values of node \"{}\" at position {} must be an object",
+                                name, index
+                            )))
+                        }
+                    };
+                    let id = match object.get("id") {
+                        None => None,
+                        Some(serde_json::Value::String(id)) => Some(id.to_string()),
+                        Some(_) => {
+                            return Err(PipelineError::Format(format!(
+                                "id of node \"{}\" at position {} must be a string",
+                                name, index
+                            )))
+                        }
+                    };
+                    let inputs = match object.get("inputs") {
+                        None => Vec::new(),
+                        Some(serde_json::Value::Array(inputs)) => inputs
+                            .iter()
+                            .map(|input| input.as_str().map(|input| input.to_string()))
+                            .collect::<Option<Vec<_>>>()
+                            .ok_or_else(|| {
+                                PipelineError::Format(format!(
+                                    "inputs of node \"{}\" at position {} must be strings",
+                                    name, index
+                                ))
+                            })?,
+                        Some(_) => {
+                            return Err(PipelineError::Format(format!(
+                                "inputs of node \"{}\" at position {} must be an array",
+                                name, index
+                            )))
+                        }
+                    };
+                    PipelineNode {
+                        name: name.to_string(),
+                        values,
+                        id,
+                        inputs,
+                    }
+                }
+                _ => {
+                    return Err(PipelineError::Format(format!(
+                        "node at position {} must be a name or an object",
+                        index
+                    )))
+                }
+            };
+            nodes.push(node);
+        }
+
+        Ok(Pipeline { nodes })
+    }
+
+    /// Loads a pipeline from a JSON file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PipelineError> {
+        let mut data = String::new();
+        File::open(path)?.read_to_string(&mut data)?;
+        Self::from_json(&data)
+    }
+
+    /// Checks that all nodes of this pipeline are registered, that all inputs refer to preceding
+    /// nodes, and that their slots fit together.
+    pub fn validate(&self, registry: &NodeRegistry) -> Result<(), PipelineError> {
+        // The input node is not part of the pipeline, assume it provides color and depth.
+        let mut provided = vec![(FLOW_INPUT.to_string(), SlotKind::ColorDepth)];
+        for (index, node) in self.nodes.iter().enumerate() {
+            let descriptor = registry.get(&node.name).ok_or_else(|| PipelineError::UnknownNode {
+                index,
+                name: node.name.clone(),
+                known: registry.names().iter().map(|name| name.to_string()).collect(),
+            })?;
+
+            let mut input_kinds = Vec::new();
+            for input in node.inputs.iter() {
+                let kind = provided
+                    .iter()
+                    .rev()
+                    .find(|(id, _)| id == input)
+                    .map(|(_, kind)| *kind)
+                    .ok_or_else(|| PipelineError::UnknownInput {
+                        index,
+                        name: node.name.clone(),
+                        input: input.clone(),
+                    })?;
+                input_kinds.push(kind);
+            }
+            // Without inputs, the predecessor is read.
+            let input = input_kinds.first().copied().unwrap_or(provided.last().unwrap().1);
+            if !descriptor.input.accepts(input) {
+                return Err(PipelineError::IncompatibleSlots {
+                    index,
+                    name: node.name.clone(),
+                    expected: descriptor.input,
+                    provided: input,
+                });
+            }
+
+            let id = node.id.as_ref().unwrap_or(&node.name);
+            if node.id.is_some() && provided.iter().any(|(other, _)| other == id) {
+                return Err(PipelineError::DuplicateId {
+                    index,
+                    id: id.clone(),
+                });
+            }
+            let output = if descriptor.output == SlotKind::Any {
+                input
+            } else {
+                descriptor.output
+            };
+            provided.push((id.clone(), output));
+        }
+        Ok(())
+    }
+
+    /// Appends the nodes of this pipeline to the given flow of the window.
+    ///
+    /// Nothing is added if the pipeline does not validate or if any node cannot be created.
+    pub fn build(&self, window: &mut Window, registry: &NodeRegistry, flow_index: usize) -> Result<(), PipelineError> {
+        self.build_nodes(window, registry, flow_index, false)
+    }
+
+    /// Like [Pipeline::build], but runs each node once per eye (see [PerEye]).
+    pub fn build_stereo(&self, window: &mut Window, registry: &NodeRegistry, flow_index: usize) -> Result<(), PipelineError> {
+        self.build_nodes(window, registry, flow_index, true)
+    }
+
+    fn build_nodes(&self, window: &mut Window, registry: &NodeRegistry, flow_index: usize, stereo: bool) -> Result<(), PipelineError> {
+        self.validate(registry)?;
+        // The flow's input node precedes the pipeline, unless the flow is empty.
+        if window.flow_nodes_len(flow_index) == 0 {
+            let mut ids = Vec::new();
+            for (index, entry) in self.nodes.iter().enumerate() {
+                if entry.inputs.iter().any(|input| input == FLOW_INPUT) && !ids.contains(&FLOW_INPUT) {
+                    return Err(PipelineError::UnknownInput {
+                        index,
+                        name: entry.name.clone(),
+                        input: FLOW_INPUT.to_string(),
+                    });
+                }
+                ids.push(entry.id.as_ref().unwrap_or(&entry.name).as_str());
+            }
+        }
+        let mut nodes = Vec::new();
+        for (index, entry) in self.nodes.iter().enumerate() {
+            // Validated above, hence known.
+            let create = || {
+                registry.create(&entry.name, window).unwrap().map_err(|error| PipelineError::Node {
+                    index,
+                    name: entry.name.clone(),
+                    error,
+                })
+            };
+            let node: Box<dyn Node> = if stereo {
+                Box::new(PerEye::from_nodes(create()?, create()?))
+            } else {
+                create()?
+            };
+            nodes.push(node);
+        }
+        for (index, (node, entry)) in nodes.into_iter().zip(self.nodes.iter()).enumerate() {
+            let id = entry.id.as_ref().unwrap_or(&entry.name);
+            // Validated above, hence all inputs are found.
+            window
+                .add_linked_node(node, entry.values.clone(), Some(id), &entry.inputs, flow_index)
+                .map_err(|error| Self::flow_error(index, &entry.name, error))?;
+        }
+        Ok(())
+    }
+
+    fn flow_error(index: usize, name: &str, error: FlowError) -> PipelineError {
+        let name = name.to_string();
+        match error {
+            FlowError::Empty => PipelineError::Format(format!("node \"{}\" at position {} has no input", name, index)),
+            FlowError::Node { error, .. } => PipelineError::Node { index, name, error },
+            FlowError::UnknownInput { id, .. } => PipelineError::UnknownInput { index, name, input: id },
+        }
+    }
+}
+
+impl Default for Pipeline {
+    /// The classic simulation, i.e., cataract, lens, retina, color blindness, variance and display.
+    fn default() -> Self {
+        let mut nodes: Vec<PipelineNode> = ["cataract", "lens", "retina", "peacock", "variance", "display"]
+            .iter()
+            .map(|name| PipelineNode::new(name))
+            .collect();
+        // The display reads the unmodified input as original image.
+        nodes[5].inputs = vec!["variance".to_string(), FLOW_INPUT.to_string()];
+        Pipeline { nodes }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::*;
+use std::fmt;
+use std::time::Duration;
+
+/// Time spent in a single node of a [Flow], summed over all profiled frames.
+#[derive(Debug, Clone, Default)]
+pub struct NodeTiming {
+    /// Identifier of the node, or its position if it has none.
+    pub name: String,
+    /// Number of frames in which the node was rendered.
+    pub frames: u32,
+    /// GPU time for rendering.
+    pub gpu: Duration,
+    /// CPU time for rendering, including submitting commands to the GPU.
+    pub render: Duration,
+    /// CPU time in [Node::update_values], e.g., for generating retina maps.
+    pub update: Duration,
+}
+
+impl NodeTiming {
+    fn average(total: Duration, frames: u32) -> f64 {
+        if frames == 0 {
+            0.0
+        } else {
+            total.as_secs_f64() * 1000.0 / frames as f64
+        }
+    }
+
+    /// Header of [NodeTiming::to_csv].
+    pub fn csv_header() -> &'static str {
+        "flow,node,frames,gpu_ms,render_ms,update_total_ms"
+    }
+
+    /// A CSV line with average times per frame (and total update time).
+    pub fn to_csv(&self, flow_index: usize) -> String {
+        format!(
+            "{},{},{},{:.4},{:.4},{:.4}",
+            flow_index,
+            self.name,
+            self.frames,
+            Self::average(self.gpu, self.frames),
+            Self::average(self.render, self.frames),
+            self.update.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+impl fmt::Display for NodeTiming {
+    /// Formats the timing as table row, with average times per frame (and total update time).
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<16} {:>8} {:>12.3} {:>12.3} {:>17.3}",
+            self.name,
+            self.frames,
+            Self::average(self.gpu, self.frames),
+            Self::average(self.render, self.frames),
+            self.update.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+/// Measures the time each node of a [Flow] takes, using GPU timer queries (`GL_TIME_ELAPSED`).
+///
+/// Commands are flushed after every node and results are waited for at the end of each frame,
+/// which slows down rendering a bit.
+pub struct Profiler {
+    /// One query per node.
+    queries: Vec<u32>,
+    /// Whether the query of a node was issued in the current frame.
+    issued: Vec<bool>,
+    timings: Vec<NodeTiming>,
+    /// For deleting the queries when dropped.
+    gl: gfx_gl::Gl,
+}
+
+impl Profiler {
+    pub fn new(window: &Window, names: Vec<String>) -> Self {
+        let mut queries = vec![0; names.len()];
+        let mut handle = None;
+        unsafe {
+            window.device().borrow_mut().with_gl(|gl| {
+                gl.GenQueries(queries.len() as i32, queries.as_mut_ptr());
+                handle = Some(gl.clone());
+            });
+        }
+        Profiler {
+            issued: vec![false; names.len()],
+            queries,
+            timings: names
+                .into_iter()
+                .map(|name| NodeTiming {
+                    name,
+                    ..NodeTiming::default()
+                })
+                .collect(),
+            gl: handle.expect("with_gl calls the closure"),
+        }
+    }
+
+    /// Continues the given timings of nodes with the same names, e.g., after nodes were added.
+    pub fn resume(&mut self, timings: &[NodeTiming]) {
+        for timing in self.timings.iter_mut() {
+            if let Some(previous) = timings.iter().find(|previous| previous.name == timing.name) {
+                *timing = previous.clone();
+            }
+        }
+    }
+
+    /// Number of nodes that are timed.
+    pub fn nodes_len(&self) -> usize {
+        self.queries.len()
+    }
+
+    pub fn begin_node(&mut self, window: &Window, index: usize) {
+        let query = self.queries[index];
+        unsafe {
+            window.device().borrow_mut().with_gl(|gl| gl.BeginQuery(gfx_gl::TIME_ELAPSED, query));
+        }
+    }
+
+    /// Stops timing the node, after its commands were flushed.
+    pub fn end_node(&mut self, window: &Window, index: usize, render: Duration) {
+        unsafe {
+            window.device().borrow_mut().with_gl(|gl| gl.EndQuery(gfx_gl::TIME_ELAPSED));
+        }
+        self.issued[index] = true;
+        self.timings[index].frames += 1;
+        self.timings[index].render += render;
+    }
+
+    /// Waits for the results of all nodes rendered in this frame.
+    pub fn end_frame(&mut self, window: &Window) {
+        let mut elapsed = vec![0u64; self.queries.len()];
+        unsafe {
+            window.device().borrow_mut().with_gl(|gl| {
+                for (index, query) in self.queries.iter().enumerate() {
+                    if self.issued[index] {
+                        gl.GetQueryObjectui64v(*query, gfx_gl::QUERY_RESULT, &mut elapsed[index]);
+                    }
+                }
+            });
+        }
+        for (index, elapsed) in elapsed.into_iter().enumerate() {
+            if self.issued[index] {
+                self.timings[index].gpu += Duration::from_nanos(elapsed);
+                self.issued[index] = false;
+            }
+        }
+    }
+
+    pub fn timings(&self) -> &[NodeTiming] {
+        &self.timings
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteQueries(self.queries.len() as i32, self.queries.as_ptr());
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use crate::*;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// Reasons why a [Simulator] cannot be set up or cannot simulate an image.
+#[derive(Debug)]
+pub enum SimulatorError {
+    /// No OpenGL context could be created.
+    Window(String),
+    /// The input or output node could not be created.
+    Node(NodeError),
+    /// The pipeline could not be built.
+    Pipeline(PipelineError),
+    /// The nodes could not be set up for the input.
+    Flow(FlowError),
+    /// The input is empty or its size does not match its pixels.
+    InvalidInput { width: u32, height: u32, len: usize },
+    /// Rendering did not produce an image.
+    NoOutput,
+}
+
+impl fmt::Display for SimulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimulatorError::Window(err) => write!(f, "cannot create window: {}", err),
+            SimulatorError::Node(err) => write!(f, "cannot create node: {}", err),
+            SimulatorError::Pipeline(err) => write!(f, "{}", err),
+            SimulatorError::Flow(err) => write!(f, "cannot set up flow: {}", err),
+            SimulatorError::InvalidInput { width, height, len } => write!(
+                f,
+                "expected {}x{} RGBA pixels ({} bytes), found {} bytes",
+                width,
+                height,
+                width * height * 4,
+                len
+            ),
+            SimulatorError::NoOutput => write!(f, "no image was rendered"),
+        }
+    }
+}
+
+impl std::error::Error for SimulatorError {}
+
+impl From<NodeError> for SimulatorError {
+    fn from(err: NodeError) -> Self {
+        SimulatorError::Node(err)
+    }
+}
+
+impl From<PipelineError> for SimulatorError {
+    fn from(err: PipelineError) -> Self {
+        SimulatorError::Pipeline(err)
+    }
+}
+
+impl From<FlowError> for SimulatorError {
+    fn from(err: FlowError) -> Self {
+        SimulatorError::Flow(err)
+    }
+}
+
+/// Lets the simulator access a node after it was added to the flow.
+struct SharedNode<N: Node>(Rc<RefCell<N>>);
+
+impl<N: Node> Node for SharedNode<N> {
+    fn new(window: &Window) -> Result<Self, NodeError> {
+        Ok(SharedNode(Rc::new(RefCell::new(N::new(window)?))))
+    }
+
+    fn negociate_slots(&mut self, window: &Window, slots: NodeSlots) -> Result<NodeSlots, NodeError> {
+        self.0.borrow_mut().negociate_slots(window, slots)
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        self.0.borrow().parameters()
+    }
+
+    fn input_channels(&self) -> Vec<ChannelInfo> {
+        self.0.borrow().input_channels()
+    }
+
+    fn output_channels(&self) -> Vec<ChannelInfo> {
+        self.0.borrow().output_channels()
+    }
+
+    fn update_values(&mut self, window: &Window, values: &ValueMap) {
+        self.0.borrow_mut().update_values(window, values)
+    }
+
+    fn input(&mut self, perspective: &EyePerspective, vis_param: &VisualizationParameters) -> EyePerspective {
+        self.0.borrow_mut().input(perspective, vis_param)
+    }
+
+    fn render(&mut self, window: &Window) {
+        self.0.borrow_mut().render(window)
+    }
+}
+
+/// Simulates images synchronously, e.g., for embedding the simulation into other tools.
+///
+/// Renders off-screen (see [Window::is_headless]) and keeps its GPU resources between calls,
+/// i.e., simulating many images of the same size only uploads and downloads pixels.
+/// Images are RGBA with the top row first, both for input and output.
+pub struct Simulator {
+    window: Window,
+    upload: Rc<RefCell<UploadRgbBuffer>>,
+    download: Rc<RefCell<DownloadRgbBuffer>>,
+    input: RgbBuffer,
+    size: Option<(u32, u32)>,
+    values: Option<ValueMap>,
+}
+
+impl Simulator {
+    /// Creates a simulator for the default pipeline and nodes.
+    pub fn new() -> Result<Self, SimulatorError> {
+        Self::with_pipeline(&Pipeline::default(), &NodeRegistry::default())
+    }
+
+    /// Creates a simulator for the given pipeline, with nodes from the given registry.
+    pub fn with_pipeline(pipeline: &Pipeline, registry: &NodeRegistry) -> Result<Self, SimulatorError> {
+        let mut window = Window::new(false, None, vec![RefCell::new(ValueMap::new())], 1).map_err(SimulatorError::Window)?;
+
+        let upload = SharedNode::<UploadRgbBuffer>::new(&window)?;
+        let download = SharedNode::<DownloadRgbBuffer>::new(&window)?;
+        let (upload_shared, download_shared) = (upload.0.clone(), download.0.clone());
+
+        window.add_node(Box::new(upload), 0);
+        pipeline.build(&mut window, registry, 0)?;
+        window.add_node(Box::new(download), 0);
+
+        Ok(Simulator {
+            window,
+            upload: upload_shared,
+            download: download_shared,
+            input: RgbBuffer::default(),
+            size: None,
+            values: None,
+        })
+    }
+
+    /// Sets how inputs are interpreted, e.g., as color with depth ([RgbInputFlags::RGBD_HORIZONTAL]).
+    pub fn set_flags(&mut self, flags: RgbInputFlags) {
+        self.upload.borrow_mut().set_flags(flags);
+        // Flags affect the output size.
+        self.size = None;
+    }
+
+    /// Describes the values read by the pipeline.
+    pub fn parameters(&self) -> Vec<Parameter> {
+        self.window.parameters(0)
+    }
+
+    /// Simulates a single image with the given values.
+    pub fn simulate(&mut self, input: &RgbBuffer, values: &ValueMap) -> Result<RgbBuffer, SimulatorError> {
+        let len = input.pixels_rgb.len();
+        if input.width == 0 || input.height == 0 || len != (input.width * input.height * 4) as usize {
+            return Err(SimulatorError::InvalidInput {
+                width: input.width,
+                height: input.height,
+                len,
+            });
+        }
+
+        // Textures have the bottom row first.
+        self.flip_input(input);
+        self.upload.borrow_mut().upload_buffer(&self.input);
+
+        // Negociate again only if the size changed, otherwise just update values.
+        let size = (input.width, input.height);
+        if self.size != Some(size) {
+            self.size = None;
+            self.window.update_nodes()?;
+            self.size = Some(size);
+        }
+        if self.values.as_ref() != Some(values) {
+            let mut flow_values = values.clone();
+            flow_values.entry("flow_id".into()).or_insert(Value::Number(0.0));
+            self.window.set_values(flow_values, 0);
+            self.values = Some(values.clone());
+        }
+
+        // Render a single frame and wait for its download.
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.download.borrow_mut().set_buffer_cb(Some(Box::new(move |rgb_buffer| {
+            let _ = tx.send(rgb_buffer);
+        })));
+        self.window.poll_events();
+        // Drops the callback (and thus the sender) if nothing was downloaded.
+        self.download.borrow_mut().set_buffer_cb(None);
+        let output = rx.recv().map_err(|_| SimulatorError::NoOutput)?;
+
+        let mut pixels = Vec::with_capacity(output.pixels_rgb.len() / 3 * 4);
+        for pixel in output.pixels_rgb.chunks(3) {
+            pixels.extend_from_slice(pixel);
+            pixels.push(255);
+        }
+        Ok(RgbBuffer {
+            pixels_rgb: pixels.into_boxed_slice(),
+            width: output.width,
+            height: output.height,
+        })
+    }
+
+    /// Simulates several images with the same values.
+    pub fn simulate_batch(&mut self, inputs: &[RgbBuffer], values: &ValueMap) -> Result<Vec<RgbBuffer>, SimulatorError> {
+        inputs.iter().map(|input| self.simulate(input, values)).collect()
+    }
+
+    fn flip_input(&mut self, input: &RgbBuffer) {
+        let row_len = (input.width * 4) as usize;
+        if self.input.pixels_rgb.len() != input.pixels_rgb.len() {
+            self.input.pixels_rgb = vec![0; input.pixels_rgb.len()].into_boxed_slice();
+        }
+        self.input.width = input.width;
+        self.input.height = input.height;
+        for (dst, src) in self
+            .input
+            .pixels_rgb
+            .chunks_mut(row_len)
+            .zip(input.pixels_rgb.chunks(row_len).rev())
+        {
+            dst.copy_from_slice(src);
+        }
+    }
+}
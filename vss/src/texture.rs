@@ -136,17 +136,22 @@ pub fn load_cubemap(
 > {
     let mut raw_data: [Vec<u8>; 6] = Default::default();
     let mut last_width = 0;
+    if data.len() != 6 {
+        return Err(format!("cubemaps need 6 sides, found {}", data.len()));
+    }
     for i in 0..6 {
         let img = image::load(data.remove(0), image::ImageFormat::Png)
-            .unwrap()
+            .map_err(|e| format!("cubemap side {}: {}", i, e))?
             .flipv()
             .to_rgba8();
         let (width, height) = img.dimensions();
         let raw = img.into_raw();
         raw_data[i] = raw;
-        assert!(width == height, "width must be equal to height in cubemaps");
-        if i > 0 {
-            assert!(width == last_width, "sizes of all cubemap sides must be equal");
+        if width != height {
+            return Err(format!("cubemap side {}: width must be equal to height ({}x{})", i, width, height));
+        }
+        if i > 0 && width != last_width {
+            return Err(format!("cubemap side {}: sizes of all sides must be equal ({} vs. {})", i, width, last_width));
         }
         last_width = width;
     }
@@ -263,6 +268,47 @@ pub fn load_highp_texture_from_bytes(
     Ok((tex, view))
 }
 
+///
+/// Creates a float texture from values, e.g., to pass lists of parameters to shaders.
+///
+/// The values are stored row by row. Float textures are not filterable everywhere (e.g., OpenGL ES
+/// without OES_texture_float_linear), so bind them with [create_sampler_nearest], even if they are
+/// only read with `texelFetch`.
+///
+pub fn load_float_texture(
+    factory: &mut gfx_device_gl::Factory,
+    values: &[[f32; 4]],
+    width: u32,
+    height: u32,
+) -> Result<
+    (
+        gfx::handle::Texture<Resources, gfx::format::R32_G32_B32_A32>,
+        gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+    ),
+    String,
+> {
+    use gfx::texture;
+
+    if values.len() != (width * height) as usize {
+        return Err(format!("float texture needs {}x{} values, found {}", width, height, values.len()));
+    }
+    let texels: Vec<[u32; 4]> = values
+        .iter()
+        .map(|value| [value[0].to_bits(), value[1].to_bits(), value[2].to_bits(), value[3].to_bits()])
+        .collect();
+    let kind = texture::Kind::D2(width as texture::Size, height as texture::Size, texture::AaMode::Single);
+    factory
+        .create_texture_immutable::<gfx::format::Rgba32F>(kind, texture::Mipmap::Provided, &[&texels])
+        .map_err(|e| format!("float texture: {}", e))
+}
+
+/// Creates a sampler without filtering, e.g., for float textures.
+pub fn create_sampler_nearest(factory: &mut gfx_device_gl::Factory) -> gfx::handle::Sampler<Resources> {
+    use gfx::texture;
+
+    factory.create_sampler(texture::SamplerInfo::new(texture::FilterMethod::Scale, texture::WrapMode::Clamp))
+}
+
 pub fn update_single_channel_texture(
     encoder: &mut gfx::Encoder<Resources, CommandBuffer>,
     texture: &gfx::handle::Texture<Resources, gfx::format::R8>,
@@ -415,10 +461,13 @@ pub fn create_texture_render_target<T>(
     factory: &mut gfx_device_gl::Factory,
     width: u32,
     height: u32,
-) -> (
-    gfx::handle::RenderTargetView<gfx_device_gl::Resources, T>,
-    gfx::handle::ShaderResourceView<gfx_device_gl::Resources, <T as gfx::format::Formatted>::View>,
-)
+) -> Result<
+    (
+        gfx::handle::RenderTargetView<gfx_device_gl::Resources, T>,
+        gfx::handle::ShaderResourceView<gfx_device_gl::Resources, <T as gfx::format::Formatted>::View>,
+    ),
+    gfx::CombinedError,
+>
 where
     T: gfx::format::TextureFormat + gfx::format::RenderFormat,
 {
@@ -433,15 +482,12 @@ where
             gfx::memory::Bind::RENDER_TARGET | gfx::memory::Bind::SHADER_RESOURCE | gfx::memory::Bind::TRANSFER_SRC,
             gfx::memory::Usage::Dynamic,
             Some( <<T as gfx::format::Formatted>::Channel as gfx::format::ChannelTyped>::get_channel_type() ),
-        )
-        .unwrap();
+        )?;
     let target_view = factory
-        .view_texture_as_shader_resource::<T>(&texture, (0, 0), gfx::format::Swizzle::new())
-        .unwrap();
+        .view_texture_as_shader_resource::<T>(&texture, (0, 0), gfx::format::Swizzle::new())?;
     let target = factory
-        .view_texture_as_render_target::<T>(&texture, 0, None)
-        .unwrap();
-    (target, target_view)
+        .view_texture_as_render_target::<T>(&texture, 0, None)?;
+    Ok((target, target_view))
 }
 
 pub fn texture_from_id_and_size<T>(
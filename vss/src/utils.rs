@@ -1,34 +1,76 @@
+use std::io;
 use std::io::Cursor;
 use std::path::Path;
 
 #[cfg(not(target_os = "android"))]
 pub fn load<P: AsRef<Path>>(path: P) -> Cursor<Vec<u8>> {
+    match try_load(&path) {
+        Ok(data) => data,
+        Err(err) => {
+            panic!("Can`t open file '{}' ({})", path.as_ref().display(), err);
+        }
+    }
+}
+
+/// Like [load], but returns an error instead of panicking, e.g., for files named in configurations.
+#[cfg(not(target_os = "android"))]
+pub fn try_load<P: AsRef<Path>>(path: P) -> io::Result<Cursor<Vec<u8>>> {
     use std::fs::File;
     use std::io::Read;
 
     let mut buf = Vec::new();
     let full_path = &Path::new("").join(&path);
-    let mut file = match File::open(&full_path) {
-        Ok(file) => file,
-        Err(err) => {
-            panic!("Can`t open file '{}' ({})", full_path.display(), err);
-        }
-    };
-    match file.read_to_end(&mut buf) {
-        Ok(_) => Cursor::new(buf),
-        Err(err) => {
-            panic!("Can`t read file '{}' ({})", full_path.display(), err);
-        }
-    }
+    File::open(&full_path)?.read_to_end(&mut buf)?;
+    Ok(Cursor::new(buf))
 }
 
 #[cfg(target_os = "android")]
 pub fn load<P: AsRef<Path>>(path: P) -> Cursor<Vec<u8>> {
+    match try_load(&path) {
+        Ok(data) => data,
+        Err(_) => panic!("Can`t load asset '{}'", path.as_ref().display()),
+    }
+}
+
+/// Like [load], but returns an error instead of panicking, e.g., for files named in configurations.
+#[cfg(target_os = "android")]
+pub fn try_load<P: AsRef<Path>>(path: P) -> io::Result<Cursor<Vec<u8>>> {
     use android_glue;
 
     let filename = path.as_ref().to_str().expect("Can`t convert Path to &str");
     match android_glue::load_asset(filename) {
-        Ok(buf) => Cursor::new(buf),
-        Err(_) => panic!("Can`t load asset '{}'", filename),
+        Ok(buf) => Ok(Cursor::new(buf)),
+        Err(_) => Err(io::Error::new(io::ErrorKind::NotFound, "asset not found")),
+    }
+}
+
+/// A small random number generator (SplitMix64), such that procedural effects can be reproduced
+/// from a seed on every platform.
+#[derive(Debug, Clone)]
+pub struct SeededRandom {
+    state: u64,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        SeededRandom { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// A number in [min, max).
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
     }
 }
@@ -0,0 +1,132 @@
+use crate::*;
+use std::fmt;
+
+/// A problem with a single configuration value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigProblem {
+    /// The value has a different type than expected (with a description of the found value).
+    WrongType { expected: ParameterType, found: String },
+    /// The number is outside of the parameter's range.
+    OutOfRange { min: f64, max: f64, found: f64 },
+    /// The key is not known, likely a typo (with the most similar known key).
+    UnknownKey { suggestion: Option<String> },
+}
+
+/// A problem found by [validate_values].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub key: String,
+    pub problem: ConfigProblem,
+    /// Where the value came from, e.g., a file name.
+    pub source: Option<String>,
+}
+
+impl ConfigIssue {
+    /// Unknown keys are ignored by the simulation, all other problems are errors.
+    pub fn is_error(&self) -> bool {
+        match self.problem {
+            ConfigProblem::UnknownKey { .. } => false,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(source) = &self.source {
+            write!(f, "{}: ", source)?;
+        }
+        write!(f, "\"{}\": ", self.key)?;
+        match &self.problem {
+            ConfigProblem::WrongType { expected, found } => {
+                write!(f, "expected {:?}, found {}", expected, found)
+            }
+            ConfigProblem::OutOfRange { min, max, found } => {
+                write!(f, "expected a value in [{}, {}], found {}", min, max, found)
+            }
+            ConfigProblem::UnknownKey { suggestion: Some(suggestion) } => {
+                write!(f, "unknown key, did you mean \"{}\"?", suggestion)
+            }
+            ConfigProblem::UnknownKey { suggestion: None } => write!(f, "unknown key"),
+        }
+    }
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Bool(b) => format!("bool {}", b),
+        Value::Number(n) => format!("number {}", n),
+        Value::String(s) => format!("string \"{}\"", s),
+        Value::Array(_) => "array".to_string(),
+        Value::Object(_) => "object".to_string(),
+        Value::Matrix(_) => "matrix".to_string(),
+    }
+}
+
+fn check(parameter: &Parameter, value: &Value) -> Option<ConfigProblem> {
+    let wrong_type = || ConfigProblem::WrongType {
+        expected: parameter.kind,
+        found: describe(value),
+    };
+    match (parameter.kind, value) {
+        (ParameterType::Bool, Value::Bool(_)) => None,
+        (ParameterType::Path, Value::String(_)) => None,
+        (ParameterType::Matrix, Value::Matrix(_)) => None,
+        (ParameterType::List, Value::Array(items)) if items.iter().all(|item| item.as_str().is_some()) => None,
+        (ParameterType::Integer, Value::Number(n)) if n.fract() != 0.0 => Some(wrong_type()),
+        (ParameterType::Number, Value::Number(n)) | (ParameterType::Integer, Value::Number(n)) => match parameter.range {
+            Some((min, max)) if *n < min || *n > max => Some(ConfigProblem::OutOfRange { min, max, found: *n }),
+            _ => None,
+        },
+        _ => Some(wrong_type()),
+    }
+}
+
+/// Edit distance between two keys, for typo suggestions.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + if ca == *cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn suggest(key: &str, parameters: &[Parameter]) -> Option<String> {
+    parameters
+        .iter()
+        .map(|parameter| (levenshtein(key, parameter.name), parameter.name))
+        .filter(|(distance, name)| *distance <= 3.max(name.len() / 4))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name.to_string())
+}
+
+/// Checks values against parameter descriptions and reports all problems (sorted by key).
+///
+/// This does not need a window, i.e., it can run before any GL resources are created.
+pub fn validate_values(values: &ValueMap, parameters: &[Parameter], source: Option<&str>) -> Vec<ConfigIssue> {
+    let mut issues: Vec<ConfigIssue> = values
+        .iter()
+        .filter_map(|(key, value)| {
+            let problem = match parameters.iter().find(|parameter| parameter.name == key) {
+                Some(parameter) => check(parameter, value)?,
+                None => ConfigProblem::UnknownKey {
+                    suggestion: suggest(key, parameters),
+                },
+            };
+            Some(ConfigIssue {
+                key: key.clone(),
+                problem,
+                source: source.map(|source| source.to_string()),
+            })
+        })
+        .collect();
+    issues.sort_by(|a, b| a.key.cmp(&b.key));
+    issues
+}
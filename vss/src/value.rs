@@ -1,15 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use cgmath::Matrix4;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Serialize, SerializeMap, Serializer};
 
-//TODO: replace all this stuff by making "trait Node: serde::Serialize + serde::Deserialize"
-// try a mix of #[serde(flatten)] and #[serde(skip)]
-
-#[derive(Debug)]
-#[derive(Clone)] //remove later
+/// A parameter value, e.g., as read from a JSON configuration.
+///
+/// Matrices are (de)serialized as `{"matrix": [[..], [..], [..], [..]]}` (column-major),
+/// such that they survive a round trip through JSON.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Bool(bool),
     Number(f64),
-    Image(String),
+    /// A string, e.g., the path of an image.
+    String(String),
+    Array(Vec<Value>),
+    Object(ValueMap),
     Matrix(Matrix4<f32>),
 }
 
@@ -28,13 +34,27 @@ impl Value {
         }
     }
 
-    pub fn as_img(&self) -> Option<&str> {
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
         match *self {
-            Value::Image(ref s) => Some(s),
+            Value::Array(ref a) => Some(a),
             _ => None,
         }
     }
-    
+
+    pub fn as_object(&self) -> Option<&ValueMap> {
+        match *self {
+            Value::Object(ref o) => Some(o),
+            _ => None,
+        }
+    }
+
     pub fn as_matrix(&self) -> Option<&Matrix4<f32>> {
         match *self {
             Value::Matrix(ref m) => Some(m),
@@ -44,3 +64,142 @@ impl Value {
 }
 
 pub type ValueMap = HashMap<String, Value>;
+
+const MATRIX_KEY: &str = "matrix";
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            // JSON has no infinity and NaN, they would be written as null and dropped when loaded.
+            Value::Number(n) if !n.is_finite() => Err(ser::Error::custom(format!("cannot write {} as JSON", n))),
+            Value::Number(n) => serializer.serialize_f64(*n),
+            Value::String(s) => serializer.serialize_str(s),
+            Value::Array(a) => a.serialize(serializer),
+            // Sorted, for stable output.
+            Value::Object(o) => o.iter().collect::<BTreeMap<_, _>>().serialize(serializer),
+            Value::Matrix(m) => {
+                let columns: [[f32; 4]; 4] = (*m).into();
+                if columns.iter().flatten().any(|entry| !entry.is_finite()) {
+                    return Err(ser::Error::custom("cannot write matrices with infinite or NaN entries as JSON"));
+                }
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(MATRIX_KEY, &columns)?;
+                map.end()
+            }
+        }
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a boolean, number, string, array or object")
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<Value, E> {
+        Ok(Value::Bool(b))
+    }
+
+    fn visit_i64<E: de::Error>(self, n: i64) -> Result<Value, E> {
+        Ok(Value::Number(n as f64))
+    }
+
+    fn visit_u64<E: de::Error>(self, n: u64) -> Result<Value, E> {
+        Ok(Value::Number(n as f64))
+    }
+
+    fn visit_f64<E: de::Error>(self, n: f64) -> Result<Value, E> {
+        Ok(Value::Number(n))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Value, E> {
+        Ok(Value::String(s.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, s: String) -> Result<Value, E> {
+        Ok(Value::String(s))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut array = Vec::new();
+        // Nulls are dropped, as everywhere else.
+        while let Some(element) = seq.next_element::<Option<Value>>()? {
+            if let Some(element) = element {
+                array.push(element);
+            }
+        }
+        Ok(Value::Array(array))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Value, A::Error> {
+        let mut map = ValueMap::new();
+        while let Some((key, value)) = access.next_entry::<String, Option<Value>>()? {
+            if let Some(value) = value {
+                map.insert(key, value);
+            }
+        }
+        if map.len() == 1 {
+            if let Some(matrix) = map.get(MATRIX_KEY).and_then(matrix_from_columns) {
+                return Ok(Value::Matrix(matrix));
+            }
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+/// Interprets an array of four columns with four numbers each as matrix.
+fn matrix_from_columns(value: &Value) -> Option<Matrix4<f32>> {
+    let columns = value.as_array()?;
+    if columns.len() != 4 {
+        return None;
+    }
+    let mut matrix = [[0.0f32; 4]; 4];
+    for (column, values) in matrix.iter_mut().zip(columns.iter()) {
+        let values = values.as_array()?;
+        if values.len() != 4 {
+            return None;
+        }
+        for (entry, value) in column.iter_mut().zip(values.iter()) {
+            *entry = value.as_f64()? as f32;
+        }
+    }
+    Some(matrix.into())
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// Converts a JSON object into a [ValueMap], skipping null values.
+pub fn value_map_from_json(object: &serde_json::Map<String, serde_json::Value>) -> serde_json::Result<ValueMap> {
+    let mut map = ValueMap::new();
+    for (key, value) in object.iter() {
+        if let Some(value) = Option::<Value>::deserialize(value)? {
+            map.insert(key.to_string(), value);
+        }
+    }
+    Ok(map)
+}
+
+/// Parses a JSON object into a [ValueMap], skipping null values.
+pub fn parse_value_map(json: &str) -> serde_json::Result<ValueMap> {
+    let map: HashMap<String, Option<Value>> = serde_json::from_str(json)?;
+    Ok(map
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|value| (key, value)))
+        .collect())
+}
+
+/// Writes a [ValueMap] as pretty-printed JSON object with sorted keys, see [parse_value_map].
+///
+/// Fails for infinite and NaN numbers, which JSON cannot represent.
+pub fn value_map_to_json(values: &ValueMap) -> serde_json::Result<String> {
+    let sorted: BTreeMap<_, _> = values.iter().collect();
+    serde_json::to_string_pretty(&sorted)
+}
@@ -15,10 +15,45 @@ pub type RenderTargetColor = gfx::handle::RenderTargetView<gfx_device_gl::Resour
 pub type RenderTargetDepthFormat = (gfx::format::D24_S8, gfx::format::Unorm);
 pub type RenderTargetDepth = gfx::handle::DepthStencilView<gfx_device_gl::Resources, RenderTargetDepthFormat>;
 
+/// The OpenGL context of a [Window].
+enum GlContext {
+    /// Attached to a (possibly hidden) window, which requires a display.
+    Windowed {
+        context: glutin::WindowedContext<glutin::PossiblyCurrent>,
+        events_loop: RefCell<glutin::EventsLoop>,
+    },
+    /// Rendering off-screen, e.g., for batch processing on servers without a display.
+    Headless {
+        #[allow(dead_code)] // Keeps the context alive.
+        context: glutin::Context<glutin::PossiblyCurrent>,
+        size: glutin::dpi::LogicalSize,
+    },
+}
+
+/// Initial size of windows and size of off-screen targets.
+const DEFAULT_SIZE: (f64, f64) = (1280.0, 720.0);
+
+/// Keys that toggle bypassing nodes, see [Flow::bypassable].
+const BYPASS_KEYS: [glutin::VirtualKeyCode; 12] = [
+    glutin::VirtualKeyCode::F1,
+    glutin::VirtualKeyCode::F2,
+    glutin::VirtualKeyCode::F3,
+    glutin::VirtualKeyCode::F4,
+    glutin::VirtualKeyCode::F5,
+    glutin::VirtualKeyCode::F6,
+    glutin::VirtualKeyCode::F7,
+    glutin::VirtualKeyCode::F8,
+    glutin::VirtualKeyCode::F9,
+    glutin::VirtualKeyCode::F10,
+    glutin::VirtualKeyCode::F11,
+    glutin::VirtualKeyCode::F12,
+];
+
 /// Represents a window along with its associated rendering context and [Flow].
+///
+/// Invisible windows use a headless context if possible, such that no display is needed.
 pub struct Window {
-    windowed_context: glutin::WindowedContext<glutin::PossiblyCurrent>,
-    events_loop: RefCell<glutin::EventsLoop>,
+    context: GlContext,
     device: RefCell<gfx_device_gl::Device>,
     factory: RefCell<DeviceFactory>,
     encoder: RefCell<DeviceEncoder>,
@@ -41,29 +76,34 @@ pub struct Window {
 }
 
 impl Window {
-    pub fn new(visible: bool, remote: Option<Remote>, values: Vec<RefCell<ValueMap>>, flow_count: usize) -> Self {
-        // Create a window and context.
-        let gl_version = glutin::GlRequest::GlThenGles {
-            opengles_version: (3, 2),
-            opengl_version: (4, 1),
+    /// Creates a window, or an off-screen context if it is not `visible` (falling back to a hidden window).
+    ///
+    /// Fails if no OpenGL context can be created, e.g., without OSMesa and without display.
+    pub fn new(
+        visible: bool,
+        remote: Option<Remote>,
+        values: Vec<RefCell<ValueMap>>,
+        flow_count: usize,
+    ) -> Result<Self, String> {
+        // Create a context, without window if possible.
+        let headless = if visible {
+            None
+        } else {
+            match Self::create_headless_context() {
+                Ok(headless) => Some(Ok(headless)),
+                Err(err) => {
+                    eprintln!("Cannot create headless context ({}), using a hidden window", err);
+                    Some(Err(err))
+                }
+            }
+        };
+        let (context, mut device, mut factory, render_target, main_depth) = match headless {
+            Some(Ok(headless)) => headless,
+            Some(Err(headless_err)) => Self::create_windowed_context(visible).map_err(|err| {
+                format!("cannot create a headless context ({}) nor a hidden window ({})", headless_err, err)
+            })?,
+            None => Self::create_windowed_context(visible).map_err(|err| format!("cannot create a window ({})", err))?,
         };
-        let events_loop = glutin::EventsLoop::new();
-        let window_builder = glutin::WindowBuilder::new()
-            .with_title("Visual System Simulator")
-            .with_min_dimensions(glutin::dpi::LogicalSize::new(640.0, 360.0))
-            .with_dimensions(glutin::dpi::LogicalSize::new(1280.0, 720.0))
-            .with_visibility(visible);
-        let context_builder = glutin::ContextBuilder::new()
-            .with_vsync(true)
-            .with_gl(gl_version);
-        let (windowed_context, mut device, mut factory, render_target, main_depth) =
-            gfx_window_glutin::init::<
-                (gfx::format::R8_G8_B8_A8, gfx::format::Unorm),
-                gfx::format::DepthStencil,
-            >(window_builder, context_builder, &events_loop)
-            .unwrap();
-
-        windowed_context.window().hide_cursor(true);
 
         // Create a command buffer.
         let encoder: DeviceEncoder = factory.create_command_buffer().into();
@@ -83,7 +123,10 @@ impl Window {
                 0 => BaseImage::Output,
                 1 => BaseImage::Original,
                 2 => BaseImage::Ganglion,
-                _ => panic!("No BaseImage of {} found", file_base_image)
+                _ => {
+                    eprintln!("No BaseImage of {} found, using default", file_base_image);
+                    BaseImage::default()
+                }
             };
         }
         if let Some(Value::Number(file_mix_type)) = values[0].borrow().get("file_mix_type") {
@@ -91,7 +134,10 @@ impl Window {
                 0 => MixType::BaseImageOnly,
                 1 => MixType::ColorMapOnly,
                 2 => MixType::OverlayThreshold,
-                _ => panic!("No MixType of {} found", file_mix_type)
+                _ => {
+                    eprintln!("No MixType of {} found, using default", file_mix_type);
+                    MixType::default()
+                }
             };
         }
         if let Some(Value::Number(file_color_map_type)) = values[0].borrow().get("file_cm_type") {
@@ -99,7 +145,10 @@ impl Window {
                 0 => ColorMapType::Viridis,
                 1 => ColorMapType::Turbo,
                 2 => ColorMapType::Grayscale,
-                _ => panic!("No ColorMapType of {} found", file_color_map_type)
+                _ => {
+                    eprintln!("No ColorMapType of {} found, using default", file_color_map_type);
+                    ColorMapType::default()
+                }
             };
         }
         if let Some(Value::Number(combination_function)) = values[0].borrow().get("file_cf") {
@@ -111,7 +160,10 @@ impl Window {
                 4 => CombinationFunction::UncertaintyXYVectorLength,
                 5 => CombinationFunction::UncertaintyRGBXYVectorLength,
                 6 => CombinationFunction::UncertaintyGenVar,
-                _ => panic!("No CombinationFunction of {} found", combination_function)
+                _ => {
+                    eprintln!("No CombinationFunction of {} found, using default", combination_function);
+                    CombinationFunction::default()
+                }
             };
         }
         if let Some(Value::Number(cm_scale)) = values[0].borrow().get("cm_scale") {
@@ -138,11 +190,10 @@ impl Window {
 
         let vis_param = RefCell::new(vis_param);
 
-        Window {
+        Ok(Window {
             flow,
             remote,
-            windowed_context,
-            events_loop: RefCell::new(events_loop),
+            context,
             device: RefCell::new(device),
             factory: RefCell::new(factory),
             encoder: RefCell::new(encoder),
@@ -157,6 +208,96 @@ impl Window {
             vis_param,
             last_render_instant: RefCell::new(Instant::now()),
             forced_view,
+        })
+    }
+
+    fn gl_request() -> glutin::GlRequest {
+        glutin::GlRequest::GlThenGles {
+            opengles_version: (3, 2),
+            opengl_version: (4, 1),
+        }
+    }
+
+    fn create_windowed_context(
+        visible: bool,
+    ) -> Result<(GlContext, gfx_device_gl::Device, DeviceFactory, RenderTargetColor, RenderTargetDepth), String> {
+        // Creating the events loop panics without display; catch it without printing the panic.
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| {}));
+        let events_loop = std::panic::catch_unwind(glutin::EventsLoop::new);
+        std::panic::set_hook(hook);
+        let events_loop = events_loop.map_err(|_| "no display available".to_string())?;
+        let window_builder = glutin::WindowBuilder::new()
+            .with_title("Visual System Simulator")
+            .with_min_dimensions(glutin::dpi::LogicalSize::new(640.0, 360.0))
+            .with_dimensions(glutin::dpi::LogicalSize::new(DEFAULT_SIZE.0, DEFAULT_SIZE.1))
+            .with_visibility(visible);
+        let context_builder = glutin::ContextBuilder::new()
+            .with_vsync(true)
+            .with_gl(Self::gl_request());
+        let (windowed_context, device, factory, render_target, main_depth) =
+            gfx_window_glutin::init::<
+                (gfx::format::R8_G8_B8_A8, gfx::format::Unorm),
+                gfx::format::DepthStencil,
+            >(window_builder, context_builder, &events_loop)
+            .map_err(|err| format!("{:?}", err))?;
+
+        windowed_context.window().hide_cursor(true);
+
+        let context = GlContext::Windowed {
+            context: windowed_context,
+            events_loop: RefCell::new(events_loop),
+        };
+        Ok((context, device, factory, render_target, main_depth))
+    }
+
+    /// Creates an off-screen context using OSMesa (software rendering), which works without display.
+    #[cfg(target_os = "linux")]
+    fn create_headless_context(
+    ) -> Result<(GlContext, gfx_device_gl::Device, DeviceFactory, RenderTargetColor, RenderTargetDepth), String> {
+        use gfx::Factory;
+        use glutin::os::unix::HeadlessContextExt;
+
+        let size = glutin::dpi::LogicalSize::new(DEFAULT_SIZE.0, DEFAULT_SIZE.1);
+        let context = glutin::ContextBuilder::new()
+            .with_gl(Self::gl_request())
+            .build_osmesa(size.to_physical(1.0))
+            .map_err(|err| err.to_string())?;
+        let context = unsafe { context.make_current() }.map_err(|(_, err)| err.to_string())?;
+        let (device, mut factory) =
+            gfx_device_gl::create(|symbol| context.get_proc_address(symbol) as *const std::os::raw::c_void);
+
+        // Render to textures instead of a default framebuffer.
+        let (width, height) = (size.width as u16, size.height as u16);
+        let (_, _, render_target) = factory
+            .create_render_target::<ColorFormat>(width, height)
+            .map_err(|err| err.to_string())?;
+        let main_depth = factory
+            .create_depth_stencil_view_only::<RenderTargetDepthFormat>(width, height)
+            .map_err(|err| err.to_string())?;
+
+        let context = GlContext::Headless { context, size };
+        Ok((context, device, factory, render_target, main_depth))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn create_headless_context(
+    ) -> Result<(GlContext, gfx_device_gl::Device, DeviceFactory, RenderTargetColor, RenderTargetDepth), String> {
+        Err("not supported on this platform".to_string())
+    }
+
+    /// Whether this window renders off-screen, i.e., without a window and events.
+    pub fn is_headless(&self) -> bool {
+        match self.context {
+            GlContext::Headless { .. } => true,
+            GlContext::Windowed { .. } => false,
+        }
+    }
+
+    fn inner_size(&self) -> glutin::dpi::LogicalSize {
+        match &self.context {
+            GlContext::Windowed { context, .. } => context.window().get_inner_size().unwrap(),
+            GlContext::Headless { size, .. } => *size,
         }
     }
 }
@@ -166,10 +307,57 @@ impl Window {
         self.flow[flow_index].add_node(node);
     }
 
+    pub fn add_configured_node(&mut self, node: Box<dyn Node>, values: ValueMap, flow_index: usize) {
+        self.flow[flow_index].add_configured_node(node, values);
+    }
+
+    /// Adds a node that may read other nodes than its predecessor, see [Flow::add_linked_node].
+    pub fn add_linked_node(
+        &mut self,
+        node: Box<dyn Node>,
+        values: ValueMap,
+        id: Option<&str>,
+        inputs: &[String],
+        flow_index: usize,
+    ) -> Result<(), FlowError> {
+        self.flow[flow_index].add_linked_node(node, values, id, inputs)
+    }
+
     pub fn replace_node(&mut self, index: usize, node: Box<dyn Node>, flow_index: usize) {
         self.flow[flow_index].replace_node(index, node);
     }
 
+    /// Describes the values read by the window itself (visualization settings).
+    pub fn schema() -> Vec<Parameter> {
+        let view = |name, help| Parameter {
+            name,
+            kind: ParameterType::Number,
+            unit: "px",
+            range: Some((0.0, 16384.0)),
+            default: None,
+            help,
+        };
+        vec![
+            Parameter::integer("file_base_image", (0.0, 2.0), 0.0, "Base image (0: output, 1: original, 2: ganglion)"),
+            Parameter::integer("file_mix_type", (0.0, 2.0), 0.0, "Mixing of base image and color map (0: base image only, 1: color map only, 2: overlay)"),
+            Parameter::integer("file_cm_type", (0.0, 2.0), 0.0, "Color map (0: viridis, 1: turbo, 2: grayscale)"),
+            Parameter::integer("file_cf", (0.0, 6.0), 0.0, "Error or uncertainty measure shown by the color map"),
+            Parameter::number("cm_scale", "", (0.0, 1000.0), 1.0, "Scale of the color map"),
+            Parameter::integer("measure_variance", (0.0, 3.0), 0.0, "Variance type (0: off, 1: before, 2: after, 3: difference)"),
+            Parameter::integer("variance_metric", (0.0, 6.0), 0.0, "Variance metric (see --variance)"),
+            Parameter::integer("variance_color_space", (0.0, 2.0), 0.0, "Color space of variance metrics (0: RGB, 1: LAB, 2: ITP)"),
+            view("view_x", "Fixed horizontal view position (instead of the mouse cursor)"),
+            view("view_y", "Fixed vertical view position (instead of the mouse cursor)"),
+        ]
+    }
+
+    /// Describes the values read by the window and the given flow.
+    pub fn parameters(&self, flow_index: usize) -> Vec<Parameter> {
+        let mut parameters = Self::schema();
+        extend_parameters(&mut parameters, self.flow[flow_index].parameters());
+        parameters
+    }
+
     pub fn delta_t(&self)  -> f32{
         if self.vis_param.borrow().bees_flying {
             return self.last_render_instant.borrow().elapsed().as_micros() as f32;
@@ -182,15 +370,49 @@ impl Window {
         self.flow[0].nodes_len()
     }
 
-    pub fn update_last_node(&mut self) {
-        self.flow.iter().for_each(|f| f.update_last_slot(&self));
+    /// Number of nodes of the given flow.
+    pub fn flow_nodes_len(&self, flow_index: usize) -> usize {
+        self.flow[flow_index].nodes_len()
     }
 
-    pub fn update_nodes(&mut self) {
+    /// Times each node of all flows while rendering, see [Window::performance_report].
+    pub fn set_profiling(&self, enabled: bool) {
+        self.flow.iter().for_each(|f| f.set_profiling(enabled));
+    }
+
+    /// Time spent in the nodes of all flows as CSV, see [NodeTiming::to_csv].
+    pub fn performance_report(&self) -> String {
+        let mut report = format!("{}\n", NodeTiming::csv_header());
+        for (flow_index, f) in self.flow.iter().enumerate() {
+            for timing in f.timings() {
+                report.push_str(&format!("{}\n", timing.to_csv(flow_index)));
+            }
+        }
+        report
+    }
+
+    /// Time spent in the nodes of the given flow.
+    pub fn timings(&self, flow_index: usize) -> Vec<NodeTiming> {
+        self.flow[flow_index].timings()
+    }
+
+    pub fn update_last_node(&mut self) -> Result<(), FlowError> {
+        for f in self.flow.iter() {
+            f.update_last_slot(&self)?;
+        }
+        Ok(())
+    }
+
+    /// Negociates the slots of all flows and updates their values, e.g., after nodes were added.
+    ///
+    /// Misconfigured flows (e.g., nodes that require depth after nodes that drop it) are reported
+    /// here, before the first frame is rendered.
+    pub fn update_nodes(&mut self) -> Result<(), FlowError> {
         for (i, f) in self.flow.iter().enumerate(){
-            f.negociate_slots(&self);
+            f.negociate_slots(&self)?;
             f.update_values(&self, &self.values[i].borrow());
         }
+        Ok(())
     }
 
     pub fn set_values(&self, values: ValueMap, flow_index: usize) {
@@ -198,13 +420,49 @@ impl Window {
         self.flow[flow_index].update_values(&self, &self.values[flow_index].borrow());
     }
     
+    /// Current values of the given flow, e.g., to save them with [value_map_to_json].
+    pub fn values(&self, flow_index: usize) -> ValueMap {
+        self.values[flow_index].borrow().clone()
+    }
+
     pub fn set_value(&self, key: String, value: Value, flow_index: usize) {
         self.values[flow_index].borrow_mut().insert(key, value);
         self.flow[flow_index].update_values(&self, &self.values[flow_index].borrow());
     }
     
+    /// Bypasses the node with the given identifier (or renders it again), see [Flow].
+    pub fn set_bypass(&self, id: &str, bypassed: bool, flow_index: usize) {
+        let mut bypass: Vec<Value> = match self.values[flow_index].borrow().get("bypass") {
+            Some(Value::Array(ids)) => ids.iter().filter(|other| other.as_str() != Some(id)).cloned().collect(),
+            _ => Vec::new(),
+        };
+        if bypassed {
+            bypass.push(Value::String(id.to_string()));
+        }
+        self.set_value("bypass".to_string(), Value::Array(bypass), flow_index);
+    }
+
+    /// Toggles the n-th node that can be bypassed in all flows, e.g., when pressing F1 for the first.
+    fn toggle_bypass(&self, position: usize) {
+        for (flow_index, f) in self.flow.iter().enumerate() {
+            if let Some(id) = f.bypassable().get(position) {
+                let bypassed = match self.values[flow_index].borrow().get("bypass") {
+                    Some(Value::Array(ids)) => ids.iter().any(|other| other.as_str() == Some(id)),
+                    _ => false,
+                };
+                self.set_bypass(id, !bypassed, flow_index);
+            }
+        }
+    }
+
+    /// Sets the perspective of all eyes of the given flow.
     pub fn set_perspective(&self, new_perspective: EyePerspective, flow_index: usize) {
-        self.flow[flow_index].last_perspective.replace(new_perspective);
+        self.flow[flow_index].set_perspective(new_perspective);
+    }
+
+    /// Sets the perspective of a single eye (right eye first) of a stereo flow, see [Flow::set_eye_perspective].
+    pub fn set_eye_perspective(&self, eye: usize, perspective: EyePerspective, flow_index: usize) {
+        self.flow[flow_index].set_eye_perspective(eye, perspective);
     }
 
     pub fn factory(&self) -> &RefCell<DeviceFactory> {
@@ -239,8 +497,8 @@ impl Window {
         let mut done = false;
         let mut deferred_size = None;
 
-        // Poll for window events.
-        self.events_loop.borrow_mut().poll_events(|event| {
+        // Handle window events.
+        let handle_event = |event| {
             if let glutin::Event::WindowEvent { event, .. } = event {
                 match event {
                     glutin::WindowEvent::KeyboardInput {
@@ -518,6 +776,17 @@ impl Window {
                             }
                         }
                     }
+                    glutin::WindowEvent::KeyboardInput {
+                        input:
+                            glutin::KeyboardInput {
+                                state: glutin::ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    } if BYPASS_KEYS.contains(&key) => {
+                        self.toggle_bypass(BYPASS_KEYS.iter().position(|other| *other == key).unwrap());
+                    }
                     glutin::WindowEvent::KeyboardInput {
                         input:glutin::KeyboardInput {virtual_keycode, ..}, ..
                     } => {
@@ -545,41 +814,52 @@ impl Window {
                     _ => (),
                 }
             }
-        });
+        };
+        // Poll for window events (there are none without window).
+        if let GlContext::Windowed { events_loop, .. } = &self.context {
+            events_loop.borrow_mut().poll_events(handle_event);
+        }
 
         if let Some(_) = self.forced_view {
             // Update pipline IO.
-            let _dpi_factor = self.windowed_context.window().get_hidpi_factor();
             let size = glutin::dpi::PhysicalSize {
                 width: 1920.0,
                 height: 1080.0,
             };
-            //self.windowed_context.resize(size);
-            gfx_window_glutin::update_views(
-                &self.windowed_context,
-                &mut self.render_target.borrow_mut(),
-                &mut self.main_depth.borrow_mut(),
-            );
+            if let GlContext::Windowed { context, .. } = &self.context {
+                //context.resize(size);
+                gfx_window_glutin::update_views(
+                    context,
+                    &mut self.render_target.borrow_mut(),
+                    &mut self.main_depth.borrow_mut(),
+                );
+            }
             for (i, f) in self.flow.iter().enumerate(){
-                f.negociate_slots(&self);
+                if let Err(err) = f.negociate_slots(&self) {
+                    eprintln!("Cannot update flow {}: {}", i, err);
+                    done = true;
+                }
                 f.update_values(&self, &self.values[i].borrow());
                 f.last_perspective.borrow_mut().proj = cgmath::perspective(
                     cgmath::Deg(70.0), (size.width/size.height) as f32, 0.05, 1000.0);
             }
         }
 
-        if let Some(size) = deferred_size {
+        if let (Some(size), GlContext::Windowed { context, .. }) = (deferred_size, &self.context) {
             // Update pipline IO.
-            let dpi_factor = self.windowed_context.window().get_hidpi_factor();
+            let dpi_factor = context.window().get_hidpi_factor();
             let size = size.to_physical(dpi_factor);
-            self.windowed_context.resize(size);
+            context.resize(size);
             gfx_window_glutin::update_views(
-                &self.windowed_context,
+                context,
                 &mut self.render_target.borrow_mut(),
                 &mut self.main_depth.borrow_mut(),
             );
             for (i, f) in self.flow.iter().enumerate(){
-                f.negociate_slots(&self);
+                if let Err(err) = f.negociate_slots(&self) {
+                    eprintln!("Cannot update flow {}: {}", i, err);
+                    done = true;
+                }
                 f.update_values(&self, &self.values[i].borrow());
                 f.last_perspective.borrow_mut().proj = cgmath::perspective(
                     cgmath::Deg(70.0), (size.width/size.height) as f32, 0.05, 1000.0);
@@ -589,7 +869,7 @@ impl Window {
         // Update input.
         for f in self.flow.iter(){
             if *self.override_view.borrow() || *self.override_gaze.borrow() {
-                let window_size = &self.windowed_context.window().get_inner_size().unwrap();
+                let window_size = &self.inner_size();
                 let cursor_pos = self.cursor_pos.borrow();
                 //println!("{} {}",cursor_pos.x as f32 ,cursor_pos.y as f32);
                 let view_input = match self.forced_view {
@@ -625,6 +905,15 @@ impl Window {
             }            
             f.input(&self.vis_param.borrow());
         }
+        for (i, f) in self.flow.iter().enumerate() {
+            // Allocate or free channels, e.g., when error tracking or nodes are toggled.
+            if f.needs_negociation() {
+                if let Err(err) = f.negociate_slots(&self) {
+                    eprintln!("Cannot update flow {}: {}", i, err);
+                    done = true;
+                }
+            }
+        }
         //println!("Rendered with: {:?}", self.vis_param.borrow_mut());
 
         self.encoder
@@ -637,8 +926,8 @@ impl Window {
         self.flush(&mut self.encoder().borrow_mut());
         self.device.borrow_mut().cleanup();
 
-        if *self.should_swap_buffers.borrow(){
-            self.windowed_context.swap_buffers().unwrap();
+        if let (true, GlContext::Windowed { context, .. }) = (*self.should_swap_buffers.borrow(), &self.context) {
+            context.swap_buffers().unwrap();
         }
 
         if let Some(remote) = &self.remote {